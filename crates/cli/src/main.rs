@@ -1,13 +1,344 @@
 use eyre::Result;
-use frost_snake_lib::execute;
-use std::{env, fs::File, io::BufReader};
+use frost_snake_lib::{
+    compress, decompress, load_client_config, load_withdrawal_rules, map_file, parse_parallel,
+    Compression, EventFormat, EventWriter, ExecutionReport, Header, InputFormat, Ledger,
+    LedgerConfig, Metadata, OutputFormat, ParallelOptions, ParserConfig, ParserError, Transaction,
+    TransactionDiscriminants, TransactionExecutionError, TransactionReader, WriterConfig,
+    DEFAULT_TIER,
+};
+use std::{
+    env,
+    fs::File,
+    io::BufReader,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const USAGE: &str = "Usage: file_name.csv[.gz|.zst] [--input-format csv|ndjson|binary] [--output-format csv|json|ndjson]
+             [--compress none|gzip|zstd] [--parallel] (memory map and parse CSV on all cores)
+             [--verbose] (print rows that failed to parse or were rejected to stderr)
+             [--extra-columns name,name,...] (carried along into --events and --verbose output)
+CSV dialect: [--delimiter char|tab] [--quote char] [--comment char] [--alias name=type|client|tx|amount]...
+             [--columns type,client,tx,amount] (for files without a header row)
+             [--lenient] (case insensitive, UTF-8 and unicode whitespace tolerant)
+             [--strict] (reject excess decimals, zero, empty, unexpected and out of range amounts)
+Output:      [--sort client|total|locked] [--locked-only] [--negative-only] [--clients id,id,...]
+             [--output-columns client,available,held,total,locked,deposits,withdrawals,open_disputes,
+                               charged_back,last_tx,shortfall,reserved,pending,fees,interest,overdraft_limit]
+             [--number-format fixed[:decimals]|trimmed|minor]
+Events:      [--events file.csv|ndjson[.gz|.zst]] [--event-format csv|ndjson] (stream of account changes)
+             [--stats text|json] (print an execution report to stderr)
+Policies:    [--lock-policy withdrawals|all|disputes-only|withdrawal-limit:amount] (rejected on locked accounts)
+             [--dispute-funding allow-negative|reject|hold-available|lock] (disputes exceeding available)
+             [--max-disputes N] [--resolved-is-final] (re-disputing a deposit)
+             [--reversal-unlock keep|unlock|unlock-if-settled] (reversed chargebacks)
+             [--timestamp-column name] (seconds since the epoch, the clock of timeouts and time windows)
+             [--authorization-expiry transactions] [--authorization-timeout seconds]
+             [--pending-deposits] [--clearing-period transactions] [--clearing-timeout seconds]
+Fees:        [--withdrawal-fee fixed|percent%|fixed+percent%] [--dispute-fee ...] [--chargeback-fee ...]
+             [--fee-rounding half-even|half-up|down|up] (rounding of the percentage)
+Interest:    [--interest-rate [tier=]credit%/debit%]... [--client-tier client=tier]... (per accrue row)
+             [--interest-rounding half-even|half-up|down|up]
+Clients:     [--client-config file.csv] (columns client,overdraft_limit,tier)
+             [--allow-admin-rows] (execute limit rows from the input, rejected by default)
+             [--withdrawal-rules file.csv] (columns client,max_amount,max_count,max_total)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StatsFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    file_name: Option<String>,
+    input_format: Option<InputFormat>,
+    output_format: Option<OutputFormat>,
+    /// Compression of the output, the input compression is always detected automatically
+    compress: Compression,
+    /// Print rows that failed to parse or were rejected to stderr
+    verbose: bool,
+    /// Parse CSV input on multiple threads
+    parallel: bool,
+    parser: ParserConfig,
+    writer: WriterConfig,
+    /// File to stream account change events to
+    events: Option<String>,
+    event_format: Option<EventFormat>,
+    stats: Option<StatsFormat>,
+    ledger: LedgerConfig,
+}
+
+fn parse_byte(value: &str) -> Result<u8> {
+    match value {
+        "tab" | "\\t" => Ok(b'\t'),
+        _ if value.len() == 1 => Ok(value.as_bytes()[0]),
+        _ => Err(eyre::eyre!(
+            "Expected a single ASCII character but got `{value}`\n{USAGE}"
+        )),
+    }
+}
+
+fn parse_alias(value: &str) -> Result<(String, Header)> {
+    let (alias, header) = value
+        .split_once('=')
+        .ok_or_else(|| eyre::eyre!("Expected name=column but got `{value}`\n{USAGE}"))?;
+    Ok((alias.to_string(), header.parse()?))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| eyre::eyre!("Missing value for {arg}\n{USAGE}"))
+        };
+        match arg.as_str() {
+            "--input-format" => parsed.input_format = Some(value()?.parse()?),
+            "--output-format" => parsed.output_format = Some(value()?.parse()?),
+            "--compress" => parsed.compress = value()?.parse()?,
+            "--verbose" => parsed.verbose = true,
+            "--parallel" => parsed.parallel = true,
+            "--events" => parsed.events = Some(value()?),
+            "--lock-policy" => parsed.ledger.lock_policy = value()?.parse()?,
+            "--dispute-funding" => parsed.ledger.dispute_funding = value()?.parse()?,
+            "--max-disputes" => parsed.ledger.max_disputes = Some(value()?.parse()?),
+            "--resolved-is-final" => parsed.ledger.resolved_is_final = true,
+            "--reversal-unlock" => parsed.ledger.reversal_unlock = value()?.parse()?,
+            "--pending-deposits" => parsed.ledger.pending_deposits = true,
+            "--allow-admin-rows" => parsed.ledger.allow_admin_rows = true,
+            "--clearing-period" => parsed.ledger.clearing_period = Some(value()?.parse()?),
+            "--clearing-timeout" => {
+                parsed.ledger.clearing_timeout =
+                    Some(Duration::try_from_secs_f64(value()?.parse()?)?)
+            }
+            "--timestamp-column" => parsed.ledger.timestamp_column = Some(value()?),
+            "--authorization-expiry" => {
+                parsed.ledger.authorization_expiry = Some(value()?.parse()?)
+            }
+            "--authorization-timeout" => {
+                parsed.ledger.authorization_timeout =
+                    Some(Duration::try_from_secs_f64(value()?.parse()?)?)
+            }
+            "--withdrawal-fee" => parsed.ledger.fees.withdrawal = value()?.parse()?,
+            "--dispute-fee" => parsed.ledger.fees.dispute = value()?.parse()?,
+            "--chargeback-fee" => parsed.ledger.fees.charge_back = value()?.parse()?,
+            "--fee-rounding" => parsed.ledger.fees.rounding = value()?.parse()?,
+            "--interest-rate" => {
+                let value = value()?;
+                let (tier, rate) = value.split_once('=').unwrap_or((DEFAULT_TIER, &value));
+                parsed
+                    .ledger
+                    .interest
+                    .rates
+                    .insert(tier.to_string(), rate.parse()?);
+            }
+            "--client-tier" => {
+                let value = value()?;
+                let (client, tier) = value.split_once('=').ok_or_else(|| {
+                    eyre::eyre!("Expected client=tier but got `{value}`\n{USAGE}")
+                })?;
+                parsed
+                    .ledger
+                    .interest
+                    .tiers
+                    .insert(client.parse()?, tier.to_string());
+            }
+            "--client-config" => {
+                let file = BufReader::new(File::open(value()?)?);
+                load_client_config(file, &mut parsed.ledger)?
+            }
+            "--withdrawal-rules" => {
+                let file = BufReader::new(File::open(value()?)?);
+                parsed.ledger.withdrawal_rules = load_withdrawal_rules(file)?
+            }
+            "--interest-rounding" => parsed.ledger.interest.rounding = value()?.parse()?,
+            "--event-format" => parsed.event_format = Some(value()?.parse()?),
+            "--stats" => {
+                parsed.stats = Some(match value()?.as_str() {
+                    "text" => StatsFormat::Text,
+                    "json" => StatsFormat::Json,
+                    other => return Err(eyre::eyre!("Unknown stats format `{other}`\n{USAGE}")),
+                })
+            }
+            "--lenient" => parsed.parser.lenient = true,
+            "--strict" => parsed.parser.strict = true,
+            "--delimiter" => parsed.parser.delimiter = parse_byte(&value()?)?,
+            "--quote" => parsed.parser.quote = parse_byte(&value()?)?,
+            "--comment" => parsed.parser.comment = Some(parse_byte(&value()?)?),
+            "--alias" => parsed.parser.aliases.push(parse_alias(&value()?)?),
+            "--extra-columns" => {
+                parsed.parser.extra_columns = value()?.split(',').map(str::to_string).collect()
+            }
+            "--sort" => parsed.writer.sort = value()?.parse()?,
+            "--locked-only" => parsed.writer.filter.locked_only = true,
+            "--negative-only" => parsed.writer.filter.negative_available_only = true,
+            "--clients" => {
+                parsed.writer.filter.clients = Some(
+                    value()?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            "--output-columns" => {
+                parsed.writer.columns = value()?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?
+            }
+            "--number-format" => parsed.writer.number_format = value()?.parse()?,
+            "--columns" => {
+                parsed.parser.columns = Some(
+                    value()?
+                        .split(',')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            _ if parsed.file_name.is_none() && !arg.starts_with("--") => {
+                parsed.file_name = Some(arg)
+            }
+            _ => return Err(eyre::eyre!("Unexpected argument `{arg}`\n{USAGE}")),
+        }
+    }
+
+    match &parsed.ledger.timestamp_column {
+        Some(column) if !parsed.parser.extra_columns.contains(column) => {
+            parsed.parser.extra_columns.push(column.clone())
+        }
+        None if parsed.ledger.authorization_timeout.is_some()
+            || parsed.ledger.clearing_timeout.is_some() =>
+        {
+            return Err(eyre::eyre!(
+                "Timeouts need the input's --timestamp-column\n{USAGE}"
+            ))
+        }
+        None if parsed.ledger.withdrawal_rules.uses_time() => {
+            return Err(eyre::eyre!(
+                "Time windows of withdrawal rules need the input's --timestamp-column\n{USAGE}"
+            ))
+        }
+        _ => {}
+    }
+    Ok(parsed)
+}
 
 fn main() -> Result<()> {
-    let file_name = env::args()
-        .nth(1)
-        .ok_or(eyre::eyre!("Missing argument\nUsage: file_name.csv"))?;
+    let args = parse_args(env::args().skip(1))?;
+    let file_name = args
+        .file_name
+        .ok_or(eyre::eyre!("Missing argument\n{USAGE}"))?;
+
+    let mut events = match &args.events {
+        Some(path) => {
+            let format = args
+                .event_format
+                .or_else(|| EventFormat::from_path(path))
+                .unwrap_or(EventFormat::Csv);
+            let compression = Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str()?.parse().ok())
+                .unwrap_or_default();
+            let writer = compress(File::create(path)?, compression)?;
+            Some(EventWriter::with_extra_columns(
+                writer,
+                format,
+                &args.parser.extra_columns,
+            )?)
+        }
+        None => None,
+    };
+
+    let start = Instant::now();
+    let mut ledger = Ledger::with_config(args.ledger.clone());
+    let mut report = ExecutionReport::default();
+    let execute = |transaction: Result<Transaction, ParserError>| -> std::io::Result<()> {
+        let transaction = match transaction {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                report.record_parse_error();
+                if args.verbose {
+                    print_parser_error(&e);
+                }
+                return Ok(());
+            }
+        };
+
+        let ty = TransactionDiscriminants::from(&transaction);
+        let rejected = args.verbose.then(|| {
+            let (tx, client) = (transaction.get_tx(), transaction.get_client_id());
+            (tx, client, transaction.metadata().clone())
+        });
+        let mut written = Ok(());
+        let result = match &mut events {
+            None => ledger.execute(transaction).err(),
+            Some(events) => ledger
+                .execute_with_events(transaction, |event| {
+                    if written.is_ok() {
+                        written = events.write(&event);
+                    }
+                })
+                .err(),
+        };
+        report.record(&ledger, ty, result.as_ref());
+        if let (Some(error), Some((tx, client, metadata))) = (&result, rejected) {
+            print_rejection(tx, client, &metadata, error);
+        }
+        written
+    };
+
+    let mut reader = BufReader::new(decompress(BufReader::new(File::open(&file_name)?))?);
+    let input_format = match args
+        .input_format
+        .or_else(|| InputFormat::from_path(&file_name))
+    {
+        Some(format) => format,
+        None => InputFormat::detect(&mut reader)?,
+    };
+
+    let uncompressed = reader.get_ref().compression() == Compression::None;
+    if args.parallel && input_format == InputFormat::Csv {
+        let data = map_file(&file_name)?;
+        parse_parallel(&data, &args.parser, ParallelOptions::default())?.try_for_each(execute)?;
+    } else if cfg!(feature = "fast-parser") && uncompressed && input_format == InputFormat::Csv {
+        #[cfg(feature = "fast-parser")]
+        frost_snake_lib::parse_fast(&map_file(&file_name)?, &args.parser)?.try_for_each(execute)?;
+    } else {
+        TransactionReader::with_config(reader, input_format, &args.parser)?
+            .try_for_each(execute)?;
+    }
 
-    execute(BufReader::new(File::open(file_name)?), std::io::stdout())?;
+    let mut writer = compress(std::io::stdout(), args.compress)?;
+    args.output_format
+        .unwrap_or(OutputFormat::Csv)
+        .write_with_config(&ledger, &mut writer, &args.writer)?;
+    writer.finish()?;
+    if let Some(events) = events {
+        events.into_inner()?.finish()?;
+    }
+
+    report.finish(&ledger, start.elapsed());
+    match args.stats {
+        Some(StatsFormat::Text) => eprint!("{report}"),
+        Some(StatsFormat::Json) => eprintln!("{}", report.to_json()),
+        None => {}
+    }
 
     Ok(())
 }
+
+fn print_rejection(tx: u32, client: u16, metadata: &Metadata, error: &TransactionExecutionError) {
+    if metadata.is_empty() {
+        eprintln!("rejected: tx {tx} of client {client}: {error}");
+    } else {
+        eprintln!("rejected: tx {tx} of client {client}: {error} ({metadata})");
+    }
+}
+
+fn print_parser_error(error: &ParserError) {
+    match error {
+        ParserError::InvalidRecord(record) => eprint!("error: {record}\n{}", record.excerpt()),
+        e => eprintln!("error: {e}"),
+    }
+}
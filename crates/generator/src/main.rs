@@ -1,13 +1,14 @@
 use csv::WriterBuilder;
+use frost_snake_lib::binary::{encode_record, write_binary_header};
 use frost_snake_lib::{
-    ChargeBack, Deposit, Dispute, Resolve, Transaction, TransactionDiscriminants,
-    TransactionExecutor, UCurrency, Withdrawal,
+    Authorize, Capture, ChargeBack, Deposit, Dispute, Ledger, Release, Representment, Resolve,
+    Reversal, Transaction, TransactionDiscriminants, TransactionExecutor, UCurrency, Withdrawal,
 };
 use rand::distributions::WeightedIndex;
 use rand::prelude::*;
 use std::collections::{HashMap, HashSet};
 use std::convert::Infallible;
-use std::io::{Cursor, Write};
+use std::io::{BufWriter, Cursor, Write};
 use strum::VariantNames;
 
 #[derive(Debug, Default)]
@@ -17,6 +18,13 @@ struct GeneratorState {
     ok_deposits: HashSet<usize>,
     disputed_deposits: HashSet<usize>,
     charged_back_deposits: HashSet<usize>,
+    represented_deposits: HashSet<usize>,
+    open_authorizations: HashSet<usize>,
+    authorization_tx_to_idx: HashMap<u32, usize>,
+    /// Executes the transactions to know which of the random authorizations got the funds
+    ledger: Ledger,
+    /// Whether the ledger accepted the transaction being executed
+    accepted: bool,
 }
 
 impl GeneratorState {
@@ -66,6 +74,56 @@ impl TransactionExecutor<&Resolve> for &mut GeneratorState {
         Ok(self)
     }
 }
+impl TransactionExecutor<&Representment> for &mut GeneratorState {
+    type TransactionError = Infallible;
+
+    fn execute(self, transaction: &Representment) -> Result<Self, Self::TransactionError> {
+        let idx = self.deposit_tx_to_idx.get(&transaction.tx).unwrap();
+        self.charged_back_deposits.remove(idx);
+        self.represented_deposits.insert(*idx);
+        Ok(self)
+    }
+}
+impl TransactionExecutor<&Reversal> for &mut GeneratorState {
+    type TransactionError = Infallible;
+
+    fn execute(self, transaction: &Reversal) -> Result<Self, Self::TransactionError> {
+        let idx = self.deposit_tx_to_idx.get(&transaction.tx).unwrap();
+        self.represented_deposits.remove(idx);
+        Ok(self)
+    }
+}
+impl TransactionExecutor<&Authorize> for &mut GeneratorState {
+    type TransactionError = Infallible;
+
+    fn execute(self, transaction: &Authorize) -> Result<Self, Self::TransactionError> {
+        // only successful authorizations can be captured or released
+        if self.accepted {
+            self.authorization_tx_to_idx
+                .insert(transaction.tx, self.transactions.len());
+            self.open_authorizations.insert(self.transactions.len());
+        }
+        Ok(self)
+    }
+}
+impl TransactionExecutor<&Capture> for &mut GeneratorState {
+    type TransactionError = Infallible;
+
+    fn execute(self, transaction: &Capture) -> Result<Self, Self::TransactionError> {
+        let idx = self.authorization_tx_to_idx.get(&transaction.tx).unwrap();
+        self.open_authorizations.remove(idx);
+        Ok(self)
+    }
+}
+impl TransactionExecutor<&Release> for &mut GeneratorState {
+    type TransactionError = Infallible;
+
+    fn execute(self, transaction: &Release) -> Result<Self, Self::TransactionError> {
+        let idx = self.authorization_tx_to_idx.get(&transaction.tx).unwrap();
+        self.open_authorizations.remove(idx);
+        Ok(self)
+    }
+}
 impl TransactionExecutor<&Withdrawal> for &mut GeneratorState {
     type TransactionError = Infallible;
 
@@ -78,18 +136,30 @@ impl TransactionExecutor<Transaction> for GeneratorState {
     type TransactionError = Infallible;
 
     fn execute(mut self, transaction: Transaction) -> Result<Self, Self::TransactionError> {
+        self.accepted = self.ledger.execute(transaction.clone()).is_ok();
         match &transaction {
             Transaction::Deposit(d) => (&mut self).execute(d),
             Transaction::Dispute(d) => (&mut self).execute(d),
             Transaction::ChargeBack(d) => (&mut self).execute(d),
             Transaction::Resolve(d) => (&mut self).execute(d),
             Transaction::Withdrawal(d) => (&mut self).execute(d),
+            Transaction::Representment(d) => (&mut self).execute(d),
+            Transaction::Reversal(d) => (&mut self).execute(d),
+            Transaction::Authorize(d) => (&mut self).execute(d),
+            Transaction::Capture(d) => (&mut self).execute(d),
+            Transaction::Release(d) => (&mut self).execute(d),
+            // never generated
+            Transaction::Clear(_)
+            | Transaction::Return(_)
+            | Transaction::Accrue(_)
+            | Transaction::Limit(_) => Ok(&mut self),
         }?;
         self.transactions.push(transaction);
         Ok(self)
     }
 }
 
+#[allow(clippy::needless_borrows_for_generic_args)]
 pub fn write_csv<W: Write>(
     transactions: impl Iterator<Item = Transaction>,
     writer: W,
@@ -110,6 +180,15 @@ pub fn write_csv<W: Write>(
             Transaction::ChargeBack(t) => ("chargeback", t.client, t.tx, None),
             Transaction::Resolve(t) => ("resolve", t.client, t.tx, None),
             Transaction::Withdrawal(t) => ("withdrawal", t.client, t.tx, Some(t.amount)),
+            Transaction::Representment(t) => ("representment", t.client, t.tx, None),
+            Transaction::Reversal(t) => ("reversal", t.client, t.tx, None),
+            Transaction::Authorize(t) => ("authorize", t.client, t.tx, Some(t.amount)),
+            Transaction::Capture(t) => ("capture", t.client, t.tx, Some(t.amount)),
+            Transaction::Release(t) => ("release", t.client, t.tx, None),
+            Transaction::Clear(t) => ("clear", t.client, t.tx, None),
+            Transaction::Return(t) => ("return", t.client, t.tx, None),
+            Transaction::Accrue(t) => ("accrue", t.client, t.tx, None),
+            Transaction::Limit(t) => ("limit", t.client, t.tx, Some(t.amount)),
         };
 
         if let Some(amount) = amount {
@@ -127,8 +206,29 @@ pub fn write_csv<W: Write>(
     writer.flush()
 }
 
+/// Writes the transactions in the binary format described in `frost_snake_lib::binary`
+pub fn write_binary<W: Write>(
+    transactions: impl Iterator<Item = Transaction>,
+    writer: W,
+) -> Result<(), std::io::Error> {
+    let mut writer = BufWriter::new(writer);
+    write_binary_header(&mut writer)?;
+
+    for transaction in transactions {
+        writer.write_all(&encode_record(&transaction))?;
+    }
+
+    writer.flush()
+}
+
 fn main() {
-    const WEIGHTS: [usize; Transaction::VARIANTS.len()] = [100, 2, 1, 1, 96];
+    let binary = std::env::args().skip(1).any(|arg| arg == "--binary");
+
+    // deposits clear immediately by default so there's nothing to clear or return, and no
+    // interest rates are configured by default so accruals would do nothing. Overdraft limits
+    // are left to admins.
+    const WEIGHTS: [usize; Transaction::VARIANTS.len()] =
+        [100, 2, 1, 1, 96, 1, 1, 4, 2, 2, 0, 0, 0, 0];
     let dist = WeightedIndex::new(WEIGHTS).unwrap();
     let mut rng1 = thread_rng();
     let mut rng2 = thread_rng();
@@ -179,9 +279,58 @@ fn main() {
                     rng2.gen_range(1..=max_clients),
                     UCurrency::from_bits(rng2.gen::<u64>()),
                 ),
+                TransactionDiscriminants::Representment => {
+                    if state.charged_back_deposits.is_empty() {
+                        return state;
+                    }
+                    let idx = *state
+                        .charged_back_deposits
+                        .iter()
+                        .choose(&mut rng2)
+                        .unwrap();
+                    let deposit = &state.transactions[idx];
+                    Transaction::new_representment(deposit.get_tx(), deposit.get_client_id())
+                }
+                TransactionDiscriminants::Reversal => {
+                    if state.represented_deposits.is_empty() {
+                        return state;
+                    }
+                    let idx = *state.represented_deposits.iter().choose(&mut rng2).unwrap();
+                    let deposit = &state.transactions[idx];
+                    Transaction::new_reversal(deposit.get_tx(), deposit.get_client_id())
+                }
+                TransactionDiscriminants::Authorize => Transaction::new_authorize(
+                    i,
+                    rng2.gen_range(1..=max_clients),
+                    UCurrency::from_bits(rng2.gen::<u64>()),
+                ),
+                TransactionDiscriminants::Capture => {
+                    if state.open_authorizations.is_empty() {
+                        return state;
+                    }
+                    let idx = *state.open_authorizations.iter().choose(&mut rng2).unwrap();
+                    let authorize = state.transactions[idx].as_authorize().unwrap();
+                    Transaction::new_capture(authorize.tx, authorize.client, authorize.amount)
+                }
+                TransactionDiscriminants::Release => {
+                    if state.open_authorizations.is_empty() {
+                        return state;
+                    }
+                    let idx = *state.open_authorizations.iter().choose(&mut rng2).unwrap();
+                    let authorize = &state.transactions[idx];
+                    Transaction::new_release(authorize.get_tx(), authorize.get_client_id())
+                }
+                TransactionDiscriminants::Clear
+                | TransactionDiscriminants::Return
+                | TransactionDiscriminants::Accrue
+                | TransactionDiscriminants::Limit => return state,
             };
             state.execute(transaction).unwrap()
         });
 
-    write_csv(generator.into_iter(), std::io::stdout()).unwrap();
+    if binary {
+        write_binary(generator.into_iter(), std::io::stdout()).unwrap();
+    } else {
+        write_csv(generator.into_iter(), std::io::stdout()).unwrap();
+    }
 }
@@ -0,0 +1,100 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use frost_snake_lib::{
+    binary::{encode_record, write_binary_header},
+    compress, parse_binary, parse_csv, parse_parallel, parse_with_config, Compression,
+    ParallelOptions, ParserConfig,
+};
+use std::io::Write;
+
+pub fn parser_bench(c: &mut Criterion) {
+    let path = "tests/test-cases/100k-complex.input.csv";
+    let csv = std::fs::read(path).unwrap();
+
+    let transactions = parse_csv(csv.as_slice())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let mut binary = Vec::new();
+    write_binary_header(&mut binary).unwrap();
+    for transaction in &transactions {
+        binary.extend_from_slice(&encode_record(transaction));
+    }
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(transactions.len() as u64));
+
+    group.bench_with_input(BenchmarkId::new("csv", path), &csv, |b, csv| {
+        b.iter(|| parse_csv(csv.as_slice()).unwrap().count());
+    });
+    // the default path must not be slowed down by the existence of the lenient mode, compare
+    // `csv` with a baseline saved before it as described in the README
+    group.bench_with_input(BenchmarkId::new("csv-configured", path), &csv, |b, csv| {
+        let config = ParserConfig::default();
+        b.iter(|| parse_with_config(csv.as_slice(), &config).unwrap().count());
+    });
+    // only input with extra columns pays for carrying them
+    let with_memo = csv_with_memo(&csv);
+    let extra_columns = ParserConfig {
+        extra_columns: vec!["memo".to_string()],
+        ..Default::default()
+    };
+    group.bench_with_input(
+        BenchmarkId::new("csv-extra-columns", path),
+        &with_memo,
+        |b, csv| {
+            b.iter(|| {
+                parse_with_config(csv.as_slice(), &extra_columns)
+                    .unwrap()
+                    .count()
+            });
+        },
+    );
+    let lenient = ParserConfig {
+        lenient: true,
+        ..Default::default()
+    };
+    group.bench_with_input(BenchmarkId::new("csv-lenient", path), &csv, |b, csv| {
+        b.iter(|| parse_with_config(csv.as_slice(), &lenient).unwrap().count());
+    });
+    let config = ParserConfig::default();
+    let options = ParallelOptions {
+        chunk_size: 256 * 1024,
+        ..Default::default()
+    };
+    group.bench_with_input(BenchmarkId::new("csv-parallel", path), &csv, |b, csv| {
+        b.iter(|| parse_parallel(csv, &config, options).unwrap().count());
+    });
+    #[cfg(feature = "fast-parser")]
+    group.bench_with_input(BenchmarkId::new("csv-fast", path), &csv, |b, csv| {
+        b.iter(|| frost_snake_lib::parse_fast(csv, &config).unwrap().count());
+    });
+    for compression in [Compression::Gzip, Compression::Zstd] {
+        let mut writer = compress(Vec::new(), compression).unwrap();
+        writer.write_all(&csv).unwrap();
+        let compressed = writer.finish().unwrap();
+        let name = format!("csv.{}", compression.extension().unwrap());
+
+        group.bench_with_input(BenchmarkId::new(name, path), &compressed, |b, input| {
+            b.iter(|| parse_csv(input.as_slice()).unwrap().count());
+        });
+    }
+    group.bench_with_input(BenchmarkId::new("binary", path), &binary, |b, binary| {
+        b.iter(|| parse_binary(binary.as_slice()).unwrap().count());
+    });
+}
+
+/// Appends a `memo` column to every row of `csv`
+fn csv_with_memo(csv: &[u8]) -> Vec<u8> {
+    let mut lines = csv.split(|b| *b == b'\n').filter(|line| !line.is_empty());
+    let mut output = Vec::with_capacity(csv.len() * 2);
+    output.extend_from_slice(lines.next().unwrap());
+    output.extend_from_slice(b",memo\n");
+    for line in lines {
+        output.extend_from_slice(line);
+        output.extend_from_slice(b",payment\n");
+    }
+    output
+}
+
+criterion_group!(benches, parser_bench);
+criterion_main!(benches);
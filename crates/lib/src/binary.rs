@@ -0,0 +1,240 @@
+//! Compact fixed width binary transaction format intended for machine to machine traffic.
+//!
+//! A file starts with an 8 byte header:
+//!
+//! | offset | size | content                  |
+//! |--------|------|--------------------------|
+//! | 0      | 4    | magic bytes `FSTX`       |
+//! | 4      | 1    | format version, always 1 |
+//! | 5      | 3    | reserved, must be zero   |
+//!
+//! Followed by any number of 15 byte records, all integers are little endian:
+//!
+//! | offset | size | content                                         |
+//! |--------|------|-------------------------------------------------|
+//! | 0      | 1    | transaction type, see the `TYPE_*` constants    |
+//! | 1      | 2    | client id                                       |
+//! | 3      | 4    | tx id                                           |
+//! | 7      | 8    | raw bits of the [`UCurrency`] amount, 0 if none |
+use crate::{parser::ParserError, transaction::Transaction, UCurrency};
+use std::io::{BufRead, Write};
+
+pub const BINARY_MAGIC: [u8; 4] = *b"FSTX";
+pub const BINARY_VERSION: u8 = 1;
+pub const BINARY_HEADER_LEN: usize = 8;
+pub const BINARY_RECORD_LEN: usize = 15;
+
+pub const TYPE_DEPOSIT: u8 = 0;
+pub const TYPE_DISPUTE: u8 = 1;
+pub const TYPE_CHARGE_BACK: u8 = 2;
+pub const TYPE_RESOLVE: u8 = 3;
+pub const TYPE_WITHDRAWAL: u8 = 4;
+pub const TYPE_REPRESENTMENT: u8 = 5;
+pub const TYPE_REVERSAL: u8 = 6;
+pub const TYPE_AUTHORIZE: u8 = 7;
+pub const TYPE_CAPTURE: u8 = 8;
+pub const TYPE_RELEASE: u8 = 9;
+pub const TYPE_CLEAR: u8 = 10;
+pub const TYPE_RETURN: u8 = 11;
+pub const TYPE_ACCRUE: u8 = 12;
+pub const TYPE_LIMIT: u8 = 13;
+
+pub fn write_binary_header<W: Write>(mut writer: W) -> Result<(), std::io::Error> {
+    let mut header = [0u8; BINARY_HEADER_LEN];
+    header[..4].copy_from_slice(&BINARY_MAGIC);
+    header[4] = BINARY_VERSION;
+    writer.write_all(&header)
+}
+
+pub fn encode_record(transaction: &Transaction) -> [u8; BINARY_RECORD_LEN] {
+    let (ty, client, tx, amount) = match transaction {
+        Transaction::Deposit(t) => (TYPE_DEPOSIT, t.client, t.tx, t.amount.to_bits()),
+        Transaction::Dispute(t) => (TYPE_DISPUTE, t.client, t.tx, 0),
+        Transaction::ChargeBack(t) => (TYPE_CHARGE_BACK, t.client, t.tx, 0),
+        Transaction::Resolve(t) => (TYPE_RESOLVE, t.client, t.tx, 0),
+        Transaction::Withdrawal(t) => (TYPE_WITHDRAWAL, t.client, t.tx, t.amount.to_bits()),
+        Transaction::Representment(t) => (TYPE_REPRESENTMENT, t.client, t.tx, 0),
+        Transaction::Reversal(t) => (TYPE_REVERSAL, t.client, t.tx, 0),
+        Transaction::Authorize(t) => (TYPE_AUTHORIZE, t.client, t.tx, t.amount.to_bits()),
+        Transaction::Capture(t) => (TYPE_CAPTURE, t.client, t.tx, t.amount.to_bits()),
+        Transaction::Release(t) => (TYPE_RELEASE, t.client, t.tx, 0),
+        Transaction::Clear(t) => (TYPE_CLEAR, t.client, t.tx, 0),
+        Transaction::Return(t) => (TYPE_RETURN, t.client, t.tx, 0),
+        Transaction::Accrue(t) => (TYPE_ACCRUE, t.client, t.tx, 0),
+        Transaction::Limit(t) => (TYPE_LIMIT, t.client, t.tx, t.amount.to_bits()),
+    };
+
+    let mut record = [0u8; BINARY_RECORD_LEN];
+    record[0] = ty;
+    record[1..3].copy_from_slice(&client.to_le_bytes());
+    record[3..7].copy_from_slice(&tx.to_le_bytes());
+    record[7..15].copy_from_slice(&amount.to_le_bytes());
+    record
+}
+
+#[inline]
+pub fn decode_record(record: &[u8; BINARY_RECORD_LEN]) -> Result<Transaction, ParserError> {
+    let client = u16::from_le_bytes([record[1], record[2]]);
+    let tx = u32::from_le_bytes([record[3], record[4], record[5], record[6]]);
+    let mut amount = [0u8; 8];
+    amount.copy_from_slice(&record[7..15]);
+    let amount = UCurrency::from_bits(u64::from_le_bytes(amount));
+
+    Ok(match record[0] {
+        TYPE_DEPOSIT => Transaction::new_deposit(tx, client, amount),
+        TYPE_DISPUTE => Transaction::new_dispute(tx, client),
+        TYPE_CHARGE_BACK => Transaction::new_charge_back(tx, client),
+        TYPE_RESOLVE => Transaction::new_resolve(tx, client),
+        TYPE_WITHDRAWAL => Transaction::new_withdrawal(tx, client, amount),
+        TYPE_REPRESENTMENT => Transaction::new_representment(tx, client),
+        TYPE_REVERSAL => Transaction::new_reversal(tx, client),
+        TYPE_AUTHORIZE => Transaction::new_authorize(tx, client, amount),
+        TYPE_CAPTURE => Transaction::new_capture(tx, client, amount),
+        TYPE_RELEASE => Transaction::new_release(tx, client),
+        TYPE_CLEAR => Transaction::new_clear(tx, client),
+        TYPE_RETURN => Transaction::new_return(tx, client),
+        TYPE_ACCRUE => Transaction::new_accrue(tx, client),
+        TYPE_LIMIT => Transaction::new_limit(tx, client, amount),
+        ty => return Err(ParserError::InvalidTypeByte(ty)),
+    })
+}
+
+pub(crate) struct TransactionBinaryIterator<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> TransactionBinaryIterator<R> {
+    pub(crate) fn new(mut reader: R) -> Result<Self, ParserError> {
+        let mut header = [0u8; BINARY_HEADER_LEN];
+        reader.read_exact(&mut header)?;
+
+        if header[..4] != BINARY_MAGIC || header[5..] != [0; 3] {
+            return Err(ParserError::InvalidBinaryHeader);
+        }
+        if header[4] != BINARY_VERSION {
+            return Err(ParserError::UnsupportedBinaryVersion(header[4]));
+        }
+
+        Ok(Self { reader })
+    }
+}
+
+impl<R: BufRead> Iterator for TransactionBinaryIterator<R> {
+    type Item = Result<Transaction, ParserError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        // Fast path, the whole record is already buffered
+        let buf = match self.reader.fill_buf() {
+            Ok([]) => return None, //EOF
+            Ok(buf) => buf,
+            Err(e) => return Some(Err(e.into())),
+        };
+        if let Some(record) = buf.get(..BINARY_RECORD_LEN) {
+            let result = decode_record(record.try_into().unwrap());
+            self.reader.consume(BINARY_RECORD_LEN);
+            return Some(result);
+        }
+
+        // The record straddles the buffer boundary
+        let mut record = [0u8; BINARY_RECORD_LEN];
+        Some(
+            self.reader
+                .read_exact(&mut record)
+                .map_err(ParserError::from)
+                .and_then(|_| decode_record(&record)),
+        )
+    }
+}
+
+/// Parses the binary format described in the [module documentation](self).
+pub fn parse_binary(
+    reader: impl BufRead,
+) -> Result<impl Iterator<Item = Result<Transaction, ParserError>>, ParserError> {
+    TransactionBinaryIterator::new(reader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+    use std::io::BufReader;
+
+    fn encode_all(transactions: &[Transaction]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_binary_header(&mut buf).unwrap();
+        for transaction in transactions {
+            buf.extend_from_slice(&encode_record(transaction));
+        }
+        buf
+    }
+
+    #[test]
+    fn round_trips() {
+        let transactions = vec![
+            Transaction::new_deposit(1, 2, ucur!(10.0001)),
+            Transaction::new_withdrawal(3, 2, ucur!(1.5)),
+            Transaction::new_dispute(1, 2),
+            Transaction::new_resolve(1, 2),
+            Transaction::new_charge_back(1, 2),
+            Transaction::new_representment(1, 2),
+            Transaction::new_reversal(1, 2),
+            Transaction::new_authorize(4, 2, ucur!(3)),
+            Transaction::new_capture(4, 2, ucur!(1.25)),
+            Transaction::new_release(4, 2),
+            Transaction::new_clear(1, 2),
+            Transaction::new_return(1, 2),
+            Transaction::new_accrue(5, 0),
+            Transaction::new_limit(6, 2, ucur!(250)),
+            Transaction::new_deposit(u32::MAX, u16::MAX, UCurrency::MAX),
+        ];
+        let buf = encode_all(&transactions);
+        assert_eq!(
+            buf.len(),
+            BINARY_HEADER_LEN + transactions.len() * BINARY_RECORD_LEN
+        );
+
+        // a tiny buffer forces records to straddle the buffer boundary
+        let decoded = parse_binary(BufReader::with_capacity(16, buf.as_slice()))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(decoded, transactions);
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        assert!(matches!(
+            parse_binary(&b"FSTY\x01\0\0\0"[..]),
+            Err(ParserError::InvalidBinaryHeader)
+        ));
+        assert!(matches!(
+            parse_binary(&b"FSTX\x02\0\0\0"[..]),
+            Err(ParserError::UnsupportedBinaryVersion(2))
+        ));
+        assert!(matches!(
+            parse_binary(&b"FSTX  "[..]),
+            Err(ParserError::InvalidBinaryHeader)
+        ));
+        assert!(matches!(
+            parse_binary(&b"FSTX"[..]),
+            Err(ParserError::IOError(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_records() {
+        let mut buf = encode_all(&[Transaction::new_dispute(1, 1)]);
+        buf[BINARY_HEADER_LEN] = 42;
+        let mut it = parse_binary(buf.as_slice()).unwrap();
+        assert!(matches!(
+            it.next(),
+            Some(Err(ParserError::InvalidTypeByte(42)))
+        ));
+        assert!(it.next().is_none());
+
+        let buf = encode_all(&[Transaction::new_dispute(1, 1)]);
+        let mut it = parse_binary(&buf[..buf.len() - 1]).unwrap();
+        assert!(matches!(it.next(), Some(Err(ParserError::IOError(_)))));
+    }
+}
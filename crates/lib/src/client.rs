@@ -1,10 +1,14 @@
 use crate::{
-    transaction::{ChargeBack, Deposit, Dispute, Resolve, Withdrawal},
+    policy::{DisputeFunding, LockPolicy, Operation},
+    transaction::{
+        Authorize, Capture, ChargeBack, Clear, Deposit, Dispute, Limit, Release, Representment,
+        Resolve, Return, Reversal, Withdrawal,
+    },
     DepositState, ICurrency, UCurrency,
 };
 use thiserror::Error;
 
-#[derive(Error, Debug, PartialEq, Eq)]
+#[derive(Error, Debug, PartialEq, Eq, strum_macros::IntoStaticStr)]
 pub enum TransactionExecutionError {
     #[error("Inssuficient funds in account")]
     InsufficientFunds,
@@ -22,6 +26,26 @@ pub enum TransactionExecutionError {
         expected_state: DepositState,
         actual_state: DepositState,
     },
+    #[error("The deposit tx = {0} reached the maximum number of disputes")]
+    DisputeLimitReached(u32),
+    #[error("The authorization tx = {0}, was not found")]
+    AuthorizationNotFound(u32),
+    #[error("The authorization tx = {0} already exists")]
+    DuplicateAuthorization(u32),
+    #[error("The capture exceeds the amount left of the authorization tx = {0}")]
+    ExceedsAuthorization(u32),
+    #[error("The transaction exceeds the overdraft limit of the account")]
+    OverLimit,
+    #[error("The withdrawal exceeds the maximum amount per transaction")]
+    AmountLimitExceeded,
+    #[error("The maximum number of withdrawals in the window was reached")]
+    WithdrawalCountExceeded,
+    #[error("The withdrawal exceeds the maximum amount withdrawn in the window")]
+    WithdrawalTotalExceeded,
+    #[error("Admin transactions aren't allowed in the input")]
+    AdminRowsNotAllowed,
+    #[error("The timestamp `{0}` isn't a whole number of seconds")]
+    InvalidTimestamp(String),
     #[error("Action resulted in an overflow")]
     Overflow,
     #[error("Action resulted in an underflow")]
@@ -34,6 +58,14 @@ pub struct ClientAccount {
     pub locked: bool,
     pub available: ICurrency,
     pub held: UCurrency,
+    /// Disputed amounts that weren't available to hold, see [`DisputeFunding::HoldAvailable`]
+    pub shortfall: UCurrency,
+    /// Funds reserved by open authorizations, they're part of the total but not available
+    pub reserved: UCurrency,
+    /// Deposits that haven't cleared yet, they're part of the total but not available
+    pub pending: UCurrency,
+    /// Approved credit line, withdrawals may take `available` down to minus this
+    pub overdraft_limit: UCurrency,
 }
 
 impl ClientAccount {
@@ -46,11 +78,34 @@ impl ClientAccount {
 
     pub fn total(&self) -> ICurrency {
         // Panic on the edge case that the client has +- ~140 trillion assets...
-        self.available.checked_add_unsigned(self.held).unwrap()
+        self.available
+            .checked_add_unsigned(self.held)
+            .and_then(|total| total.checked_add_unsigned(self.reserved))
+            .and_then(|total| total.checked_add_unsigned(self.pending))
+            .unwrap()
+    }
+
+    /// Fails if `available` went further below zero than the overdraft limit allows, with
+    /// [`TransactionExecutionError::OverLimit`] if the account has a limit at all
+    fn check_overdraft(&self) -> Result<(), TransactionExecutionError> {
+        if self.available >= ICurrency::ZERO
+            || self.available.unsigned_abs() <= self.overdraft_limit
+        {
+            Ok(())
+        } else if self.overdraft_limit == UCurrency::ZERO {
+            Err(TransactionExecutionError::InsufficientFunds)
+        } else {
+            Err(TransactionExecutionError::OverLimit)
+        }
     }
 
-    pub fn deposit(mut self, deposit: Deposit) -> Result<Self, TransactionExecutionError> {
+    pub fn deposit(
+        mut self,
+        deposit: Deposit,
+        policy: &LockPolicy,
+    ) -> Result<Self, TransactionExecutionError> {
         assert_eq!(self.id, deposit.client);
+        policy.check(self.locked, Operation::Deposit, deposit.amount)?;
 
         self.available = self
             .available
@@ -60,24 +115,134 @@ impl ClientAccount {
         Ok(self)
     }
 
-    pub fn withdraw(mut self, withdrawal: Withdrawal) -> Result<Self, TransactionExecutionError> {
-        assert_eq!(self.id, withdrawal.client);
+    /// Takes `fee` from the available funds, `allow_negative` for fees that can't be refused,
+    /// otherwise only down to the overdraft limit
+    pub fn charge_fee(
+        mut self,
+        fee: UCurrency,
+        allow_negative: bool,
+    ) -> Result<Self, TransactionExecutionError> {
+        self.available = self
+            .available
+            .checked_sub_unsigned(fee)
+            .ok_or(TransactionExecutionError::Underflow)?;
+
+        if !allow_negative {
+            self.check_overdraft()?;
+        }
+
+        Ok(self)
+    }
 
-        // This is not defined in the specification but it does not
-        // make sense if money can be withdrawn from a locked account
-        if self.locked {
-            return Err(TransactionExecutionError::AccountLocked);
+    /// Posts accrued interest to the available funds, negative `interest` is charged
+    pub fn accrue(mut self, interest: ICurrency) -> Result<Self, TransactionExecutionError> {
+        self.available =
+            self.available
+                .checked_add(interest)
+                .ok_or(if interest < ICurrency::ZERO {
+                    TransactionExecutionError::Underflow
+                } else {
+                    TransactionExecutionError::Overflow
+                })?;
+        Ok(self)
+    }
+
+    /// Like [`ClientAccount::deposit`] but the funds only become available once the deposit clears
+    pub fn deposit_pending(
+        mut self,
+        deposit: Deposit,
+        policy: &LockPolicy,
+    ) -> Result<Self, TransactionExecutionError> {
+        assert_eq!(self.id, deposit.client);
+        policy.check(self.locked, Operation::Deposit, deposit.amount)?;
+
+        self.pending = self
+            .pending
+            .checked_add(deposit.amount)
+            .ok_or(TransactionExecutionError::Overflow)?;
+
+        Ok(self)
+    }
+
+    /// Makes a pending deposit available
+    pub fn clear(
+        self,
+        clear: Clear,
+        amount: UCurrency,
+        deposit_state: DepositState,
+        policy: &LockPolicy,
+    ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Clear, amount)?;
+        if deposit_state != DepositState::Pending {
+            return Err(TransactionExecutionError::InvalidDepositState {
+                tx: clear.tx,
+                expected_state: DepositState::Pending,
+                actual_state: deposit_state,
+            });
         }
 
+        Ok((self.settle_pending(amount)?, DepositState::Ok))
+    }
+
+    /// Like [`ClientAccount::clear`] but for deposits clearing on their own, which can't be refused
+    pub(crate) fn settle_pending(
+        mut self,
+        amount: UCurrency,
+    ) -> Result<Self, TransactionExecutionError> {
         self.available = self
             .available
-            .checked_sub_unsigned(withdrawal.amount)
-            .ok_or(TransactionExecutionError::Underflow)?;
+            .checked_add_unsigned(amount)
+            .ok_or(TransactionExecutionError::Overflow)?;
+        self.pending = self
+            .pending
+            .checked_sub(amount)
+            .expect("pending should never underflow");
+
+        Ok(self)
+    }
 
-        if self.available < ICurrency::ZERO {
-            return Err(TransactionExecutionError::InsufficientFunds);
+    /// Drops a pending deposit that failed to clear
+    pub fn return_deposit(
+        mut self,
+        deposit_return: Return,
+        amount: UCurrency,
+        deposit_state: DepositState,
+        policy: &LockPolicy,
+    ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Return, amount)?;
+        if deposit_state != DepositState::Pending {
+            return Err(TransactionExecutionError::InvalidDepositState {
+                tx: deposit_return.tx,
+                expected_state: DepositState::Pending,
+                actual_state: deposit_state,
+            });
         }
 
+        self.pending = self
+            .pending
+            .checked_sub(amount)
+            .expect("pending should never underflow");
+
+        Ok((self, DepositState::Returned))
+    }
+
+    pub fn withdraw(
+        mut self,
+        withdrawal: Withdrawal,
+        policy: &LockPolicy,
+    ) -> Result<Self, TransactionExecutionError> {
+        assert_eq!(self.id, withdrawal.client);
+
+        // This is not defined in the specification, by default
+        // money can't be withdrawn from a locked account
+        policy.check(self.locked, Operation::Withdrawal, withdrawal.amount)?;
+
+        self.available = self
+            .available
+            .checked_sub_unsigned(withdrawal.amount)
+            .ok_or(TransactionExecutionError::Underflow)?;
+        self.check_overdraft()?;
+
         Ok(self)
     }
 
@@ -86,7 +251,10 @@ impl ClientAccount {
         dispute: Dispute,
         amount: UCurrency,
         deposit_state: DepositState,
+        policy: &LockPolicy,
+        funding: DisputeFunding,
     ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Dispute, amount)?;
         if deposit_state != DepositState::Ok {
             return Err(TransactionExecutionError::InvalidDepositState {
                 tx: dispute.tx,
@@ -95,25 +263,50 @@ impl ClientAccount {
             });
         }
 
+        let funded = self
+            .available
+            .max(ICurrency::ZERO)
+            .to_num::<UCurrency>()
+            .min(amount);
+        let mut held = amount;
+        if funded < amount {
+            match funding {
+                DisputeFunding::AllowNegative => {}
+                DisputeFunding::Reject => return Err(TransactionExecutionError::InsufficientFunds),
+                DisputeFunding::HoldAvailable => {
+                    held = funded;
+                    self.shortfall = self
+                        .shortfall
+                        .checked_add(amount - funded)
+                        .ok_or(TransactionExecutionError::Overflow)?;
+                }
+                DisputeFunding::Lock => self.locked = true,
+            }
+        }
+
         self.available = self
             .available
-            .checked_sub_unsigned(amount)
+            .checked_sub_unsigned(held)
             .ok_or(TransactionExecutionError::Underflow)?;
 
         self.held = self
             .held
-            .checked_add(amount)
+            .checked_add(held)
             .ok_or(TransactionExecutionError::Overflow)?;
 
         Ok((self, DepositState::Disputed))
     }
 
+    /// `shortfall` is the part of `amount` that wasn't held when the deposit was disputed
     pub fn resolve(
         mut self,
         resolve: Resolve,
         amount: UCurrency,
         deposit_state: DepositState,
+        policy: &LockPolicy,
+        shortfall: UCurrency,
     ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Resolve, amount)?;
         if deposit_state != DepositState::Disputed {
             return Err(TransactionExecutionError::InvalidDepositState {
                 tx: resolve.tx,
@@ -122,25 +315,35 @@ impl ClientAccount {
             });
         }
 
+        let held = amount - shortfall;
         self.available = self
             .available
-            .checked_add_unsigned(amount)
+            .checked_add_unsigned(held)
             .ok_or(TransactionExecutionError::Overflow)?;
 
         self.held = self
             .held
-            .checked_sub(amount)
+            .checked_sub(held)
             .expect("held should never underflow");
+        self.shortfall = self
+            .shortfall
+            .checked_sub(shortfall)
+            .expect("shortfall should never underflow");
 
         Ok((self, DepositState::Ok))
     }
 
+    /// `shortfall` is the part of `amount` that wasn't held when the deposit was disputed,
+    /// it stays on the account as a receivable.
     pub fn charge_back(
         mut self,
         charge_back: ChargeBack,
         amount: UCurrency,
         deposit_state: DepositState,
+        policy: &LockPolicy,
+        shortfall: UCurrency,
     ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::ChargeBack, amount)?;
         if deposit_state != DepositState::Disputed {
             return Err(TransactionExecutionError::InvalidDepositState {
                 tx: charge_back.tx,
@@ -151,36 +354,191 @@ impl ClientAccount {
 
         self.held = self
             .held
-            .checked_sub(amount)
+            .checked_sub(amount - shortfall)
             .expect("held should never underflow");
 
         self.locked = true;
 
         Ok((self, DepositState::ChargedBack))
     }
+
+    /// Moves `amount` from `available` to `reserved`, failing like a withdrawal would
+    pub fn authorize(
+        mut self,
+        authorize: Authorize,
+        policy: &LockPolicy,
+    ) -> Result<Self, TransactionExecutionError> {
+        assert_eq!(self.id, authorize.client);
+        policy.check(self.locked, Operation::Authorize, authorize.amount)?;
+
+        self.available = self
+            .available
+            .checked_sub_unsigned(authorize.amount)
+            .ok_or(TransactionExecutionError::Underflow)?;
+        self.check_overdraft()?;
+
+        self.reserved = self
+            .reserved
+            .checked_add(authorize.amount)
+            .ok_or(TransactionExecutionError::Overflow)?;
+
+        Ok(self)
+    }
+
+    /// Withdraws part of the `remaining` reserved funds of an authorization,
+    /// returns the account and what remains of the authorization.
+    /// Being a withdrawal, a capture has to pass the policy's withdrawal rule as well.
+    pub fn capture(
+        mut self,
+        capture: Capture,
+        remaining: UCurrency,
+        policy: &LockPolicy,
+    ) -> Result<(Self, UCurrency), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Withdrawal, capture.amount)?;
+        policy.check(self.locked, Operation::Capture, capture.amount)?;
+        if capture.amount > remaining {
+            return Err(TransactionExecutionError::ExceedsAuthorization(capture.tx));
+        }
+
+        self.reserved = self
+            .reserved
+            .checked_sub(capture.amount)
+            .expect("reserved should never underflow");
+
+        Ok((self, remaining - capture.amount))
+    }
+
+    /// Makes the `remaining` reserved funds of an authorization available again
+    pub fn release(
+        self,
+        _release: Release,
+        remaining: UCurrency,
+        policy: &LockPolicy,
+    ) -> Result<Self, TransactionExecutionError> {
+        policy.check(self.locked, Operation::Release, remaining)?;
+        self.unreserve(remaining)
+    }
+
+    /// Like [`ClientAccount::release`] but for authorizations that expired, which can't be refused
+    pub(crate) fn unreserve(
+        mut self,
+        remaining: UCurrency,
+    ) -> Result<Self, TransactionExecutionError> {
+        self.available = self
+            .available
+            .checked_add_unsigned(remaining)
+            .ok_or(TransactionExecutionError::Overflow)?;
+        self.reserved = self
+            .reserved
+            .checked_sub(remaining)
+            .expect("reserved should never underflow");
+
+        Ok(self)
+    }
+
+    /// Contests the chargeback of a deposit, the funds only move once it's reversed
+    pub fn represent(
+        self,
+        representment: Representment,
+        amount: UCurrency,
+        deposit_state: DepositState,
+        policy: &LockPolicy,
+    ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Representment, amount)?;
+        if deposit_state != DepositState::ChargedBack {
+            return Err(TransactionExecutionError::InvalidDepositState {
+                tx: representment.tx,
+                expected_state: DepositState::ChargedBack,
+                actual_state: deposit_state,
+            });
+        }
+
+        Ok((self, DepositState::Represented))
+    }
+
+    /// Credits the charged back funds again. `shortfall` is the part of `amount` that was never
+    /// held, it's taken off the account's receivable instead. The account is unlocked if `unlock`.
+    pub fn reverse(
+        mut self,
+        reversal: Reversal,
+        amount: UCurrency,
+        deposit_state: DepositState,
+        policy: &LockPolicy,
+        shortfall: UCurrency,
+        unlock: bool,
+    ) -> Result<(Self, DepositState), TransactionExecutionError> {
+        policy.check(self.locked, Operation::Reversal, amount)?;
+        if deposit_state != DepositState::Represented {
+            return Err(TransactionExecutionError::InvalidDepositState {
+                tx: reversal.tx,
+                expected_state: DepositState::Represented,
+                actual_state: deposit_state,
+            });
+        }
+
+        self.available = self
+            .available
+            .checked_add_unsigned(amount - shortfall)
+            .ok_or(TransactionExecutionError::Overflow)?;
+        self.shortfall = self
+            .shortfall
+            .checked_sub(shortfall)
+            .expect("shortfall should never underflow");
+
+        if unlock {
+            self.locked = false;
+        }
+
+        Ok((self, DepositState::Reversed))
+    }
+
+    /// Sets the overdraft limit to the amount of the admin transaction `limit`, zero removes it
+    pub fn set_limit(
+        mut self,
+        limit: Limit,
+        policy: &LockPolicy,
+    ) -> Result<Self, TransactionExecutionError> {
+        policy.check(self.locked, Operation::Limit, limit.amount)?;
+        self.overdraft_limit = limit.amount;
+        Ok(self)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ClientAccount;
-    use crate::ChargeBack;
-    use crate::{icur, ucur, Deposit, DepositState, Dispute, Resolve, Withdrawal};
+    use super::{ClientAccount, TransactionExecutionError};
+    use crate::{icur, ucur, Deposit, DepositState, Dispute, Metadata, Resolve, Withdrawal};
+    use crate::{Authorize, Capture, ChargeBack, Limit, Release, Representment, Reversal};
+    use crate::{DisputeFunding, LockPolicy};
 
     #[allow(non_upper_case_globals)] //make it easier to construct ClientAccount
     const client: u16 = 1;
 
+    fn policy() -> LockPolicy {
+        LockPolicy::default()
+    }
+
     #[test]
     fn can_deposit() {
         let tx = 1;
         let amount = ucur!(1);
-        let deposit = Deposit { tx, client, amount };
+        let deposit = Deposit {
+            tx,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        };
         assert_eq!(
-            ClientAccount::new(client).deposit(deposit),
+            ClientAccount::new(client).deposit(deposit, &policy()),
             Ok(ClientAccount {
                 id: client,
                 held: ucur!(0),
                 available: icur!(1),
                 locked: false,
+                shortfall: ucur!(0),
+                reserved: ucur!(0),
+                pending: ucur!(0),
+                overdraft_limit: ucur!(0),
             })
         );
     }
@@ -193,32 +551,87 @@ mod tests {
                 id: client,
                 ..Default::default()
             }
-            .withdraw(Withdrawal {
-                tx: 1,
-                client,
-                amount: ucur!(1)
-            }),
+            .withdraw(
+                Withdrawal {
+                    tx: 1,
+                    client,
+                    amount: ucur!(1),
+                    metadata: Metadata::default(),
+                },
+                &policy()
+            ),
             Ok(ClientAccount {
                 id: client,
                 locked: false,
+                shortfall: ucur!(0),
+                reserved: ucur!(0),
+                pending: ucur!(0),
+                overdraft_limit: ucur!(0),
                 available: icur!(0),
                 held: ucur!(0),
             })
         );
     }
 
+    #[test]
+    fn withdrawals_can_use_the_overdraft_limit() {
+        let withdraw = |account: ClientAccount, amount| {
+            account.withdraw(
+                Withdrawal {
+                    tx: 1,
+                    client,
+                    amount,
+                    metadata: Metadata::default(),
+                },
+                &policy(),
+            )
+        };
+        let account = ClientAccount {
+            id: client,
+            available: icur!(1),
+            overdraft_limit: ucur!(10),
+            ..Default::default()
+        };
+        assert_eq!(
+            withdraw(account, ucur!(11)).map(|account| account.available),
+            Ok(icur!(-10))
+        );
+        assert_eq!(
+            withdraw(account, ucur!(11.0001)),
+            Err(TransactionExecutionError::OverLimit)
+        );
+        let account = ClientAccount {
+            overdraft_limit: ucur!(0),
+            ..account
+        };
+        assert_eq!(
+            withdraw(account, ucur!(1.0001)),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+    }
+
     #[test]
     fn can_dispute() {
         assert_eq!(
             ClientAccount::new(client).dispute(
-                Dispute { tx: 1, client },
+                Dispute {
+                    tx: 1,
+                    client,
+                    metadata: Metadata::default(),
+                },
                 ucur!(1.0),
-                DepositState::Ok
+                DepositState::Ok,
+                &policy(),
+                DisputeFunding::AllowNegative
             ),
             Ok((
                 ClientAccount {
                     id: client,
                     locked: false,
+                    shortfall: ucur!(0),
+                    reserved: ucur!(0),
+                    pending: ucur!(0),
+                    overdraft_limit: ucur!(0),
                     available: icur!(-1),
                     held: ucur!(1),
                 },
@@ -236,14 +649,24 @@ mod tests {
                 ..Default::default()
             }
             .resolve(
-                Resolve { tx: 1, client },
+                Resolve {
+                    tx: 1,
+                    client,
+                    metadata: Metadata::default(),
+                },
                 ucur!(1.0),
-                DepositState::Disputed
+                DepositState::Disputed,
+                &policy(),
+                ucur!(0)
             ),
             Ok((
                 ClientAccount {
                     id: client,
                     locked: false,
+                    shortfall: ucur!(0),
+                    reserved: ucur!(0),
+                    pending: ucur!(0),
+                    overdraft_limit: ucur!(0),
                     available: icur!(1),
                     held: ucur!(0),
                 },
@@ -260,14 +683,24 @@ mod tests {
                 ..Default::default()
             }
             .charge_back(
-                ChargeBack { tx: 1, client },
+                ChargeBack {
+                    tx: 1,
+                    client,
+                    metadata: Metadata::default(),
+                },
                 ucur!(1.0),
-                DepositState::Disputed
+                DepositState::Disputed,
+                &policy(),
+                ucur!(0)
             ),
             Ok((
                 ClientAccount {
                     id: client,
                     locked: true,
+                    shortfall: ucur!(0),
+                    reserved: ucur!(0),
+                    pending: ucur!(0),
+                    overdraft_limit: ucur!(0),
                     available: icur!(0),
                     held: ucur!(0),
                 },
@@ -282,29 +715,526 @@ mod tests {
         let tx = 1;
         let amount = ucur!(1);
         assert_eq!(acc.total(), icur!(0));
-        acc = acc.deposit(Deposit { tx, client, amount }).unwrap();
+        acc = acc
+            .deposit(
+                Deposit {
+                    tx,
+                    client,
+                    amount,
+                    metadata: Metadata::default(),
+                },
+                &policy(),
+            )
+            .unwrap();
         assert_eq!(acc.total(), amount);
         acc = acc
-            .withdraw(Withdrawal {
-                tx: 1,
-                client,
-                amount,
-            })
+            .withdraw(
+                Withdrawal {
+                    tx: 1,
+                    client,
+                    amount,
+                    metadata: Metadata::default(),
+                },
+                &policy(),
+            )
             .unwrap();
         assert_eq!(acc.total(), icur!(0));
 
         (acc, _) = acc
-            .deposit(Deposit { tx, client, amount })
+            .deposit(
+                Deposit {
+                    tx,
+                    client,
+                    amount,
+                    metadata: Metadata::default(),
+                },
+                &policy(),
+            )
             .unwrap()
-            .dispute(Dispute { tx, client }, amount, DepositState::Ok)
+            .dispute(
+                Dispute {
+                    tx,
+                    client,
+                    metadata: Metadata::default(),
+                },
+                amount,
+                DepositState::Ok,
+                &policy(),
+                DisputeFunding::AllowNegative,
+            )
             .unwrap();
 
         assert_eq!(acc.total(), amount);
 
         (acc, _) = acc
-            .charge_back(ChargeBack { tx, client }, amount, DepositState::Disputed)
+            .charge_back(
+                ChargeBack {
+                    tx,
+                    client,
+                    metadata: Metadata::default(),
+                },
+                amount,
+                DepositState::Disputed,
+                &policy(),
+                ucur!(0),
+            )
             .unwrap();
 
         assert_eq!(acc.total(), icur!(0));
     }
+
+    #[test]
+    fn consults_lock_policy() {
+        let locked = ClientAccount {
+            id: client,
+            locked: true,
+            available: icur!(5),
+            ..Default::default()
+        };
+        let deposit = Deposit {
+            tx: 1,
+            client,
+            amount: ucur!(1),
+            metadata: Metadata::default(),
+        };
+        assert_eq!(
+            locked.deposit(deposit.clone(), &LockPolicy::FROZEN),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+        assert!(locked.deposit(deposit, &LockPolicy::default()).is_ok());
+
+        let withdrawal = Withdrawal {
+            tx: 2,
+            client,
+            amount: ucur!(1),
+            metadata: Metadata::default(),
+        };
+        assert_eq!(
+            locked.withdraw(withdrawal.clone(), &LockPolicy::default()),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+        assert_eq!(
+            locked.withdraw(withdrawal, &LockPolicy::withdrawal_limit(ucur!(1))),
+            Ok(ClientAccount {
+                available: icur!(4),
+                ..locked
+            })
+        );
+    }
+
+    #[test]
+    fn frozen_accounts_reject_everything() {
+        let locked = ClientAccount {
+            id: client,
+            locked: true,
+            available: icur!(5),
+            held: ucur!(2),
+            reserved: ucur!(2),
+            ..Default::default()
+        };
+        let frozen = LockPolicy::FROZEN;
+        let (amount, metadata) = (ucur!(1), Metadata::default());
+        let results = [
+            locked
+                .deposit(
+                    Deposit {
+                        tx: 1,
+                        client,
+                        amount,
+                        metadata: metadata.clone(),
+                    },
+                    &frozen,
+                )
+                .map(|_| ()),
+            locked
+                .withdraw(
+                    Withdrawal {
+                        tx: 2,
+                        client,
+                        amount,
+                        metadata: metadata.clone(),
+                    },
+                    &frozen,
+                )
+                .map(|_| ()),
+            locked
+                .dispute(
+                    Dispute {
+                        tx: 1,
+                        client,
+                        metadata: metadata.clone(),
+                    },
+                    amount,
+                    DepositState::Ok,
+                    &frozen,
+                    DisputeFunding::AllowNegative,
+                )
+                .map(|_| ()),
+            locked
+                .resolve(
+                    Resolve {
+                        tx: 1,
+                        client,
+                        metadata: metadata.clone(),
+                    },
+                    amount,
+                    DepositState::Disputed,
+                    &frozen,
+                    ucur!(0),
+                )
+                .map(|_| ()),
+            locked
+                .charge_back(
+                    ChargeBack {
+                        tx: 1,
+                        client,
+                        metadata: metadata.clone(),
+                    },
+                    amount,
+                    DepositState::Disputed,
+                    &frozen,
+                    ucur!(0),
+                )
+                .map(|_| ()),
+            locked
+                .authorize(
+                    Authorize {
+                        tx: 3,
+                        client,
+                        amount,
+                        metadata: metadata.clone(),
+                    },
+                    &frozen,
+                )
+                .map(|_| ()),
+            locked
+                .capture(
+                    Capture {
+                        tx: 3,
+                        client,
+                        amount,
+                        metadata: metadata.clone(),
+                    },
+                    ucur!(2),
+                    &frozen,
+                )
+                .map(|_| ()),
+            locked
+                .release(
+                    Release {
+                        tx: 3,
+                        client,
+                        metadata: metadata.clone(),
+                    },
+                    ucur!(2),
+                    &frozen,
+                )
+                .map(|_| ()),
+            locked
+                .set_limit(
+                    Limit {
+                        tx: 4,
+                        client,
+                        amount,
+                        metadata,
+                    },
+                    &frozen,
+                )
+                .map(|_| ()),
+        ];
+        for result in results {
+            assert_eq!(result, Err(TransactionExecutionError::AccountLocked));
+        }
+
+        // nothing is rejected while the account isn't locked
+        let unlocked = ClientAccount {
+            locked: false,
+            ..locked
+        };
+        let deposit = Deposit {
+            tx: 1,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        };
+        assert!(unlocked.deposit(deposit, &frozen).is_ok());
+    }
+
+    #[test]
+    fn default_policy_only_blocks_withdrawals_and_authorizations() {
+        let locked = ClientAccount {
+            id: client,
+            locked: true,
+            available: icur!(5),
+            ..Default::default()
+        };
+        let (amount, metadata) = (ucur!(1), Metadata::default());
+        let deposit = Deposit {
+            tx: 1,
+            client,
+            amount,
+            metadata: metadata.clone(),
+        };
+        let dispute = Dispute {
+            tx: 1,
+            client,
+            metadata: metadata.clone(),
+        };
+        let authorize = Authorize {
+            tx: 3,
+            client,
+            amount,
+            metadata: metadata.clone(),
+        };
+        assert!(locked.deposit(deposit, &policy()).is_ok());
+        assert!(locked
+            .dispute(
+                dispute,
+                amount,
+                DepositState::Ok,
+                &policy(),
+                DisputeFunding::AllowNegative
+            )
+            .is_ok());
+        assert_eq!(
+            locked.authorize(authorize, &policy()),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+
+        // capturing an authorization made before the lock is still a withdrawal
+        let locked = ClientAccount {
+            reserved: ucur!(2),
+            ..locked
+        };
+        let capture = Capture {
+            tx: 3,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        };
+        assert_eq!(
+            locked.capture(capture.clone(), ucur!(2), &policy()),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+        assert!(locked
+            .capture(capture, ucur!(2), &LockPolicy::withdrawal_limit(amount))
+            .is_ok());
+    }
+
+    #[test]
+    fn dispute_funding_only_matters_without_funds() {
+        let dispute = Dispute {
+            tx: 1,
+            client,
+            metadata: Metadata::default(),
+        };
+        let account = ClientAccount {
+            id: client,
+            available: icur!(3),
+            ..Default::default()
+        };
+        for funding in [
+            DisputeFunding::AllowNegative,
+            DisputeFunding::Reject,
+            DisputeFunding::HoldAvailable,
+            DisputeFunding::Lock,
+        ] {
+            assert_eq!(
+                account.dispute(
+                    dispute.clone(),
+                    ucur!(3),
+                    DepositState::Ok,
+                    &policy(),
+                    funding
+                ),
+                Ok((
+                    ClientAccount {
+                        available: icur!(0),
+                        held: ucur!(3),
+                        ..account
+                    },
+                    DepositState::Disputed
+                ))
+            );
+        }
+
+        let dispute = |funding| {
+            account
+                .dispute(
+                    dispute.clone(),
+                    ucur!(5),
+                    DepositState::Ok,
+                    &policy(),
+                    funding,
+                )
+                .map(|(account, _)| account)
+        };
+        assert_eq!(
+            dispute(DisputeFunding::Reject),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        assert_eq!(
+            dispute(DisputeFunding::HoldAvailable),
+            Ok(ClientAccount {
+                available: icur!(0),
+                held: ucur!(3),
+                shortfall: ucur!(2),
+                ..account
+            })
+        );
+        assert_eq!(
+            dispute(DisputeFunding::Lock),
+            Ok(ClientAccount {
+                available: icur!(-2),
+                held: ucur!(5),
+                locked: true,
+                ..account
+            })
+        );
+    }
+
+    #[test]
+    fn can_reverse_charge_back() {
+        let charged_back = ClientAccount {
+            id: client,
+            locked: true,
+            available: icur!(-1),
+            shortfall: ucur!(2),
+            ..Default::default()
+        };
+        let (represented, state) = charged_back
+            .represent(
+                Representment {
+                    tx: 1,
+                    client,
+                    metadata: Metadata::default(),
+                },
+                ucur!(5),
+                DepositState::ChargedBack,
+                &LockPolicy::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            (represented, state),
+            (charged_back, DepositState::Represented)
+        );
+
+        let reversal = Reversal {
+            tx: 1,
+            client,
+            metadata: Metadata::default(),
+        };
+        let reverse = |state, unlock| {
+            represented.reverse(
+                reversal.clone(),
+                ucur!(5),
+                state,
+                &LockPolicy::default(),
+                ucur!(2),
+                unlock,
+            )
+        };
+        assert_eq!(
+            reverse(DepositState::Represented, false),
+            Ok((
+                ClientAccount {
+                    available: icur!(2),
+                    shortfall: ucur!(0),
+                    reserved: ucur!(0),
+                    pending: ucur!(0),
+                    overdraft_limit: ucur!(0),
+                    ..charged_back
+                },
+                DepositState::Reversed
+            ))
+        );
+        assert!(!reverse(DepositState::Represented, true).unwrap().0.locked);
+        assert_eq!(
+            reverse(DepositState::ChargedBack, true),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx: 1,
+                expected_state: DepositState::Represented,
+                actual_state: DepositState::ChargedBack
+            })
+        );
+        assert_eq!(
+            reverse(DepositState::Represented, true),
+            represented.reverse(
+                reversal.clone(),
+                ucur!(5),
+                DepositState::Represented,
+                &LockPolicy::DISPUTES_ONLY,
+                ucur!(2),
+                true
+            )
+        );
+        assert_eq!(
+            represented.reverse(
+                reversal,
+                ucur!(5),
+                DepositState::Represented,
+                &LockPolicy::FROZEN,
+                ucur!(2),
+                true
+            ),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+    }
+
+    #[test]
+    fn can_authorize_capture_and_release() {
+        let account = ClientAccount {
+            id: client,
+            available: icur!(5),
+            ..Default::default()
+        };
+        let authorized = account
+            .authorize(
+                Authorize {
+                    tx: 2,
+                    client,
+                    amount: ucur!(3),
+                    metadata: Metadata::default(),
+                },
+                &policy(),
+            )
+            .unwrap();
+        assert_eq!(
+            authorized,
+            ClientAccount {
+                available: icur!(2),
+                reserved: ucur!(3),
+                ..account
+            }
+        );
+
+        let capture = |amount| Capture {
+            tx: 2,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        };
+        assert_eq!(
+            authorized.capture(capture(ucur!(3.0001)), ucur!(3), &policy()),
+            Err(TransactionExecutionError::ExceedsAuthorization(2))
+        );
+        let (captured, remaining) = authorized
+            .capture(capture(ucur!(1)), ucur!(3), &policy())
+            .unwrap();
+        assert_eq!(remaining, ucur!(2));
+        assert_eq!(captured.total(), icur!(4));
+
+        let release = Release {
+            tx: 2,
+            client,
+            metadata: Metadata::default(),
+        };
+        assert_eq!(
+            captured.release(release, remaining, &policy()),
+            Ok(ClientAccount {
+                available: icur!(4),
+                ..account
+            })
+        );
+    }
 }
@@ -0,0 +1,89 @@
+//! Per client settings loaded from a CSV file, see [`load_client_config`].
+use crate::{
+    ledger::LedgerConfig,
+    parser::{Header, ParserError},
+    UCurrency,
+};
+use std::io::Read;
+
+/// Reads per client settings into `config` from CSV with a header row, a `client` column and any
+/// of the optional columns:
+///
+/// * `overdraft_limit`, see [`LedgerConfig::overdraft_limits`]
+/// * `tier`, see [`InterestConfig::tiers`](crate::InterestConfig::tiers)
+///
+/// Empty values leave the setting of the client alone.
+pub fn load_client_config(reader: impl Read, config: &mut LedgerConfig) -> Result<(), ParserError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let client_column = column("client").ok_or(ParserError::MissingHeader(Header::Client))?;
+    let (limit_column, tier_column) = (column("overdraft_limit"), column("tier"));
+    if let Some(unknown) = headers
+        .iter()
+        .find(|header| !["client", "overdraft_limit", "tier"].contains(header))
+    {
+        return Err(ParserError::UnknownHeader(unknown.to_string()));
+    }
+
+    for record in reader.records() {
+        let record = record?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+        };
+        let client = field(Some(client_column))
+            .unwrap_or_default()
+            .parse::<u16>()?;
+        if let Some(limit) = field(limit_column) {
+            config
+                .overdraft_limits
+                .insert(client, limit.parse::<UCurrency>()?);
+        }
+        if let Some(tier) = field(tier_column) {
+            config.interest.tiers.insert(client, tier.to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    #[test]
+    fn loads_limits_and_tiers() {
+        let mut config = LedgerConfig::default();
+        load_client_config(
+            "client, overdraft_limit, tier\n1, 100.5, gold\n2, , silver\n3, 20,\n".as_bytes(),
+            &mut config,
+        )
+        .unwrap();
+        assert_eq!(config.overdraft_limits.len(), 2);
+        assert_eq!(config.overdraft_limits[&1], ucur!(100.5));
+        assert_eq!(config.overdraft_limits[&3], ucur!(20));
+        assert_eq!(config.interest.tiers[&2], "silver");
+        assert!(!config.interest.tiers.contains_key(&3));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let load = |input: &str| load_client_config(input.as_bytes(), &mut LedgerConfig::default());
+        assert!(matches!(
+            load("overdraft_limit\n1\n"),
+            Err(ParserError::MissingHeader(Header::Client))
+        ));
+        assert!(matches!(
+            load("client,limit\n1,1\n"),
+            Err(ParserError::UnknownHeader(header)) if header == "limit"
+        ));
+        assert!(matches!(
+            load("client,overdraft_limit\n1,-5\n"),
+            Err(ParserError::CurrencyParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,189 @@
+//! Transparent gzip and zstd support for both input and output.
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder};
+use std::{
+    io::{BufRead, Read, Write},
+    str::FromStr,
+};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Detects the compression from the leading magic bytes of a stream
+    pub fn from_magic(magic: &[u8]) -> Self {
+        if magic.starts_with(&GZIP_MAGIC) {
+            Self::Gzip
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Self::Zstd
+        } else {
+            Self::None
+        }
+    }
+
+    /// File extension, without the dot, conventionally used for the compression
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some("gz"),
+            Self::Zstd => Some("zst"),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = crate::UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "gzip" | "gz" => Ok(Self::Gzip),
+            "zstd" | "zst" => Ok(Self::Zstd),
+            _ => Err(crate::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// A reader that decompresses on the fly, see [`decompress`]
+pub enum Decompressed<R: BufRead> {
+    Plain(R),
+    Gzip(Box<MultiGzDecoder<R>>),
+    Zstd(Box<zstd::Decoder<'static, R>>),
+}
+
+impl<R: BufRead> Decompressed<R> {
+    pub fn compression(&self) -> Compression {
+        match self {
+            Self::Plain(_) => Compression::None,
+            Self::Gzip(_) => Compression::Gzip,
+            Self::Zstd(_) => Compression::Zstd,
+        }
+    }
+}
+
+impl<R: BufRead> Read for Decompressed<R> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(r) => r.read(buf),
+            Self::Gzip(r) => r.read(buf),
+            Self::Zstd(r) => r.read(buf),
+        }
+    }
+}
+
+/// Wraps `reader` in a streaming decoder if it starts with gzip or zstd magic bytes,
+/// uncompressed input is passed through untouched. The magic bytes are looked for in what the
+/// first [`BufRead::fill_buf`] returns without consuming anything.
+pub fn decompress<R: BufRead>(mut reader: R) -> std::io::Result<Decompressed<R>> {
+    Ok(match Compression::from_magic(reader.fill_buf()?) {
+        Compression::None => Decompressed::Plain(reader),
+        Compression::Gzip => Decompressed::Gzip(Box::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Decompressed::Zstd(Box::new(zstd::Decoder::with_buffer(reader)?)),
+    })
+}
+
+/// A writer that compresses on the fly, see [`compress`].
+/// [`Compressed::finish`] must be called to write the trailing bytes of the stream.
+pub enum Compressed<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> Compressed<W> {
+    /// Finishes the compressed stream and returns the underlying writer
+    pub fn finish(self) -> std::io::Result<W> {
+        match self {
+            Self::Plain(mut w) => {
+                w.flush()?;
+                Ok(w)
+            }
+            Self::Gzip(w) => w.finish(),
+            Self::Zstd(w) => w.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Compressed<W> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Gzip(w) => w.write(buf),
+            Self::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Gzip(w) => w.flush(),
+            Self::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+pub fn compress<W: Write>(writer: W, compression: Compression) -> std::io::Result<Compressed<W>> {
+    Ok(match compression {
+        Compression::None => Compressed::Plain(writer),
+        Compression::Gzip => {
+            Compressed::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+        }
+        Compression::Zstd => Compressed::Zstd(zstd::Encoder::new(writer, 0)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "type,client,tx,amount\ndeposit,1,1,1.0\n";
+
+    fn round_trip(compression: Compression) {
+        let mut writer = compress(Vec::new(), compression).unwrap();
+        writer.write_all(INPUT.as_bytes()).unwrap();
+        let compressed = writer.finish().unwrap();
+
+        assert_eq!(Compression::from_magic(&compressed), compression);
+
+        let mut reader = decompress(compressed.as_slice()).unwrap();
+        assert_eq!(reader.compression(), compression);
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, INPUT);
+    }
+
+    #[test]
+    fn can_round_trip() {
+        round_trip(Compression::None);
+        round_trip(Compression::Gzip);
+        round_trip(Compression::Zstd);
+    }
+
+    #[test]
+    fn passes_through_uncompressed_readers() {
+        let input = INPUT.as_bytes();
+        assert!(matches!(
+            decompress(input).unwrap(),
+            Decompressed::Plain(reader) if reader == input
+        ));
+    }
+
+    #[test]
+    fn passes_through_short_input() {
+        let mut output = String::new();
+        decompress("a".as_bytes())
+            .unwrap()
+            .read_to_string(&mut output)
+            .unwrap();
+        assert_eq!(output, "a");
+    }
+}
@@ -0,0 +1,400 @@
+//! Stream of account changes emitted while transactions are executed, so downstream systems
+//! can mirror the ledger incrementally instead of re-reading the final snapshot.
+use crate::{format::UnknownFormat, ClientAccount, Metadata};
+use csv::{ByteRecord, WriterBuilder};
+use std::{
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// What changed in an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Deposited,
+    Withdrawn,
+    Disputed,
+    Resolved,
+    ChargedBack,
+    Represented,
+    Reversed,
+    Authorized,
+    Captured,
+    Released,
+    /// An open authorization was released because it expired, `tx` is the authorization
+    Expired,
+    /// A pending deposit became available
+    Cleared,
+    /// A pending deposit failed to clear
+    Returned,
+    /// A fee was charged for the transaction `tx`, see [`Ledger::fees`](crate::Ledger::fees)
+    Fee,
+    /// Interest was paid or charged by the accrual run `tx`, see
+    /// [`Ledger::accrue`](crate::Ledger::accrue)
+    Accrued,
+    /// The overdraft limit of the account was set by an admin transaction
+    LimitChanged,
+    /// Emitted after the change that locked the account, with the same balances
+    Locked,
+    /// Emitted after the change that unlocked the account, with the same balances
+    Unlocked,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Deposited => "deposited",
+            EventKind::Withdrawn => "withdrawn",
+            EventKind::Disputed => "disputed",
+            EventKind::Resolved => "resolved",
+            EventKind::ChargedBack => "charged_back",
+            EventKind::Represented => "represented",
+            EventKind::Reversed => "reversed",
+            EventKind::Authorized => "authorized",
+            EventKind::Captured => "captured",
+            EventKind::Released => "released",
+            EventKind::Expired => "expired",
+            EventKind::Cleared => "cleared",
+            EventKind::Returned => "returned",
+            EventKind::Fee => "fee",
+            EventKind::Accrued => "accrued",
+            EventKind::LimitChanged => "limit_changed",
+            EventKind::Locked => "locked",
+            EventKind::Unlocked => "unlocked",
+        }
+    }
+}
+
+/// A single change of an account, see [`Ledger::execute_with_events`](crate::Ledger::execute_with_events)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountEvent {
+    pub kind: EventKind,
+    /// The transaction causing the change
+    pub tx: u32,
+    pub before: ClientAccount,
+    pub after: ClientAccount,
+    /// [`Transaction::metadata`](crate::Transaction::metadata) of the transaction causing the
+    /// change, empty for the changes the ledger makes on its own like [`EventKind::Expired`]
+    pub metadata: Metadata,
+}
+
+/// Supported formats for writing events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventFormat {
+    Csv,
+    NdJson,
+}
+
+impl EventFormat {
+    /// Guesses the format from the file extension, returns `None` for unknown extensions.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match crate::format::extension(path.as_ref())?.as_str() {
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::NdJson),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for EventFormat {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "ndjson" | "jsonl" => Ok(Self::NdJson),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+const COLUMNS: [&str; 9] = [
+    "event",
+    "client",
+    "tx",
+    "available_before",
+    "held_before",
+    "total_before",
+    "available_after",
+    "held_after",
+    "total_after",
+];
+
+enum Inner<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    NdJson(BufWriter<W>),
+}
+
+/// Writes [`AccountEvent`]s one record per event as they happen.
+/// Amounts are written with 4 decimals like the account output.
+pub struct EventWriter<W: Write> {
+    inner: Inner<W>,
+    buf: Vec<u8>,
+    record: ByteRecord,
+    /// Metadata columns written after [`COLUMNS`]
+    extra_columns: Vec<String>,
+}
+
+impl<W: Write> EventWriter<W> {
+    /// Creates the writer, the CSV header is written right away
+    pub fn new(writer: W, format: EventFormat) -> Result<Self, std::io::Error> {
+        Self::with_extra_columns(writer, format, &[])
+    }
+
+    /// Like [`EventWriter::new`] but also writes the given columns of
+    /// [`AccountEvent::metadata`], empty for events without them
+    pub fn with_extra_columns(
+        writer: W,
+        format: EventFormat,
+        extra_columns: &[String],
+    ) -> Result<Self, std::io::Error> {
+        let inner = match format {
+            EventFormat::Csv => {
+                let mut writer = WriterBuilder::new().from_writer(writer);
+                let mut header = ByteRecord::from(COLUMNS.to_vec());
+                extra_columns
+                    .iter()
+                    .for_each(|column| header.push_field(column.as_bytes()));
+                writer.write_byte_record(&header)?;
+                Inner::Csv(Box::new(writer))
+            }
+            EventFormat::NdJson => Inner::NdJson(BufWriter::new(writer)),
+        };
+        Ok(Self {
+            inner,
+            buf: Vec::new(),
+            record: ByteRecord::new(),
+            extra_columns: extra_columns.to_vec(),
+        })
+    }
+
+    /// Writes the values of `event` into `buf` in the order of [`COLUMNS`], returns the end of each
+    fn values(buf: &mut Vec<u8>, event: &AccountEvent) -> Result<[usize; 9], std::io::Error> {
+        let mut ends = [0; 9];
+        buf.clear();
+        buf.extend_from_slice(event.kind.name().as_bytes());
+        ends[0] = buf.len();
+        buf.extend_from_slice(itoa::Buffer::new().format(event.after.id).as_bytes());
+        ends[1] = buf.len();
+        buf.extend_from_slice(itoa::Buffer::new().format(event.tx).as_bytes());
+        ends[2] = buf.len();
+        for (i, account) in [event.before, event.after].iter().enumerate() {
+            write!(buf, "{:.4}", account.available)?;
+            ends[3 + i * 3] = buf.len();
+            write!(buf, "{:.4}", account.held)?;
+            ends[4 + i * 3] = buf.len();
+            write!(buf, "{:.4}", account.total())?;
+            ends[5 + i * 3] = buf.len();
+        }
+        Ok(ends)
+    }
+
+    pub fn write(&mut self, event: &AccountEvent) -> Result<(), std::io::Error> {
+        let ends = Self::values(&mut self.buf, event)?;
+        let values = ends.iter().scan(0, |start, end| {
+            let value = &self.buf[*start..*end];
+            *start = *end;
+            Some(value)
+        });
+
+        let extras = self
+            .extra_columns
+            .iter()
+            .map(|column| (column, event.metadata.get(column).unwrap_or_default()));
+
+        match &mut self.inner {
+            Inner::Csv(writer) => {
+                self.record.clear();
+                values.for_each(|value| self.record.push_field(value));
+                extras.for_each(|(_, value)| self.record.push_field(value.as_bytes()));
+                writer.write_byte_record(&self.record)?;
+            }
+            Inner::NdJson(writer) => {
+                for (i, (column, value)) in COLUMNS.iter().zip(values).enumerate() {
+                    writer.write_all(if i == 0 { b"{\"" } else { b",\"" })?;
+                    writer.write_all(column.as_bytes())?;
+                    writer.write_all(b"\":")?;
+                    if i == 0 {
+                        // the only string value
+                        writer.write_all(b"\"")?;
+                        writer.write_all(value)?;
+                        writer.write_all(b"\"")?;
+                    } else {
+                        writer.write_all(value)?;
+                    }
+                }
+                for (column, value) in extras {
+                    writer.write_all(b",")?;
+                    serde_json::to_writer(&mut *writer, column)?;
+                    writer.write_all(b":")?;
+                    serde_json::to_writer(&mut *writer, value)?;
+                }
+                writer.write_all(b"}\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), std::io::Error> {
+        match &mut self.inner {
+            Inner::Csv(writer) => writer.flush(),
+            Inner::NdJson(writer) => writer.flush(),
+        }
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn into_inner(self) -> Result<W, std::io::Error> {
+        match self.inner {
+            Inner::Csv(writer) => writer
+                .into_inner()
+                .map_err(|e| std::io::Error::new(e.error().kind(), e.error().to_string())),
+            Inner::NdJson(writer) => writer.into_inner().map_err(|e| e.into_error()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ucur, Ledger, Transaction};
+
+    fn events(transactions: Vec<Transaction>) -> Vec<AccountEvent> {
+        let mut ledger = Ledger::default();
+        let mut events = Vec::new();
+        for transaction in transactions {
+            ledger
+                .execute_with_events(transaction, |e| events.push(e))
+                .ok();
+        }
+        events
+    }
+
+    #[test]
+    fn emits_events_for_successful_changes() {
+        let events = events(vec![
+            Transaction::new_deposit(1, 1, ucur!(2)),
+            Transaction::new_withdrawal(2, 1, ucur!(5)),
+            Transaction::new_withdrawal(3, 1, ucur!(1)),
+            Transaction::new_dispute(1, 1),
+            Transaction::new_resolve(1, 1),
+            Transaction::new_dispute(1, 1),
+            Transaction::new_charge_back(1, 1),
+        ]);
+        let kinds = events.iter().map(|e| (e.kind, e.tx)).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (EventKind::Deposited, 1),
+                (EventKind::Withdrawn, 3),
+                (EventKind::Disputed, 1),
+                (EventKind::Resolved, 1),
+                (EventKind::Disputed, 1),
+                (EventKind::ChargedBack, 1),
+                (EventKind::Locked, 1),
+            ]
+        );
+        assert_eq!(events[0].before, ClientAccount::new(1));
+        for pair in events.windows(2) {
+            if pair[1].kind != EventKind::Locked {
+                assert_eq!(pair[0].after, pair[1].before);
+            }
+        }
+        assert!(events[6].after.locked && !events[6].before.locked);
+    }
+
+    #[test]
+    fn emits_unlocked_for_reversals() {
+        let mut ledger = Ledger::with_config(crate::LedgerConfig {
+            reversal_unlock: crate::ReversalUnlock::Unlock,
+            ..Default::default()
+        });
+        let mut kinds = Vec::new();
+        for transaction in [
+            Transaction::new_deposit(1, 1, ucur!(2)),
+            Transaction::new_dispute(1, 1),
+            Transaction::new_charge_back(1, 1),
+            Transaction::new_representment(1, 1),
+            Transaction::new_reversal(1, 1),
+        ] {
+            ledger
+                .execute_with_events(transaction, |e| kinds.push(e.kind))
+                .unwrap();
+        }
+        assert_eq!(
+            kinds[4..],
+            [
+                EventKind::Represented,
+                EventKind::Reversed,
+                EventKind::Unlocked
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_csv_and_ndjson() {
+        let events = events(vec![
+            Transaction::new_deposit(1, 1, ucur!(2)),
+            Transaction::new_dispute(1, 1),
+        ]);
+        let write = |format| {
+            let mut writer = EventWriter::new(Vec::new(), format).unwrap();
+            events.iter().for_each(|e| writer.write(e).unwrap());
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            write(EventFormat::Csv),
+            "event,client,tx,available_before,held_before,total_before,available_after,held_after,total_after\n\
+             deposited,1,1,0.0000,0.0000,0.0000,2.0000,0.0000,2.0000\n\
+             disputed,1,1,2.0000,0.0000,2.0000,0.0000,2.0000,2.0000\n"
+        );
+        let ndjson = write(EventFormat::NdJson);
+        assert_eq!(
+            ndjson.lines().next().unwrap(),
+            "{\"event\":\"deposited\",\"client\":1,\"tx\":1,\
+             \"available_before\":0.0000,\"held_before\":0.0000,\"total_before\":0.0000,\
+             \"available_after\":2.0000,\"held_after\":0.0000,\"total_after\":2.0000}"
+        );
+        for line in ndjson.lines() {
+            serde_json::from_str::<serde_json::Value>(line).unwrap();
+        }
+    }
+
+    #[test]
+    fn writes_metadata_columns() {
+        let mut deposit = Transaction::new_deposit(1, 1, ucur!(2));
+        deposit.metadata_mut().insert("memo", "say \"hi\"");
+        deposit.metadata_mut().insert("timestamp", "1700000000");
+        let events = events(vec![deposit, Transaction::new_dispute(1, 1)]);
+        assert_eq!(events[0].metadata.get("memo"), Some("say \"hi\""));
+        assert!(events[1].metadata.is_empty());
+
+        let columns = ["memo".to_string()];
+        let write = |format| {
+            let mut writer = EventWriter::with_extra_columns(Vec::new(), format, &columns).unwrap();
+            events.iter().for_each(|e| writer.write(e).unwrap());
+            String::from_utf8(writer.into_inner().unwrap()).unwrap()
+        };
+
+        let csv = write(EventFormat::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert!(lines[0].ends_with(",total_after,memo"));
+        assert!(lines[1].ends_with(",2.0000,\"say \"\"hi\"\"\""));
+        assert!(lines[2].ends_with(",2.0000,"));
+
+        let ndjson = write(EventFormat::NdJson);
+        let values = ndjson
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values[0]["memo"], "say \"hi\"");
+        assert_eq!(values[1]["memo"], "");
+    }
+
+    #[test]
+    fn detects_format_from_extension() {
+        assert_eq!(EventFormat::from_path("e.csv.gz"), Some(EventFormat::Csv));
+        assert_eq!(EventFormat::from_path("e.jsonl"), Some(EventFormat::NdJson));
+        assert_eq!(EventFormat::from_path("e.json"), None);
+    }
+}
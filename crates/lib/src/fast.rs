@@ -0,0 +1,364 @@
+//! Hand written zero copy CSV scanner specialised for the transaction schema, enabled by the
+//! `fast-parser` feature.
+//!
+//! Records are found with [`memchr`] and the fields are parsed straight from the input bytes,
+//! integers and amounts without going through `&str` first. Only the common case is handled
+//! here: any record the scanner doesn't fully understand is handed to the regular `csv` based
+//! parser, seeked to that record, so the results including errors and their positions are
+//! always identical to [`parse_with_config`](crate::parse_with_config).
+//! [`ParserConfig::lenient`], [`ParserConfig::strict`] and [`ParserConfig::extra_columns`]
+//! always use the regular parser.
+use crate::{
+    parser::{FieldToIndexMap, ParserConfig, ParserError, TransactionCsvIterator},
+    scanner::{RecordScanner, ScannedRecord},
+    transaction::Transaction,
+    UCurrency,
+};
+use memchr::{memchr, memchr2_iter};
+use std::io::Cursor;
+
+/// Fractional bits of [`UCurrency`]
+const FRAC_BITS: u32 = 16;
+/// Decimals that can be converted without the exact rounding of the `fixed` parser
+const MAX_DECIMALS: usize = 4;
+
+enum Outcome {
+    Parsed(Transaction),
+    /// Something the scanner doesn't handle, parse the record with `csv` instead
+    Delegate,
+    /// A quoted field, which may span multiple lines, so `csv` has to take over from here
+    Quoted,
+}
+
+#[inline]
+fn trim(mut field: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = field {
+        field = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = field {
+        field = rest;
+    }
+    field
+}
+
+/// Parses a non empty run of ASCII digits, at most 19 so it can't overflow
+#[inline]
+fn parse_digits(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || digits.len() > 19 {
+        return None;
+    }
+    digits.iter().try_fold(0u64, |acc, b| {
+        b.is_ascii_digit().then(|| acc * 10 + u64::from(b - b'0'))
+    })
+}
+
+#[inline]
+fn parse_int<T: TryFrom<u64>>(field: &[u8]) -> Option<T> {
+    parse_digits(trim(field))?.try_into().ok()
+}
+
+/// Parses `123` or `123.4567` directly into the bits of a [`UCurrency`]
+#[inline]
+fn parse_amount(field: &[u8]) -> Option<UCurrency> {
+    let field = trim(field);
+    let (integral, fraction) = match memchr(b'.', field) {
+        Some(dot) => (&field[..dot], Some(&field[dot + 1..])),
+        None => (field, None),
+    };
+
+    let integral = parse_digits(integral).filter(|i| *i < 1 << (64 - FRAC_BITS))?;
+    let fraction = match fraction {
+        None => 0,
+        Some(fraction) if fraction.len() <= MAX_DECIMALS => {
+            let scaled =
+                parse_digits(fraction)? * 10u64.pow((MAX_DECIMALS - fraction.len()) as u32);
+            // rounds to nearest, there are no ties with 4 decimals and 16 fractional bits
+            ((scaled << FRAC_BITS) + 5_000) / 10_000
+        }
+        Some(_) => return None,
+    };
+
+    Some(UCurrency::from_bits(integral << FRAC_BITS | fraction))
+}
+
+struct Scanner<'a> {
+    data: &'a [u8],
+    config: &'a ParserConfig,
+    records: RecordScanner<'a>,
+    field_map: FieldToIndexMap,
+    /// Fields every record must have, from the header or the first record of headerless files
+    field_count: Option<usize>,
+}
+
+impl<'a> Scanner<'a> {
+    #[inline]
+    fn parse(&self, record: &[u8]) -> Outcome {
+        let map = self.field_map;
+        let (mut ty, mut tx, mut client, mut amount) = (None, None, None, None);
+
+        let mut count = 0;
+        let mut start = 0;
+        let ends = memchr2_iter(self.config.delimiter, self.config.quote, record);
+        for end in ends.chain(std::iter::once(record.len())) {
+            if end < record.len() && record[end] == self.config.quote {
+                return Outcome::Quoted;
+            }
+            let field = &record[start..end];
+            match u8::try_from(count) {
+                Ok(i) if i == map.ty => ty = Some(field),
+                Ok(i) if i == map.tx => tx = Some(field),
+                Ok(i) if i == map.client => client = Some(field),
+                Ok(i) if i == map.amount => amount = Some(field),
+                _ => {}
+            }
+            count += 1;
+            start = end + 1;
+        }
+        if self.field_count != Some(count) {
+            return Outcome::Delegate;
+        }
+
+        let parsed = (|| {
+            let tx = parse_int(tx?)?;
+            let client = parse_int(client?)?;
+            Some(match trim(ty?) {
+                b"deposit" => Transaction::new_deposit(tx, client, parse_amount(amount?)?),
+                b"withdrawal" => Transaction::new_withdrawal(tx, client, parse_amount(amount?)?),
+                b"dispute" => Transaction::new_dispute(tx, client),
+                b"resolve" => Transaction::new_resolve(tx, client),
+                b"chargeback" => Transaction::new_charge_back(tx, client),
+                b"representment" => Transaction::new_representment(tx, client),
+                b"reversal" => Transaction::new_reversal(tx, client),
+                b"authorize" => Transaction::new_authorize(tx, client, parse_amount(amount?)?),
+                b"capture" => Transaction::new_capture(tx, client, parse_amount(amount?)?),
+                b"release" => Transaction::new_release(tx, client),
+                b"clear" => Transaction::new_clear(tx, client),
+                b"return" => Transaction::new_return(tx, client),
+                b"accrue" => Transaction::new_accrue(tx, client),
+                b"limit" => Transaction::new_limit(tx, client, parse_amount(amount?)?),
+                _ => return None,
+            })
+        })();
+        parsed.map_or(Outcome::Delegate, Outcome::Parsed)
+    }
+
+    #[cold]
+    fn delegate(&self, record: ScannedRecord) -> Result<Transaction, ParserError> {
+        let data = &self.data[..record.end.byte() as usize];
+        TransactionCsvIterator::seeked(data, self.config, record.start)?
+            .next()
+            .expect("the scanner found a record")
+    }
+}
+
+enum Inner<'a> {
+    Fast(Scanner<'a>),
+    Csv(TransactionCsvIterator<Cursor<&'a [u8]>>),
+}
+
+/// Iterator returned by [`parse_fast`]
+pub struct FastCsvIterator<'a>(Inner<'a>);
+
+impl<'a> FastCsvIterator<'a> {
+    fn new(
+        data: &'a [u8],
+        config: &'a ParserConfig,
+        start: Option<csv::Position>,
+    ) -> Result<Self, ParserError> {
+        // reads the header row, if any, which also reports invalid headers up front
+        let mut probe = TransactionCsvIterator::<_, true>::from_csv(Cursor::new(data), config)?;
+        let start = start.unwrap_or_else(|| probe.position().clone());
+
+        if !config.is_plain() {
+            return Ok(Self(Inner::Csv(TransactionCsvIterator::seeked(
+                data, config, start,
+            )?)));
+        }
+
+        Ok(Self(Inner::Fast(Scanner {
+            data,
+            config,
+            records: RecordScanner::new(data, config.comment, start),
+            field_map: probe.field_map(),
+            field_count: probe.first_record_len(),
+        })))
+    }
+
+    /// Parses `data` starting from a record boundary found by [`RecordScanner`]
+    pub(crate) fn seeked(
+        data: &'a [u8],
+        config: &'a ParserConfig,
+        position: csv::Position,
+    ) -> Result<Self, ParserError> {
+        Self::new(data, config, Some(position))
+    }
+
+    #[cold]
+    fn switch_to_csv(&mut self, position: csv::Position) -> Result<(), ParserError> {
+        if let Inner::Fast(scanner) = &self.0 {
+            let iter = TransactionCsvIterator::seeked(scanner.data, scanner.config, position)?;
+            self.0 = Inner::Csv(iter);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for FastCsvIterator<'a> {
+    type Item = Result<Transaction, ParserError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let scanner = match &mut self.0 {
+            Inner::Fast(scanner) => scanner,
+            Inner::Csv(iter) => return iter.next(),
+        };
+
+        let position = match scanner.records.next()? {
+            Ok(record) => match scanner.parse(&scanner.data[record.content.clone()]) {
+                Outcome::Parsed(transaction) => return Some(Ok(transaction)),
+                Outcome::Delegate => return Some(scanner.delegate(record)),
+                Outcome::Quoted => record.start,
+            },
+            Err(_) => scanner.records.position().clone(),
+        };
+
+        if let Err(e) = self.switch_to_csv(position) {
+            return Some(Err(e));
+        }
+        self.next()
+    }
+}
+
+/// Parses uncompressed CSV `data`, typically from [`map_file`](crate::map_file), with the
+/// scanner described in the [module documentation](self).
+pub fn parse_fast<'a>(
+    data: &'a [u8],
+    config: &'a ParserConfig,
+) -> Result<FastCsvIterator<'a>, ParserError> {
+    FastCsvIterator::new(data, config, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ucur, Header};
+
+    fn assert_same(input: &str, config: &ParserConfig) {
+        let expected = crate::parse_with_config(input.as_bytes(), config)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let actual = parse_fast(input.as_bytes(), config)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(format!("{actual:?}"), format!("{expected:?}"), "{input:?}");
+    }
+
+    #[test]
+    fn parses_amounts_like_the_currency_parser() {
+        for fraction in 0..10_000 {
+            for text in [format!("123.{fraction:04}"), format!("7.{fraction}")] {
+                assert_eq!(
+                    parse_amount(text.as_bytes()),
+                    Some(text.parse::<UCurrency>().unwrap()),
+                    "{text}"
+                );
+            }
+        }
+        assert_eq!(parse_amount(b" 42 "), Some(ucur!(42)));
+        assert_eq!(
+            parse_amount(b"281474976710655.9999"),
+            Some("281474976710655.9999".parse().unwrap())
+        );
+        // left to the regular parser
+        for text in [
+            "281474976710656",
+            "1.23456",
+            "1.",
+            ".5",
+            "-1",
+            "+1",
+            "1e5",
+            "",
+        ] {
+            assert_eq!(parse_amount(text.as_bytes()), None, "{text}");
+        }
+    }
+
+    #[test]
+    fn matches_regular_parser() {
+        let config = ParserConfig::default();
+        assert_same(
+            "type, client, tx, amount\n\
+             deposit,1,1,1.0\n\
+             withdrawal, 1 ,2, 0.12345\n\
+             \n\
+             dispute,1,1,\n\
+             resolve,1,1,garbage\n\
+             Deposit,1,3,1.0\n\
+             deposit,1,4\n\
+             deposit,1,5,1.0,extra\n\
+             deposit,+1,6,1\n\
+             deposit,1,99999999999,1\n\
+             chargeback,1,1,\n\
+             representment,1,1,\n\
+             reversal,1,1,\n\
+             authorize,1,7,2.5\n\
+             capture,1,7,1\n\
+             capture,1,7,\n\
+             release,1,7,\n\
+             clear,1,1,\n\
+             return,1,1,\n\
+             accrue,0,8,\n\
+             limit,1,9,50\n\
+             limit,1,10,",
+            &config,
+        );
+        assert_same(
+            "\u{feff}tx,amount,type,client\r\n1,2.5,deposit,3\r\n\r\n2,,dispute,3\r\n",
+            &config,
+        );
+        assert_same("type,client,tx,amount\n", &config);
+
+        // well formed input never leaves the fast path
+        let mut iter = parse_fast(b"type,client,tx,amount\ndeposit,1,1,1\n", &config).unwrap();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(iter.0, Inner::Fast(_)));
+    }
+
+    #[test]
+    fn matches_regular_parser_with_dialect() {
+        let config = ParserConfig {
+            delimiter: b';',
+            comment: Some(b'#'),
+            columns: Some(vec![
+                Header::Type,
+                Header::Client,
+                Header::Tx,
+                Header::Amount,
+            ]),
+            ..Default::default()
+        };
+        assert_same(
+            "deposit;1;1;1.0\n#deposit;1;2;1.0\n\nwithdrawal;1;3\ndeposit;1;4;2\n",
+            &config,
+        );
+        let config = ParserConfig {
+            strict: true,
+            ..Default::default()
+        };
+        assert_same("type,client,tx,amount\ndeposit,1,1,0\n", &config);
+    }
+
+    #[test]
+    fn hands_quoted_and_bare_cr_input_to_csv() {
+        let config = ParserConfig::default();
+        assert_same(
+            "type,client,tx,amount\ndeposit,1,1,1.0\n\"deposit\",1,2,\"2.0\n\"\ndeposit,1,3,1\n",
+            &config,
+        );
+        assert_same(
+            "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,2.0\rdeposit,1,3,1\n",
+            &config,
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Fees charged by the [`Ledger`](crate::Ledger) on top of withdrawals, disputes and chargebacks,
+//! see [`FeeSchedule`].
+use crate::{format::UnknownFormat, TransactionExecutionError, UCurrency};
+use std::str::FromStr;
+
+/// Number of smallest units of 0.0001 in 1
+const UNITS: u128 = 10_000;
+
+/// How the percentage part of a fee is rounded to the 4 decimals of the currency
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round to nearest, ties to even, also known as bankers rounding
+    #[default]
+    HalfEven,
+    /// Round to nearest, ties away from zero
+    HalfUp,
+    /// Towards zero, in favour of the client
+    Down,
+    /// Away from zero, in favour of the fee income
+    Up,
+}
+
+impl Rounding {
+    /// `numerator / denominator` rounded to an integer
+    fn divide(self, numerator: u128, denominator: u128) -> u128 {
+        let (quotient, remainder) = (numerator / denominator, numerator % denominator);
+        let round_up = match self {
+            Rounding::Down => false,
+            Rounding::Up => remainder > 0,
+            Rounding::HalfUp => remainder * 2 >= denominator,
+            Rounding::HalfEven => match (remainder * 2).cmp(&denominator) {
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Equal => quotient % 2 == 1,
+                std::cmp::Ordering::Greater => true,
+            },
+        };
+        quotient + u128::from(round_up)
+    }
+}
+
+impl FromStr for Rounding {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "half-even" => Ok(Self::HalfEven),
+            "half-up" => Ok(Self::HalfUp),
+            "down" => Ok(Self::Down),
+            "up" => Ok(Self::Up),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Converts to units of 0.0001, rounding to the nearest like parsing a decimal does
+fn to_units(value: UCurrency) -> u128 {
+    Rounding::HalfUp.divide(
+        u128::from(value.to_bits()) * UNITS,
+        1 << UCurrency::FRAC_NBITS,
+    )
+}
+
+fn from_units(units: u128) -> Option<UCurrency> {
+    let bits = Rounding::HalfUp.divide(units << UCurrency::FRAC_NBITS, UNITS);
+    u64::try_from(bits).ok().map(UCurrency::from_bits)
+}
+
+/// A fixed amount plus a percentage of the amount of the transaction
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Fee {
+    pub fixed: UCurrency,
+    /// In percent, `1.5` is 1.5% of the amount
+    pub percent: UCurrency,
+}
+
+impl Fee {
+    pub const ZERO: Self = Self {
+        fixed: UCurrency::ZERO,
+        percent: UCurrency::ZERO,
+    };
+
+    pub fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+
+    /// The fee for a transaction of `amount`. The percentage is computed exactly on the 4 decimals
+    /// of `amount` and `percent` and then rounded with `rounding`.
+    pub fn compute(
+        &self,
+        amount: UCurrency,
+        rounding: Rounding,
+    ) -> Result<UCurrency, TransactionExecutionError> {
+        if self.percent == UCurrency::ZERO {
+            return Ok(self.fixed);
+        }
+        let units = rounding.divide(to_units(amount) * to_units(self.percent), 100 * UNITS);
+        from_units(units)
+            .and_then(|percentage| self.fixed.checked_add(percentage))
+            .ok_or(TransactionExecutionError::Overflow)
+    }
+}
+
+impl FromStr for Fee {
+    type Err = UnknownFormat;
+
+    /// Parses a fixed amount `0.5`, a percentage `1.5%` or both `0.5+1.5%`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || UnknownFormat(s.to_string());
+        let parse = |value: &str| value.parse::<UCurrency>().map_err(|_| error());
+        let (fixed, percent) = match s.split_once('+') {
+            Some((fixed, percent)) => (Some(fixed), Some(percent)),
+            None if s.ends_with('%') => (None, Some(s)),
+            None => (Some(s), None),
+        };
+        Ok(Self {
+            fixed: fixed.map(parse).transpose()?.unwrap_or_default(),
+            percent: percent
+                .map(|percent| parse(percent.strip_suffix('%').ok_or_else(error)?))
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// The fees charged by a [`Ledger`](crate::Ledger), nothing is charged by default.
+///
+/// Withdrawal fees are charged on top of the withdrawal, which is rejected with
+/// [`TransactionExecutionError::InsufficientFunds`] if both aren't available.
+/// Dispute and chargeback fees can't be refused and may drive `available` negative.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSchedule {
+    pub withdrawal: Fee,
+    /// Charged on the disputed amount
+    pub dispute: Fee,
+    /// Charged on the charged back amount
+    pub charge_back: Fee,
+    pub rounding: Rounding,
+}
+
+/// What a fee was charged for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeeKind {
+    Withdrawal,
+    Dispute,
+    ChargeBack,
+}
+
+/// A fee posted against a client, see [`Ledger::fees`](crate::Ledger::fees)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeEntry {
+    /// [`Ledger::sequence`](crate::Ledger::sequence) of the transaction charged for
+    pub sequence: u64,
+    pub tx: u32,
+    pub kind: FeeKind,
+    pub amount: UCurrency,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    #[test]
+    fn computes_fixed_plus_percentage() {
+        let fee = Fee {
+            fixed: ucur!(0.25),
+            percent: ucur!(1.5),
+        };
+        assert_eq!(fee.compute(ucur!(100), Rounding::HalfEven), Ok(ucur!(1.75)));
+        assert_eq!(Fee::ZERO.compute(ucur!(100), Rounding::Up), Ok(ucur!(0)));
+    }
+
+    #[test]
+    fn rounds_the_percentage() {
+        let fee = |amount: &str, rounding| {
+            let percent = Fee {
+                fixed: ucur!(0),
+                percent: ucur!(1),
+            };
+            let fee = percent.compute(amount.parse().unwrap(), rounding).unwrap();
+            format!("{fee:.4}")
+        };
+        // 1% of 0.0150 is 0.00015, exactly half way
+        assert_eq!(fee("0.015", Rounding::HalfEven), "0.0002");
+        assert_eq!(fee("0.025", Rounding::HalfEven), "0.0002");
+        assert_eq!(fee("0.025", Rounding::HalfUp), "0.0003");
+        assert_eq!(fee("0.0299", Rounding::Down), "0.0002");
+        assert_eq!(fee("0.0201", Rounding::Up), "0.0003");
+        assert_eq!(fee("12.3456", Rounding::HalfEven), "0.1235");
+    }
+
+    #[test]
+    fn parses_fees() {
+        assert_eq!(
+            "0.5+1.5%".parse(),
+            Ok(Fee {
+                fixed: ucur!(0.5),
+                percent: ucur!(1.5)
+            })
+        );
+        assert_eq!(
+            "2%".parse(),
+            Ok(Fee {
+                fixed: ucur!(0),
+                percent: ucur!(2)
+            })
+        );
+        assert_eq!(
+            "3".parse(),
+            Ok(Fee {
+                fixed: ucur!(3),
+                percent: ucur!(0)
+            })
+        );
+        assert!("1+2".parse::<Fee>().is_err());
+        assert!("x%".parse::<Fee>().is_err());
+        assert_eq!("half-up".parse(), Ok(Rounding::HalfUp));
+        assert!("nearest".parse::<Rounding>().is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    binary::{TransactionBinaryIterator, BINARY_MAGIC},
+    ndjson::TransactionNdJsonIterator,
+    parser::{ParserConfig, ParserError, TransactionCsvIterator},
+    transaction::Transaction,
+    writer::WriterConfig,
+    Ledger,
+};
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+    str::FromStr,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+#[error("Unknown format `{0}`")]
+pub struct UnknownFormat(pub(crate) String);
+
+/// Supported formats for reading transactions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    NdJson,
+    /// See [`crate::binary`]
+    Binary,
+}
+
+/// Supported formats for writing client accounts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    Json,
+    NdJson,
+}
+
+/// Lowercase file extension ignoring any compression suffix, so `a.CSV.gz` gives `csv`
+pub(crate) fn extension(path: &Path) -> Option<String> {
+    let ext = |path: &Path| {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+    };
+    match ext(path)?.as_str() {
+        "gz" | "zst" => path.file_stem().and_then(|stem| ext(Path::new(stem))),
+        ext => Some(ext.to_string()),
+    }
+}
+
+impl InputFormat {
+    /// Guesses the format from the file extension, returns `None` for unknown extensions.
+    /// `.json` is unknown like in [`FromStr`], a JSON document isn't one transaction per line.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match extension(path.as_ref())?.as_str() {
+            "csv" => Some(Self::Csv),
+            "ndjson" | "jsonl" => Some(Self::NdJson),
+            "fstx" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the first bytes without consuming any input.
+    /// The binary magic bytes means binary, a `{` as the first non whitespace byte means NDJSON
+    /// and anything else is assumed to be CSV.
+    pub fn detect(reader: &mut impl BufRead) -> std::io::Result<Self> {
+        let buf = reader.fill_buf()?;
+        if buf.starts_with(&BINARY_MAGIC) {
+            return Ok(Self::Binary);
+        }
+        Ok(match buf.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => Self::NdJson,
+            _ => Self::Csv,
+        })
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "ndjson" | "jsonl" => Ok(Self::NdJson),
+            "binary" => Ok(Self::Binary),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Guesses the format from the file extension, returns `None` for unknown extensions.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match extension(path.as_ref())?.as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "ndjson" | "jsonl" => Some(Self::NdJson),
+            _ => None,
+        }
+    }
+
+    pub fn write<W: Write>(self, ledger: &Ledger, writer: W) -> Result<(), std::io::Error> {
+        self.write_with_config(ledger, writer, &WriterConfig::default())
+    }
+
+    pub fn write_with_config<W: Write>(
+        self,
+        ledger: &Ledger,
+        writer: W,
+        config: &WriterConfig,
+    ) -> Result<(), std::io::Error> {
+        match self {
+            Self::Csv => crate::write_csv_with_config(ledger, writer, config),
+            Self::Json => crate::write_json_with_config(ledger, writer, config),
+            Self::NdJson => crate::write_ndjson_with_config(ledger, writer, config),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "ndjson" | "jsonl" => Ok(Self::NdJson),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+enum Inner<R: BufRead> {
+    PlainCsv(TransactionCsvIterator<R, false>),
+    Csv(TransactionCsvIterator<R>),
+    NdJson(TransactionNdJsonIterator<R>),
+    Binary(TransactionBinaryIterator<R>),
+}
+
+/// Iterator over transactions in any of the supported [`InputFormat`]s
+pub struct TransactionReader<R: BufRead>(Inner<R>);
+
+impl<R: BufRead> TransactionReader<R> {
+    pub fn new(reader: R, format: InputFormat) -> Result<Self, ParserError> {
+        Self::with_config(reader, format, &ParserConfig::default())
+    }
+
+    /// Like [`TransactionReader::new`] but with a custom CSV dialect.
+    /// Only [`ParserConfig::lenient`], [`ParserConfig::strict`] and
+    /// [`ParserConfig::extra_columns`] are used by the NDJSON format, binary ignores `config`
+    /// entirely.
+    pub fn with_config(
+        reader: R,
+        format: InputFormat,
+        config: &ParserConfig,
+    ) -> Result<Self, ParserError> {
+        Ok(Self(match format {
+            InputFormat::Csv if config.is_plain() => {
+                Inner::PlainCsv(TransactionCsvIterator::from_csv(reader, config)?)
+            }
+            InputFormat::Csv => Inner::Csv(TransactionCsvIterator::from_csv(reader, config)?),
+            InputFormat::NdJson => {
+                Inner::NdJson(TransactionNdJsonIterator::from_config(reader, config))
+            }
+            InputFormat::Binary => Inner::Binary(TransactionBinaryIterator::new(reader)?),
+        }))
+    }
+
+    /// Like [`TransactionReader::new`] but uses [`InputFormat::detect`] to pick the format.
+    pub fn detect(mut reader: R) -> Result<Self, ParserError> {
+        let format = InputFormat::detect(&mut reader)?;
+        Self::new(reader, format)
+    }
+}
+
+impl<R: BufRead> Iterator for TransactionReader<R> {
+    type Item = Result<Transaction, ParserError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            Inner::PlainCsv(it) => it.next(),
+            Inner::Csv(it) => it.next(),
+            Inner::NdJson(it) => it.next(),
+            Inner::Binary(it) => it.next(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    #[test]
+    fn detects_from_extension() {
+        assert_eq!(InputFormat::from_path("a/b.csv"), Some(InputFormat::Csv));
+        assert_eq!(
+            InputFormat::from_path("b.NDJSON"),
+            Some(InputFormat::NdJson)
+        );
+        assert_eq!(InputFormat::from_path("b.fstx"), Some(InputFormat::Binary));
+        assert_eq!(InputFormat::from_path("b.txt"), None);
+        assert_eq!(InputFormat::from_path("b.csv.gz"), Some(InputFormat::Csv));
+        assert_eq!(
+            InputFormat::from_path("b.ndjson.zst"),
+            Some(InputFormat::NdJson)
+        );
+        assert_eq!(InputFormat::from_path("b.gz"), None);
+        assert_eq!(InputFormat::from_path("b.json"), None);
+        assert_eq!(OutputFormat::from_path("b.json"), Some(OutputFormat::Json));
+        assert_eq!(
+            OutputFormat::from_path("b.jsonl"),
+            Some(OutputFormat::NdJson)
+        );
+        assert_eq!(OutputFormat::from_path("b"), None);
+    }
+
+    #[test]
+    fn detects_from_first_byte() {
+        let mut csv = "type,client,tx,amount\n".as_bytes();
+        let input = "  \n{\"type\":\"deposit\"}";
+        let mut ndjson = input.as_bytes();
+        assert_eq!(InputFormat::detect(&mut csv).unwrap(), InputFormat::Csv);
+        assert_eq!(
+            InputFormat::detect(&mut ndjson).unwrap(),
+            InputFormat::NdJson
+        );
+        // detection must not consume anything
+        assert_eq!(ndjson, input.as_bytes());
+
+        let mut binary = &b"FSTX\x01\0\0\0"[..];
+        assert_eq!(
+            InputFormat::detect(&mut binary).unwrap(),
+            InputFormat::Binary
+        );
+    }
+
+    #[test]
+    fn both_formats_parse_to_the_same_transactions() {
+        let csv = "type,client,tx,amount\ndeposit,1,1,1.5\ndispute,1,1,\n";
+        let ndjson = "{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":1.5}\n{\"type\":\"dispute\",\"client\":1,\"tx\":1}\n";
+        let expected = vec![
+            Transaction::new_deposit(1, 1, ucur!(1.5)),
+            Transaction::new_dispute(1, 1),
+        ];
+
+        let mut binary = Vec::new();
+        crate::binary::write_binary_header(&mut binary).unwrap();
+        for transaction in &expected {
+            binary.extend_from_slice(&crate::binary::encode_record(transaction));
+        }
+
+        for input in [csv.as_bytes(), ndjson.as_bytes(), &binary] {
+            let actual = TransactionReader::detect(input)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn parses_format_names() {
+        assert_eq!("ndjson".parse(), Ok(InputFormat::NdJson));
+        assert_eq!("json".parse(), Ok(OutputFormat::Json));
+        assert_eq!(
+            "json".parse::<InputFormat>(),
+            Err(UnknownFormat("json".to_string()))
+        );
+        assert_eq!(
+            "xml".parse::<OutputFormat>(),
+            Err(UnknownFormat("xml".to_string()))
+        );
+    }
+}
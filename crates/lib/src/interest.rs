@@ -0,0 +1,144 @@
+//! Interest accrued by the [`Ledger`](crate::Ledger) on the available funds of its clients,
+//! see [`InterestConfig`].
+use crate::{
+    fees::{Fee, Rounding},
+    format::UnknownFormat,
+    ICurrency, TransactionExecutionError, UCurrency,
+};
+use std::{collections::HashMap, str::FromStr};
+
+/// Tier of the clients without one in [`InterestConfig::tiers`]
+pub const DEFAULT_TIER: &str = "default";
+
+/// Rates of an accrual run, in percent like [`Fee::percent`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InterestRate {
+    /// Paid to clients on positive available funds
+    pub credit: UCurrency,
+    /// Charged to clients on negative available funds, e.g. after a chargeback
+    pub debit: UCurrency,
+}
+
+impl InterestRate {
+    /// The interest on `available`, positive if paid to the client and negative if charged.
+    /// The magnitude is rounded with `rounding`.
+    pub fn compute(
+        &self,
+        available: ICurrency,
+        rounding: Rounding,
+    ) -> Result<ICurrency, TransactionExecutionError> {
+        let percent = if available < ICurrency::ZERO {
+            self.debit
+        } else {
+            self.credit
+        };
+        let interest = Fee {
+            fixed: UCurrency::ZERO,
+            percent,
+        }
+        .compute(available.unsigned_abs(), rounding)?;
+        let interest =
+            ICurrency::checked_from_num(interest).ok_or(TransactionExecutionError::Overflow)?;
+        Ok(if available < ICurrency::ZERO {
+            -interest
+        } else {
+            interest
+        })
+    }
+}
+
+impl FromStr for InterestRate {
+    type Err = UnknownFormat;
+
+    /// Parses `credit%/debit%` like `0.5%/2%`, the `%` signs are optional
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .strip_suffix('%')
+                .unwrap_or(value)
+                .parse::<UCurrency>()
+                .map_err(|_| UnknownFormat(s.to_string()))
+        };
+        let (credit, debit) = s
+            .split_once('/')
+            .ok_or_else(|| UnknownFormat(s.to_string()))?;
+        Ok(Self {
+            credit: parse(credit)?,
+            debit: parse(debit)?,
+        })
+    }
+}
+
+/// Interest accrued by every `accrue` row or call to [`Ledger::accrue`](crate::Ledger::accrue),
+/// nothing accrues by default.
+///
+/// Accruals run regardless of the lock policy, locked accounts with negative funds left by a
+/// chargeback are the ones interest is charged on.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct InterestConfig {
+    /// Rates by tier name, clients of a tier without rates accrue nothing
+    pub rates: HashMap<String, InterestRate>,
+    /// Tier name by client, clients not listed are in [`DEFAULT_TIER`]
+    pub tiers: HashMap<u16, String>,
+    pub rounding: Rounding,
+}
+
+impl InterestConfig {
+    pub fn rate(&self, client: u16) -> Option<&InterestRate> {
+        let tier = self.tiers.get(&client).map_or(DEFAULT_TIER, String::as_str);
+        self.rates.get(tier)
+    }
+}
+
+/// Interest posted to a client, see [`Ledger::accruals`](crate::Ledger::accruals)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterestEntry {
+    /// [`Ledger::sequence`](crate::Ledger::sequence) of the accrual run
+    pub sequence: u64,
+    /// Id of the accrual run
+    pub tx: u32,
+    /// Positive if paid to the client, negative if charged
+    pub amount: ICurrency,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{icur, ucur};
+
+    #[test]
+    fn computes_credit_and_debit_interest() {
+        let rate = InterestRate {
+            credit: ucur!(0.5),
+            debit: ucur!(2),
+        };
+        assert_eq!(rate.compute(icur!(100), Rounding::HalfEven), Ok(icur!(0.5)));
+        assert_eq!(rate.compute(icur!(-100), Rounding::HalfEven), Ok(icur!(-2)));
+        assert_eq!(rate.compute(icur!(0), Rounding::HalfEven), Ok(icur!(0)));
+        // 0.5% of 0.0150 is 0.000075, the magnitude is rounded
+        assert_eq!(rate.compute(icur!(0.015), Rounding::Up), Ok(icur!(0.0001)));
+        assert_eq!(
+            rate.compute(icur!(-0.0199), Rounding::Down),
+            Ok(icur!(-0.0003))
+        );
+    }
+
+    #[test]
+    fn parses_rates_and_finds_tiers() {
+        let rate = InterestRate {
+            credit: ucur!(0.5),
+            debit: ucur!(2),
+        };
+        assert_eq!("0.5%/2%".parse(), Ok(rate));
+        assert_eq!("0.5/2".parse(), Ok(rate));
+        assert!("0.5%".parse::<InterestRate>().is_err());
+
+        let config = InterestConfig {
+            rates: HashMap::from([(DEFAULT_TIER.to_string(), rate)]),
+            tiers: HashMap::from([(2, "gold".to_string())]),
+            ..Default::default()
+        };
+        assert_eq!(config.rate(1), Some(&rate));
+        assert_eq!(config.rate(2), None);
+    }
+}
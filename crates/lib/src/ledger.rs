@@ -1,12 +1,22 @@
 use crate::{
     client::{ClientAccount, TransactionExecutionError},
-    transaction::Transaction,
-    UCurrency,
+    events::{AccountEvent, EventKind},
+    fees::{Fee, FeeEntry, FeeKind, FeeSchedule, Rounding},
+    interest::{InterestConfig, InterestEntry},
+    observer::{LedgerObserver, Observers},
+    policy::{DisputeFunding, LockPolicy, ReversalUnlock},
+    rules::{RuleSet, WithdrawalHistory},
+    transaction::{Metadata, Transaction},
+    ICurrency, UCurrency,
 };
-use std::collections::{
-    hash_map::Entry::{Occupied, Vacant},
-    HashMap,
+use std::{
+    collections::{
+        hash_map::Entry::{Occupied, Vacant},
+        HashMap, VecDeque,
+    },
+    time::Duration,
 };
+use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -14,6 +24,16 @@ pub enum DepositState {
     Ok,
     Disputed,
     ChargedBack,
+    /// Final state of resolved deposits when [`LedgerConfig::resolved_is_final`] is set
+    Resolved,
+    /// The merchant contests the chargeback
+    Represented,
+    /// Final state of a chargeback that was reversed, the funds are credited again
+    Reversed,
+    /// Not cleared yet when [`LedgerConfig::pending_deposits`] is set
+    Pending,
+    /// Final state of a pending deposit that failed to clear
+    Returned,
 }
 
 impl std::fmt::Display for DepositState {
@@ -22,28 +42,196 @@ impl std::fmt::Display for DepositState {
             DepositState::Ok => "Ok",
             DepositState::Disputed => "Disputed",
             DepositState::ChargedBack => "ChargedBack",
+            DepositState::Resolved => "Resolved",
+            DepositState::Represented => "Represented",
+            DepositState::Reversed => "Reversed",
+            DepositState::Pending => "Pending",
+            DepositState::Returned => "Returned",
         })
     }
 }
-#[derive(Default, Debug, Clone, PartialEq)]
 
+/// The dispute lifecycle of a single deposit, see [`Ledger::dispute_history`]
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct DisputeHistory {
+    /// Number of times the deposit was disputed
+    pub disputes: u32,
+    /// [`Ledger::sequence`] of each transaction changing the state of the deposit and the state
+    /// it changed to, in order
+    pub transitions: Vec<(u64, DepositState)>,
+}
+
+/// An open authorization, see [`Ledger::authorization`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Authorization {
+    /// Funds still reserved
+    pub remaining: UCurrency,
+    pub captured: UCurrency,
+    /// [`Ledger::sequence`] of the authorize transaction
+    pub sequence: u64,
+    /// [`Ledger::now`] when it was authorized
+    pub authorized_at: u64,
+}
+
+/// Per client counters kept up to date by [`Ledger::execute`], only successful transactions count
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientStats {
+    pub deposits: u32,
+    pub withdrawals: u32,
+    /// Sum of all withdrawals and captures, without their fees
+    pub withdrawn: UCurrency,
+    /// Disputes that are neither resolved nor charged back yet
+    pub open_disputes: u32,
+    /// Sum of all charged back deposits
+    pub charged_back: UCurrency,
+    /// Id of the last transaction executed for the client
+    pub last_tx: Option<u32>,
+    /// Sum of all fees charged, see [`Ledger::fees`]
+    pub fees: UCurrency,
+    /// Net interest paid to the client, see [`Ledger::accruals`]
+    pub interest: ICurrency,
+}
+
+/// An imbalance found by [`Ledger::reconcile`]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconciliationError {
+    #[error("The total {total} of client {client} doesn't add up from its transactions")]
+    ClientTotal { client: u16, total: ICurrency },
+    #[error("The fee income {0} doesn't match the fees charged to the clients")]
+    FeeIncome(UCurrency),
+    #[error("The interest paid {0} doesn't match the interest posted to the clients")]
+    InterestPaid(ICurrency),
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
 struct ClientAccountAndDeposits {
     account: ClientAccount,
     deposits: HashMap<u32, (UCurrency, DepositState)>,
+    /// Part of disputed deposits that couldn't be held, see [`DisputeFunding::HoldAvailable`].
+    /// Kept for charged back deposits in case the chargeback is reversed.
+    shortfalls: HashMap<u32, UCurrency>,
+    /// Only deposits that were ever disputed have a history
+    histories: HashMap<u32, DisputeHistory>,
+    /// Open authorizations, removed once fully captured, released or expired
+    authorizations: HashMap<u32, Authorization>,
+    /// Every fee charged, in order
+    fees: Vec<FeeEntry>,
+    /// Every non zero interest accrual, in order
+    accruals: Vec<InterestEntry>,
+    /// Only kept while the client has withdrawal rules
+    withdrawals: WithdrawalHistory,
+    stats: ClientStats,
 }
 
 impl ClientAccountAndDeposits {
-    pub fn new(client: u16) -> Self {
+    pub fn new(client: u16, overdraft_limit: UCurrency) -> Self {
         Self {
-            account: ClientAccount::new(client),
+            account: ClientAccount {
+                overdraft_limit,
+                ..ClientAccount::new(client)
+            },
             deposits: Default::default(),
+            shortfalls: Default::default(),
+            histories: Default::default(),
+            authorizations: Default::default(),
+            fees: Default::default(),
+            accruals: Default::default(),
+            withdrawals: Default::default(),
+            stats: Default::default(),
         }
     }
 }
 
+/// Policies of a [`Ledger`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LedgerConfig {
+    pub lock_policy: LockPolicy,
+    pub dispute_funding: DisputeFunding,
+    /// Times a single deposit may be disputed, further disputes fail with
+    /// [`TransactionExecutionError::DisputeLimitReached`]
+    pub max_disputes: Option<u32>,
+    /// Resolved deposits go to [`DepositState::Resolved`] rather than back to
+    /// [`DepositState::Ok`] so they can't be disputed again
+    pub resolved_is_final: bool,
+    pub reversal_unlock: ReversalUnlock,
+    /// Open authorizations are released once this many more transactions were executed
+    pub authorization_expiry: Option<u64>,
+    /// Open authorizations are released once they are older than this, measured by
+    /// [`LedgerConfig::timestamp_column`]
+    pub authorization_timeout: Option<Duration>,
+    /// Deposits start out [`DepositState::Pending`] and only become available once cleared
+    pub pending_deposits: bool,
+    /// Pending deposits clear on their own once this many more transactions were executed
+    pub clearing_period: Option<u64>,
+    /// Pending deposits clear on their own once they are older than this, measured by
+    /// [`LedgerConfig::timestamp_column`]
+    pub clearing_timeout: Option<Duration>,
+    pub fees: FeeSchedule,
+    pub interest: InterestConfig,
+    /// Overdraft limits of clients when they're first seen, see
+    /// [`ClientAccount::overdraft_limit`] and [`load_client_config`](crate::load_client_config)
+    pub overdraft_limits: HashMap<u16, UCurrency>,
+    /// Checked before every withdrawal, see [`load_withdrawal_rules`](crate::load_withdrawal_rules)
+    pub withdrawal_rules: RuleSet,
+    /// Execute admin transactions like [`Transaction::Limit`] from the input, otherwise they're
+    /// rejected with [`TransactionExecutionError::AdminRowsNotAllowed`] so a client can't grant
+    /// itself credit. Limits can always be given with [`LedgerConfig::overdraft_limits`].
+    pub allow_admin_rows: bool,
+    /// Metadata column with the time of each transaction in whole seconds, e.g. since the unix
+    /// epoch, which has to be one of the
+    /// [`ParserConfig::extra_columns`](crate::ParserConfig::extra_columns). It's the only clock
+    /// of the time based options, without it time stands still. See [`Ledger::now`].
+    pub timestamp_column: Option<String>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq)]
 pub struct Ledger {
     clients: HashMap<u16, ClientAccountAndDeposits>,
+    observers: Observers,
+    config: LedgerConfig,
+    /// Number of transactions executed, including the rejected ones
+    sequence: u64,
+    /// `(client, tx, sequence)` of authorizations in the order they were made, only kept if
+    /// they can expire. Entries of authorizations that were closed are skipped.
+    expiries: VecDeque<(u16, u32, u64)>,
+    /// `(client, tx, sequence, time)` of pending deposits in the order they were made, only kept
+    /// if they can clear on their own. Entries of deposits that were cleared or returned are
+    /// skipped.
+    clearings: VecDeque<(u16, u32, u64, u64)>,
+    /// The fee income system account every fee is credited to
+    fee_income: UCurrency,
+    /// The interest system account every accrual is debited from
+    interest_paid: ICurrency,
+    /// Deposits kept for disputes across all clients, see [`Ledger::deposit_count`]
+    deposits: usize,
+    /// See [`Ledger::now`]
+    now: u64,
+}
+
+/// Whether something made by the transaction `since` at `at` is due by the time the transaction
+/// `sequence` executes at `now`, given it's due `after` transactions or once older than `timeout`
+fn is_due(
+    after: Option<u64>,
+    timeout: Option<Duration>,
+    sequence: u64,
+    (since, at): (u64, u64),
+    now: u64,
+) -> bool {
+    after.is_some_and(|after| sequence - since >= after)
+        || timeout.is_some_and(|timeout| Duration::from_secs(now - at) >= timeout)
+}
+
+/// `(tx, from, to)` of a deposit changing state
+type DepositTransition = (u32, Option<DepositState>, DepositState);
+
+/// What applying a transaction did besides changing its account
+#[derive(Debug, Default)]
+struct Applied {
+    /// The deposit state change it caused
+    transition: Option<DepositTransition>,
+    /// The account right before the fee charged for it, the state in between the transaction's
+    /// event and its [`EventKind::Fee`]
+    uncharged: Option<ClientAccount>,
 }
 
 fn get_deposit_and_state_mut(
@@ -56,22 +244,476 @@ fn get_deposit_and_state_mut(
         .map(|(amount, state)| (*amount, state))
 }
 
+/// The fee of a transaction moving `amount`, making sure it can be credited to `income`
+fn compute_fee(
+    fee: &Fee,
+    rounding: Rounding,
+    amount: UCurrency,
+    income: UCurrency,
+) -> Result<UCurrency, TransactionExecutionError> {
+    let fee = fee.compute(amount, rounding)?;
+    income
+        .checked_add(fee)
+        .ok_or(TransactionExecutionError::Overflow)?;
+    Ok(fee)
+}
+
+/// Records that the disputed deposit `tx` went to `state`
+fn record_transition(
+    histories: &mut HashMap<u32, DisputeHistory>,
+    tx: u32,
+    sequence: u64,
+    state: DepositState,
+) {
+    histories
+        .entry(tx)
+        .or_default()
+        .transitions
+        .push((sequence, state));
+}
+
 impl Ledger {
+    /// A ledger calling every observer in `observers`, in order, see [`LedgerObserver`]
+    pub fn with_observers(observers: Vec<Box<dyn LedgerObserver + Send>>) -> Self {
+        Self {
+            observers: Observers(observers),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_config(config: LedgerConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn config(&self) -> &LedgerConfig {
+        &self.config
+    }
+
+    pub fn add_observer(&mut self, observer: impl LedgerObserver + Send + 'static) {
+        self.observers.0.push(Box::new(observer));
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &ClientAccount> {
         self.clients.values().map(|client| &client.account)
     }
 
+    pub fn iter_with_stats(&self) -> impl Iterator<Item = (&ClientAccount, &ClientStats)> {
+        self.clients
+            .values()
+            .map(|client| (&client.account, &client.stats))
+    }
+
+    pub fn stats(&self, client: u16) -> Option<&ClientStats> {
+        self.clients.get(&client).map(|client| &client.stats)
+    }
+
+    /// The dispute history of the deposit `tx`, `None` if it was never disputed
+    pub fn dispute_history(&self, client: u16, tx: u32) -> Option<&DisputeHistory> {
+        self.clients.get(&client)?.histories.get(&tx)
+    }
+
+    /// The open authorization `tx`, `None` once it's fully captured, released or expired
+    pub fn authorization(&self, client: u16, tx: u32) -> Option<&Authorization> {
+        self.clients.get(&client)?.authorizations.get(&tx)
+    }
+
+    /// Number of transactions executed so far, including the rejected ones.
+    /// The first transaction has sequence number 1.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The fees charged to `client` in the order they were charged
+    pub fn fees(&self, client: u16) -> &[FeeEntry] {
+        self.clients
+            .get(&client)
+            .map_or(&[], |client| client.fees.as_slice())
+    }
+
+    /// Balance of the fee income system account, the sum of all fees charged
+    pub fn fee_income(&self) -> UCurrency {
+        self.fee_income
+    }
+
+    /// The interest posted to `client` in the order it accrued
+    pub fn accruals(&self, client: u16) -> &[InterestEntry] {
+        self.clients
+            .get(&client)
+            .map_or(&[], |client| client.accruals.as_slice())
+    }
+
+    /// Net interest paid to clients, negative if more was charged than paid
+    pub fn interest_paid(&self) -> ICurrency {
+        self.interest_paid
+    }
+
+    /// Runs the interest accrual `tx` like an `accrue` row in the input would
+    pub fn accrue(&mut self, tx: u32) -> Result<&mut Self, TransactionExecutionError> {
+        self.execute(Transaction::new_accrue(tx, 0))
+    }
+
+    /// Number of deposits kept for disputes across all clients
+    pub fn deposit_count(&self) -> usize {
+        self.deposits
+    }
+
+    /// Checks that the books balance: the total of every client adds up from its deposits,
+    /// withdrawals, fees and interest, and the fee income and interest paid system accounts match
+    /// what was posted to the clients. Charged back deposits only count with the part that
+    /// couldn't be taken back, see [`DisputeFunding::HoldAvailable`].
+    pub fn reconcile(&self) -> Result<(), ReconciliationError> {
+        let mut fees = 0;
+        let mut interest = 0;
+        for (&client, entry) in &self.clients {
+            let stats = &entry.stats;
+            let deposited = entry
+                .deposits
+                .iter()
+                .map(|(tx, (amount, state))| match state {
+                    DepositState::ChargedBack | DepositState::Represented => entry
+                        .shortfalls
+                        .get(tx)
+                        .map_or(0, |shortfall| shortfall.to_bits()),
+                    DepositState::Returned => 0,
+                    _ => amount.to_bits(),
+                })
+                .map(i128::from)
+                .sum::<i128>();
+            let expected = deposited
+                - i128::from(stats.withdrawn.to_bits())
+                - i128::from(stats.fees.to_bits())
+                + i128::from(stats.interest.to_bits());
+            let total = entry.account.total();
+            if i128::from(total.to_bits()) != expected {
+                return Err(ReconciliationError::ClientTotal { client, total });
+            }
+            fees += i128::from(stats.fees.to_bits());
+            interest += i128::from(stats.interest.to_bits());
+        }
+        if fees != i128::from(self.fee_income.to_bits()) {
+            return Err(ReconciliationError::FeeIncome(self.fee_income));
+        }
+        if interest != i128::from(self.interest_paid.to_bits()) {
+            return Err(ReconciliationError::InterestPaid(self.interest_paid));
+        }
+        Ok(())
+    }
+
+    /// Time of the latest transaction in seconds, taken from the
+    /// [`LedgerConfig::timestamp_column`] of the transactions. It never goes backwards, an
+    /// earlier timestamp than the latest one leaves it alone.
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    /// Moves [`Ledger::now`] forward to the timestamp of `transaction`, transactions without one
+    /// leave it alone
+    fn advance_clock(
+        &mut self,
+        transaction: &Transaction,
+    ) -> Result<(), TransactionExecutionError> {
+        let timestamp = self
+            .config
+            .timestamp_column
+            .as_ref()
+            .and_then(|column| transaction.metadata().get(column))
+            .filter(|timestamp| !timestamp.is_empty());
+        if let Some(timestamp) = timestamp {
+            let timestamp = timestamp
+                .parse::<u64>()
+                .map_err(|_| TransactionExecutionError::InvalidTimestamp(timestamp.to_string()))?;
+            self.now = self.now.max(timestamp);
+        }
+        Ok(())
+    }
+
     pub fn execute(
         &mut self,
         transaction: Transaction,
     ) -> Result<&mut Self, TransactionExecutionError> {
+        self.execute_accruing(transaction, |_| {})?;
+        Ok(self)
+    }
+
+    /// Like [`Ledger::execute`] but calls `on_accrued` with [`EventKind::Accrued`] for every
+    /// client an accrual run posted interest to. Returns the account right before the fee charged
+    /// for `transaction`, if any.
+    fn execute_accruing(
+        &mut self,
+        transaction: Transaction,
+        on_accrued: impl FnMut(AccountEvent),
+    ) -> Result<Option<ClientAccount>, TransactionExecutionError> {
+        self.sequence += 1;
+        if let Err(error) = self.advance_clock(&transaction) {
+            return Err(self.reject(&transaction, error));
+        }
+        self.expire_authorizations(self.sequence, |_| {});
+        self.clear_pending_deposits(self.sequence, |_| {});
+        if self.observers.is_empty() {
+            Ok(self.apply(transaction, on_accrued)?.uncharged)
+        } else {
+            self.apply_observed(transaction, on_accrued)
+        }
+    }
+
+    /// Releases the authorizations that expired by the time the transaction with the sequence
+    /// number `sequence` executes and calls `on_event` with [`EventKind::Expired`] for each
+    fn expire_authorizations(&mut self, sequence: u64, mut on_event: impl FnMut(AccountEvent)) {
+        let Self {
+            clients,
+            observers,
+            config,
+            expiries,
+            now,
+            ..
+        } = self;
+        while let Some(&(client_id, tx, authorized)) = expiries.front() {
+            let client = clients
+                .get_mut(&client_id)
+                .expect("authorizing client exists");
+            match client.authorizations.get(&tx) {
+                Some(authorization) if authorization.sequence == authorized => {
+                    if !is_due(
+                        config.authorization_expiry,
+                        config.authorization_timeout,
+                        sequence,
+                        (authorized, authorization.authorized_at),
+                        *now,
+                    ) {
+                        break;
+                    }
+                    let before = client.account;
+                    // on the off chance releasing overflows the funds stay reserved
+                    if let Ok(account) = before.unreserve(authorization.remaining) {
+                        client.account = account;
+                        client.authorizations.remove(&tx);
+                        for observer in &mut observers.0 {
+                            observer.on_authorization_expired(&account, tx);
+                        }
+                        on_event(AccountEvent {
+                            kind: EventKind::Expired,
+                            tx,
+                            before,
+                            after: account,
+                            metadata: Metadata::default(),
+                        });
+                    }
+                }
+                // closed before it expired
+                _ => {}
+            }
+            expiries.pop_front();
+        }
+    }
+
+    /// Clears the pending deposits that are due by the time the transaction with the sequence
+    /// number `sequence` executes and calls `on_event` with [`EventKind::Cleared`] for each
+    fn clear_pending_deposits(&mut self, sequence: u64, mut on_event: impl FnMut(AccountEvent)) {
+        let Self {
+            clients,
+            observers,
+            config,
+            clearings,
+            now,
+            ..
+        } = self;
+        // deposits that can't clear yet stay queued in order and are retried next time
+        let mut retries = Vec::new();
+        while let Some(&(client_id, tx, deposited, at)) = clearings.front() {
+            if !is_due(
+                config.clearing_period,
+                config.clearing_timeout,
+                sequence,
+                (deposited, at),
+                *now,
+            ) {
+                break;
+            }
+            let entry = clearings.pop_front().expect("front exists");
+
+            let client = clients
+                .get_mut(&client_id)
+                .expect("depositing client exists");
+            let Some((amount, state @ DepositState::Pending)) = client.deposits.get_mut(&tx) else {
+                // cleared or returned already
+                continue;
+            };
+            let before = client.account;
+            // on the off chance clearing overflows the deposit stays pending until it fits
+            let Ok(account) = before.settle_pending(*amount) else {
+                retries.push(entry);
+                continue;
+            };
+            *state = DepositState::Ok;
+            client.account = account;
+            for observer in &mut observers.0 {
+                observer.on_deposit_state_changed(
+                    &account,
+                    tx,
+                    Some(DepositState::Pending),
+                    DepositState::Ok,
+                );
+            }
+            on_event(AccountEvent {
+                kind: EventKind::Cleared,
+                tx,
+                before,
+                after: account,
+                metadata: Metadata::default(),
+            });
+        }
+        for entry in retries.into_iter().rev() {
+            clearings.push_front(entry);
+        }
+    }
+
+    /// Posts the interest of the accrual run `tx` to every client with a rate and calls
+    /// `on_event` with [`EventKind::Accrued`] for each, in order of client id. Nothing is posted
+    /// if any of the accruals overflows.
+    fn accrue_interest(
+        &mut self,
+        tx: u32,
+        mut on_event: impl FnMut(AccountEvent),
+    ) -> Result<(), TransactionExecutionError> {
+        let config = &self.config.interest;
+        if config.rates.is_empty() {
+            return Ok(());
+        }
+        let mut interest_paid = self.interest_paid;
+        let mut accruals = Vec::new();
+        for (&client_id, client) in &self.clients {
+            let Some(rate) = config.rate(client_id) else {
+                continue;
+            };
+            let interest = rate.compute(client.account.available, config.rounding)?;
+            if interest == ICurrency::ZERO {
+                continue;
+            }
+            let account = client.account.accrue(interest)?;
+            interest_paid = interest_paid
+                .checked_add(interest)
+                .ok_or(TransactionExecutionError::Overflow)?;
+            accruals.push((client_id, account, interest));
+        }
+
+        accruals.sort_unstable_by_key(|(client_id, ..)| *client_id);
+        for (client_id, account, interest) in accruals {
+            let client = self
+                .clients
+                .get_mut(&client_id)
+                .expect("accruing client exists");
+            let before = std::mem::replace(&mut client.account, account);
+            client.accruals.push(InterestEntry {
+                sequence: self.sequence,
+                tx,
+                amount: interest,
+            });
+            client.stats.interest = client.stats.interest.saturating_add(interest);
+            for observer in &mut self.observers.0 {
+                observer.on_interest_accrued(&account, tx, interest);
+            }
+            on_event(AccountEvent {
+                kind: EventKind::Accrued,
+                tx,
+                before,
+                after: account,
+                metadata: Metadata::default(),
+            });
+        }
+        self.interest_paid = interest_paid;
+        Ok(())
+    }
+
+    fn apply_observed(
+        &mut self,
+        transaction: Transaction,
+        on_accrued: impl FnMut(AccountEvent),
+    ) -> Result<Option<ClientAccount>, TransactionExecutionError> {
         let client_id = transaction.get_client_id();
-        let ClientAccountAndDeposits { account, deposits } = self
+        let was_locked = self
+            .clients
+            .get(&client_id)
+            .is_some_and(|client| client.account.locked);
+        let observed = transaction.clone();
+
+        let Applied {
+            transition,
+            uncharged,
+        } = match self.apply(transaction, on_accrued) {
+            Ok(applied) => applied,
+            Err(e) => return Err(self.reject(&observed, e)),
+        };
+
+        // accrual rows name any client, who doesn't have to exist
+        let account = self
             .clients
-            .entry(client_id)
-            .or_insert_with(|| ClientAccountAndDeposits::new(client_id));
+            .get(&client_id)
+            .map_or_else(|| ClientAccount::new(client_id), |client| client.account);
+        let observers = &mut self.observers.0;
+        observers
+            .iter_mut()
+            .for_each(|observer| observer.on_applied(&observed, &account));
+        if let Some((tx, from, to)) = transition {
+            observers
+                .iter_mut()
+                .for_each(|observer| observer.on_deposit_state_changed(&account, tx, from, to));
+        }
+        if account.locked != was_locked {
+            observers
+                .iter_mut()
+                .for_each(|observer| observer.on_lock_changed(&account, observed.get_tx()));
+        }
+        Ok(uncharged)
+    }
 
-        match transaction {
+    /// Reports the rejection of `transaction` to the observers and hands back `error`
+    fn reject(
+        &mut self,
+        transaction: &Transaction,
+        error: TransactionExecutionError,
+    ) -> TransactionExecutionError {
+        for observer in &mut self.observers.0 {
+            observer.on_rejected(transaction, &error);
+        }
+        error
+    }
+
+    /// Applies `transaction` and returns the deposit state change and fee it caused, if any. Accrual
+    /// runs call `on_accrued` for every client they posted interest to.
+    fn apply(
+        &mut self,
+        transaction: Transaction,
+        on_accrued: impl FnMut(AccountEvent),
+    ) -> Result<Applied, TransactionExecutionError> {
+        if let Transaction::Accrue(a) = transaction {
+            // accruals run over all clients instead of the one of the row
+            self.accrue_interest(a.tx, on_accrued)?;
+            return Ok(Applied::default());
+        }
+        let client_id = transaction.get_client_id();
+        let tx = transaction.get_tx();
+        let (config, sequence) = (&self.config, self.sequence);
+        let (policy, fees, income) = (&config.lock_policy, &config.fees, self.fee_income);
+        let mut charged = None;
+        let ClientAccountAndDeposits {
+            account,
+            deposits,
+            shortfalls,
+            histories,
+            authorizations,
+            fees: fee_entries,
+            withdrawals,
+            stats,
+            ..
+        } = self.clients.entry(client_id).or_insert_with(|| {
+            let overdraft_limit = config.overdraft_limits.get(&client_id).copied();
+            ClientAccountAndDeposits::new(client_id, overdraft_limit.unwrap_or_default())
+        });
+
+        let transition = match transaction {
             Transaction::Deposit(d) => {
                 let tx = d.tx;
                 let amount = d.amount;
@@ -80,41 +722,337 @@ impl Ledger {
 
                 match ent {
                     Occupied(_) => return Err(TransactionExecutionError::DuplicateDeposit(tx)),
+                    Vacant(ent) if config.pending_deposits => {
+                        *account = account.deposit_pending(d, policy)?;
+                        ent.insert((amount, DepositState::Pending));
+                        stats.deposits += 1;
+                        self.deposits += 1;
+                        if config.clearing_period.is_some() || config.clearing_timeout.is_some() {
+                            self.clearings
+                                .push_back((client_id, tx, sequence, self.now));
+                        }
+                        Some((tx, None, DepositState::Pending))
+                    }
                     Vacant(ent) => {
-                        *account = account.deposit(d)?;
+                        *account = account.deposit(d, policy)?;
                         ent.insert((amount, DepositState::Ok));
+                        stats.deposits += 1;
+                        self.deposits += 1;
+                        Some((tx, None, DepositState::Ok))
                     }
                 }
             }
             Transaction::Dispute(d) => {
                 let (amount, state) = get_deposit_and_state_mut(deposits, d.tx)?;
-                (*account, *state) = account.dispute(d, amount, *state)?;
+                let from = *state;
+                let disputes = histories.get(&tx).map_or(0, |history| history.disputes);
+                if config.max_disputes.is_some_and(|max| disputes >= max) {
+                    return Err(TransactionExecutionError::DisputeLimitReached(tx));
+                }
+                let funding = config.dispute_funding;
+                let shortfall = account.shortfall;
+                let fee = compute_fee(&fees.dispute, fees.rounding, amount, income)?;
+                let (disputed, disputed_state) =
+                    account.dispute(d, amount, *state, policy, funding)?;
+                (*account, *state) = (disputed.charge_fee(fee, true)?, disputed_state);
+                charged = Some((FeeKind::Dispute, fee, disputed));
+                if account.shortfall != shortfall {
+                    shortfalls.insert(tx, account.shortfall - shortfall);
+                }
+                stats.open_disputes += 1;
+
+                let history = histories.entry(tx).or_default();
+                history.disputes += 1;
+                history.transitions.push((sequence, *state));
+                Some((tx, Some(from), *state))
             }
             Transaction::ChargeBack(c) => {
                 let (amount, state) = get_deposit_and_state_mut(deposits, c.tx)?;
+                let from = *state;
+                let shortfall = shortfalls.get(&tx).copied().unwrap_or_default();
 
-                (*account, *state) = account.charge_back(c, amount, *state)?;
+                let fee = compute_fee(&fees.charge_back, fees.rounding, amount, income)?;
+                // the shortfall stays on the account as a receivable
+                let (charged_back, charged_back_state) =
+                    account.charge_back(c, amount, *state, policy, shortfall)?;
+                (*account, *state) = (charged_back.charge_fee(fee, true)?, charged_back_state);
+                charged = Some((FeeKind::ChargeBack, fee, charged_back));
+                stats.open_disputes -= 1;
+                stats.charged_back = stats.charged_back.saturating_add(amount);
+                record_transition(histories, tx, sequence, *state);
+                Some((tx, Some(from), *state))
             }
             Transaction::Resolve(r) => {
                 let (amount, state) = get_deposit_and_state_mut(deposits, r.tx)?;
+                let from = *state;
+                let shortfall = shortfalls.get(&tx).copied().unwrap_or_default();
+
+                (*account, *state) = account.resolve(r, amount, *state, policy, shortfall)?;
+                if config.resolved_is_final {
+                    *state = DepositState::Resolved;
+                }
+                shortfalls.remove(&tx);
+                stats.open_disputes -= 1;
+                record_transition(histories, tx, sequence, *state);
+                Some((tx, Some(from), *state))
+            }
+            Transaction::Representment(r) => {
+                let (amount, state) = get_deposit_and_state_mut(deposits, r.tx)?;
+                let from = *state;
+
+                (*account, *state) = account.represent(r, amount, *state, policy)?;
+                record_transition(histories, tx, sequence, *state);
+                Some((tx, Some(from), *state))
+            }
+            Transaction::Reversal(r) => {
+                let unlock = match config.reversal_unlock {
+                    ReversalUnlock::Keep => false,
+                    ReversalUnlock::Unlock => true,
+                    ReversalUnlock::UnlockIfSettled => {
+                        deposits.iter().all(|(other, (_, state))| {
+                            *other == tx
+                                || !matches!(
+                                    state,
+                                    DepositState::ChargedBack | DepositState::Represented
+                                )
+                        })
+                    }
+                };
+                let (amount, state) = get_deposit_and_state_mut(deposits, r.tx)?;
+                let from = *state;
+                let shortfall = shortfalls.get(&tx).copied().unwrap_or_default();
+
+                (*account, *state) =
+                    account.reverse(r, amount, *state, policy, shortfall, unlock)?;
+                shortfalls.remove(&tx);
+                stats.charged_back = stats.charged_back.saturating_sub(amount);
+                record_transition(histories, tx, sequence, *state);
+                Some((tx, Some(from), *state))
+            }
+            Transaction::Clear(c) => {
+                let (amount, state) = get_deposit_and_state_mut(deposits, c.tx)?;
+                let from = *state;
+
+                (*account, *state) = account.clear(c, amount, *state, policy)?;
+                Some((tx, Some(from), *state))
+            }
+            Transaction::Return(r) => {
+                let (amount, state) = get_deposit_and_state_mut(deposits, r.tx)?;
+                let from = *state;
 
-                (*account, *state) = account.resolve(r, amount, *state)?;
+                (*account, *state) = account.return_deposit(r, amount, *state, policy)?;
+                Some((tx, Some(from), *state))
             }
             Transaction::Withdrawal(w) => {
-                *account = account.withdraw(w)?;
+                let amount = w.amount;
+                let rules = config.withdrawal_rules.for_client(client_id);
+                if !rules.is_empty() {
+                    rules.check(withdrawals, amount, sequence, self.now)?;
+                }
+                let fee = compute_fee(&fees.withdrawal, fees.rounding, amount, income)?;
+                let withdrawn = account.withdraw(w, policy)?;
+                *account = withdrawn.charge_fee(fee, false)?;
+                if !rules.is_empty() {
+                    withdrawals.record(sequence, self.now, amount);
+                }
+                stats.withdrawals += 1;
+                stats.withdrawn = stats.withdrawn.saturating_add(amount);
+                charged = Some((FeeKind::Withdrawal, fee, withdrawn));
+                None
+            }
+            Transaction::Authorize(a) => {
+                let Vacant(entry) = authorizations.entry(tx) else {
+                    return Err(TransactionExecutionError::DuplicateAuthorization(tx));
+                };
+                let amount = a.amount;
+                *account = account.authorize(a, policy)?;
+                entry.insert(Authorization {
+                    remaining: amount,
+                    captured: UCurrency::ZERO,
+                    sequence,
+                    authorized_at: self.now,
+                });
+                if config.authorization_expiry.is_some() || config.authorization_timeout.is_some() {
+                    self.expiries.push_back((client_id, tx, sequence));
+                }
+                None
             }
+            Transaction::Capture(c) => {
+                let Occupied(mut entry) = authorizations.entry(tx) else {
+                    return Err(TransactionExecutionError::AuthorizationNotFound(tx));
+                };
+                let amount = c.amount;
+                // a capture is the actual withdrawal, held to the rules and paying its fee
+                let rules = config.withdrawal_rules.for_client(client_id);
+                if !rules.is_empty() {
+                    rules.check(withdrawals, amount, sequence, self.now)?;
+                }
+                let fee = compute_fee(&fees.withdrawal, fees.rounding, amount, income)?;
+                let (captured, remaining) = account.capture(c, entry.get().remaining, policy)?;
+                *account = captured.charge_fee(fee, false)?;
+                if !rules.is_empty() {
+                    withdrawals.record(sequence, self.now, amount);
+                }
+                charged = Some((FeeKind::Withdrawal, fee, captured));
+                if remaining == UCurrency::ZERO {
+                    entry.remove();
+                } else {
+                    let authorization = entry.get_mut();
+                    authorization.remaining = remaining;
+                    authorization.captured += amount;
+                }
+                stats.withdrawals += 1;
+                stats.withdrawn = stats.withdrawn.saturating_add(amount);
+                None
+            }
+            Transaction::Release(r) => {
+                let Occupied(entry) = authorizations.entry(tx) else {
+                    return Err(TransactionExecutionError::AuthorizationNotFound(tx));
+                };
+                *account = account.release(r, entry.get().remaining, policy)?;
+                entry.remove();
+                None
+            }
+            Transaction::Limit(l) => {
+                if !config.allow_admin_rows {
+                    return Err(TransactionExecutionError::AdminRowsNotAllowed);
+                }
+                *account = account.set_limit(l, policy)?;
+                None
+            }
+            // posted to every client above
+            Transaction::Accrue(_) => None,
+        };
+        stats.last_tx = Some(tx);
+        let charged = charged.filter(|(_, fee, _)| *fee > UCurrency::ZERO);
+        if let Some((kind, fee, _)) = charged {
+            fee_entries.push(FeeEntry {
+                sequence,
+                tx,
+                kind,
+                amount: fee,
+            });
+            stats.fees = stats.fees.saturating_add(fee);
+            self.fee_income = income + fee;
+        }
+
+        Ok(Applied {
+            transition,
+            uncharged: charged.map(|(_, _, uncharged)| uncharged),
+        })
+    }
+
+    /// Like [`Ledger::execute`] but calls `on_event` with the change of the account if
+    /// `transaction` succeeds, followed by [`EventKind::Fee`] if a fee was charged for it,
+    /// followed by [`EventKind::Locked`] or [`EventKind::Unlocked`] if it changed the lock of the
+    /// account. Accrual runs emit an [`EventKind::Accrued`] for every client instead.
+    /// Authorizations expiring before `transaction` executes are emitted first as
+    /// [`EventKind::Expired`], followed by pending deposits clearing on their own as
+    /// [`EventKind::Cleared`].
+    pub fn execute_with_events(
+        &mut self,
+        transaction: Transaction,
+        mut on_event: impl FnMut(AccountEvent),
+    ) -> Result<&mut Self, TransactionExecutionError> {
+        let client_id = transaction.get_client_id();
+        let tx = transaction.get_tx();
+        let metadata = transaction.metadata().clone();
+        // the clock moves before anything expires, rejecting like execute would
+        if let Err(error) = self.advance_clock(&transaction) {
+            self.sequence += 1;
+            return Err(self.reject(&transaction, error));
         }
+        self.expire_authorizations(self.sequence + 1, &mut on_event);
+        self.clear_pending_deposits(self.sequence + 1, &mut on_event);
+        let kind = match transaction {
+            Transaction::Deposit(_) => EventKind::Deposited,
+            Transaction::Dispute(_) => EventKind::Disputed,
+            Transaction::ChargeBack(_) => EventKind::ChargedBack,
+            Transaction::Resolve(_) => EventKind::Resolved,
+            Transaction::Withdrawal(_) => EventKind::Withdrawn,
+            Transaction::Representment(_) => EventKind::Represented,
+            Transaction::Reversal(_) => EventKind::Reversed,
+            Transaction::Authorize(_) => EventKind::Authorized,
+            Transaction::Capture(_) => EventKind::Captured,
+            Transaction::Release(_) => EventKind::Released,
+            Transaction::Clear(_) => EventKind::Cleared,
+            Transaction::Return(_) => EventKind::Returned,
+            Transaction::Limit(_) => EventKind::LimitChanged,
+            Transaction::Accrue(_) => {
+                self.execute_accruing(transaction, |event| {
+                    on_event(AccountEvent {
+                        metadata: metadata.clone(),
+                        ..event
+                    })
+                })?;
+                return Ok(self);
+            }
+        };
+        let before = self
+            .clients
+            .get(&client_id)
+            .map_or_else(|| ClientAccount::new(client_id), |client| client.account);
 
+        let uncharged = self.execute_accruing(transaction, |_| {})?;
+
+        let after = self.clients[&client_id].account;
+        match uncharged {
+            Some(uncharged) => {
+                on_event(AccountEvent {
+                    kind,
+                    tx,
+                    before,
+                    after: uncharged,
+                    metadata: metadata.clone(),
+                });
+                on_event(AccountEvent {
+                    kind: EventKind::Fee,
+                    tx,
+                    before: uncharged,
+                    after,
+                    metadata: metadata.clone(),
+                });
+            }
+            None => on_event(AccountEvent {
+                kind,
+                tx,
+                before,
+                after,
+                metadata: metadata.clone(),
+            }),
+        }
+        if after.locked != before.locked {
+            on_event(AccountEvent {
+                kind: if after.locked {
+                    EventKind::Locked
+                } else {
+                    EventKind::Unlocked
+                },
+                tx,
+                before,
+                after,
+                metadata,
+            });
+        }
         Ok(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ClientAccount, ClientAccountAndDeposits};
+    use super::{
+        ClientAccount, ClientAccountAndDeposits, ClientStats, DisputeHistory, LedgerConfig,
+        ReconciliationError,
+    };
     use crate::transaction::Transaction;
-    use crate::{icur, ucur, DepositState, Ledger, TransactionExecutionError, UCurrency};
-    use std::collections::HashMap;
+    use crate::EventKind;
+    use crate::{
+        icur, ucur, DepositState, Ledger, LockPolicy, TransactionExecutionError, UCurrency,
+    };
+    use crate::{rules::Window, RuleSet, WithdrawalRules};
+    use crate::{DisputeFunding, Fee, FeeEntry, FeeKind, FeeSchedule, ReversalUnlock};
+    use crate::{InterestConfig, InterestEntry, InterestRate, Rounding, DEFAULT_TIER};
+    use std::{collections::HashMap, time::Duration};
 
     //make it easier to construct stuff
     #[allow(non_upper_case_globals)]
@@ -138,10 +1076,28 @@ mod tests {
                             held: ucur!(0),
                             available: icur!(1),
                             locked: false,
+                            shortfall: ucur!(0),
+                            reserved: ucur!(0),
+                            pending: ucur!(0),
+                            overdraft_limit: ucur!(0),
+                        },
+                        deposits: HashMap::from([(tx, (amount, DepositState::Ok))]),
+                        shortfalls: HashMap::new(),
+                        histories: HashMap::new(),
+                        authorizations: HashMap::new(),
+                        fees: Vec::new(),
+                        accruals: Vec::new(),
+                        withdrawals: Default::default(),
+                        stats: ClientStats {
+                            deposits: 1,
+                            last_tx: Some(tx),
+                            ..Default::default()
                         },
-                        deposits: HashMap::from([(tx, (amount, DepositState::Ok))])
                     },
                 )]),
+                sequence: 1,
+                deposits: 1,
+                ..Default::default()
             })
         );
     }
@@ -165,10 +1121,35 @@ mod tests {
                             held: amount,
                             available: icur!(0),
                             locked: false,
+                            shortfall: ucur!(0),
+                            reserved: ucur!(0),
+                            pending: ucur!(0),
+                            overdraft_limit: ucur!(0),
+                        },
+                        deposits: HashMap::from([(tx, (amount, DepositState::Disputed))]),
+                        shortfalls: HashMap::new(),
+                        histories: HashMap::from([(
+                            tx,
+                            DisputeHistory {
+                                disputes: 1,
+                                transitions: vec![(2, DepositState::Disputed)],
+                            }
+                        )]),
+                        authorizations: HashMap::new(),
+                        fees: Vec::new(),
+                        accruals: Vec::new(),
+                        withdrawals: Default::default(),
+                        stats: ClientStats {
+                            deposits: 1,
+                            open_disputes: 1,
+                            last_tx: Some(tx),
+                            ..Default::default()
                         },
-                        deposits: HashMap::from([(tx, (amount, DepositState::Disputed))])
                     })
-                )])
+                )]),
+                sequence: 2,
+                deposits: 1,
+                ..Default::default()
             }
         )
     }
@@ -179,15 +1160,32 @@ mod tests {
         let dispute = Transaction::new_dispute(tx, client);
         let resolve = Transaction::new_resolve(tx, client);
 
-        //deposit -> dispute -> resolve should == deposit alone
+        //deposit -> dispute -> resolve should == deposit alone, apart from the history
+        let mut resolved = Ledger::default();
+        resolved
+            .execute(deposit.clone())
+            .unwrap()
+            .execute(dispute)
+            .unwrap()
+            .execute(resolve)
+            .unwrap();
+        let mut deposited = Ledger::default();
+        deposited.execute(deposit).unwrap();
+
+        let client_state = |ledger: &Ledger| {
+            let ClientAccountAndDeposits {
+                account, deposits, ..
+            } = ledger.clients[&client].clone();
+            (account, deposits)
+        };
+        assert_eq!(client_state(&resolved), client_state(&deposited));
+        assert_eq!(resolved.stats(client), deposited.stats(client));
         assert_eq!(
-            Ledger::default()
-                .execute(deposit.clone())
-                .unwrap()
-                .execute(dispute)
-                .unwrap()
-                .execute(resolve),
-            Ledger::default().execute(deposit)
+            resolved.dispute_history(client, tx),
+            Some(&DisputeHistory {
+                disputes: 1,
+                transitions: vec![(2, DepositState::Disputed), (3, DepositState::Ok)],
+            })
         );
     }
 
@@ -238,10 +1236,40 @@ mod tests {
                             held: ucur!(0),
                             available: icur!(-1),
                             locked: true,
+                            shortfall: ucur!(0),
+                            reserved: ucur!(0),
+                            pending: ucur!(0),
+                            overdraft_limit: ucur!(0),
+                        },
+                        deposits: HashMap::from([(tx, (amount, DepositState::ChargedBack))]),
+                        shortfalls: HashMap::new(),
+                        histories: HashMap::from([(
+                            tx,
+                            DisputeHistory {
+                                disputes: 1,
+                                transitions: vec![
+                                    (3, DepositState::Disputed),
+                                    (4, DepositState::ChargedBack)
+                                ],
+                            }
+                        )]),
+                        authorizations: HashMap::new(),
+                        fees: Vec::new(),
+                        accruals: Vec::new(),
+                        withdrawals: Default::default(),
+                        stats: ClientStats {
+                            deposits: 1,
+                            withdrawals: 1,
+                            withdrawn: amount,
+                            charged_back: amount,
+                            last_tx: Some(tx),
+                            ..Default::default()
                         },
-                        deposits: HashMap::from([(tx, (amount, DepositState::ChargedBack))])
                     }
-                )])
+                )]),
+                sequence: 4,
+                deposits: 1,
+                ..Default::default()
             })
         )
     }
@@ -284,4 +1312,1230 @@ mod tests {
             })
         )
     }
+
+    #[test]
+    fn failed_transactions_dont_count_in_stats() {
+        let mut ledger = Ledger::default();
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap();
+        ledger
+            .execute(Transaction::new_dispute(tx, client))
+            .unwrap();
+        assert!(ledger
+            .execute(Transaction::new_withdrawal(2, client, amount))
+            .is_err());
+        assert!(ledger.execute(Transaction::new_dispute(3, client)).is_err());
+
+        assert_eq!(
+            ledger.stats(client),
+            Some(&ClientStats {
+                deposits: 1,
+                open_disputes: 1,
+                last_tx: Some(tx),
+                ..Default::default()
+            })
+        );
+        ledger
+            .execute(Transaction::new_resolve(tx, client))
+            .unwrap();
+        assert_eq!(ledger.stats(client).unwrap().open_disputes, 0);
+        assert_eq!(ledger.stats(client + 1), None);
+    }
+
+    #[test]
+    fn uses_lock_policy() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            lock_policy: LockPolicy::FROZEN,
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap()
+            .execute(Transaction::new_deposit(2, client, amount))
+            .unwrap()
+            .execute(Transaction::new_dispute(tx, client))
+            .unwrap()
+            .execute(Transaction::new_dispute(2, client))
+            .unwrap()
+            .execute(Transaction::new_charge_back(tx, client))
+            .unwrap();
+
+        for transaction in [
+            Transaction::new_deposit(3, client, amount),
+            Transaction::new_resolve(2, client),
+        ] {
+            assert_eq!(
+                ledger.execute(transaction),
+                Err(TransactionExecutionError::AccountLocked)
+            );
+        }
+        // the default still allows deposits into a locked account
+        assert_eq!(
+            Ledger::default().config().lock_policy,
+            LockPolicy::default()
+        );
+    }
+
+    /// Deposits 10, withdraws 8 and disputes the deposit with `funding`
+    fn dispute_withdrawn_funds(
+        funding: DisputeFunding,
+    ) -> (Ledger, Result<(), TransactionExecutionError>) {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            dispute_funding: funding,
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(2, client, ucur!(8)))
+            .unwrap();
+        let result = ledger
+            .execute(Transaction::new_dispute(tx, client))
+            .map(|_| ());
+        (ledger, result)
+    }
+
+    fn account(ledger: &Ledger) -> ClientAccount {
+        *ledger.iter().find(|account| account.id == client).unwrap()
+    }
+
+    #[test]
+    fn disputes_can_drive_available_negative() {
+        let (ledger, result) = dispute_withdrawn_funds(DisputeFunding::AllowNegative);
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                available: icur!(-8),
+                held: ucur!(10),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn disputes_exceeding_available_can_be_rejected() {
+        let (ledger, result) = dispute_withdrawn_funds(DisputeFunding::Reject);
+        assert_eq!(result, Err(TransactionExecutionError::InsufficientFunds));
+        assert_eq!(account(&ledger).available, icur!(2));
+        assert_eq!(ledger.stats(client).unwrap().open_disputes, 0);
+    }
+
+    #[test]
+    fn disputes_exceeding_available_can_lock() {
+        let (ledger, result) = dispute_withdrawn_funds(DisputeFunding::Lock);
+        assert_eq!(result, Ok(()));
+        let account = account(&ledger);
+        assert!(account.locked);
+        assert_eq!((account.available, account.held), (icur!(-8), ucur!(10)));
+    }
+
+    #[test]
+    fn disputes_can_hold_available_and_record_shortfall() {
+        let (mut ledger, result) = dispute_withdrawn_funds(DisputeFunding::HoldAvailable);
+        assert_eq!(result, Ok(()));
+        let disputed = ClientAccount {
+            id: client,
+            available: icur!(0),
+            held: ucur!(2),
+            shortfall: ucur!(8),
+            reserved: ucur!(0),
+            pending: ucur!(0),
+            overdraft_limit: ucur!(0),
+            locked: false,
+        };
+        assert_eq!(account(&ledger), disputed);
+
+        // resolving releases what was held and clears the shortfall
+        let mut resolved = ledger.clone();
+        resolved
+            .execute(Transaction::new_resolve(tx, client))
+            .unwrap();
+        assert_eq!(
+            account(&resolved),
+            ClientAccount {
+                available: icur!(2),
+                held: ucur!(0),
+                shortfall: ucur!(0),
+                reserved: ucur!(0),
+                pending: ucur!(0),
+                overdraft_limit: ucur!(0),
+                ..disputed
+            }
+        );
+
+        // charging back keeps the shortfall as a receivable
+        ledger
+            .execute(Transaction::new_charge_back(tx, client))
+            .unwrap();
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                held: ucur!(0),
+                locked: true,
+                ..disputed
+            }
+        );
+        assert_eq!(
+            ledger.stats(client).unwrap().charged_back,
+            ucur!(10),
+            "the full deposit is charged back"
+        );
+    }
+
+    #[test]
+    fn limits_disputes_per_deposit() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            max_disputes: Some(2),
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap();
+        for _ in 0..2 {
+            ledger
+                .execute(Transaction::new_dispute(tx, client))
+                .unwrap()
+                .execute(Transaction::new_resolve(tx, client))
+                .unwrap();
+        }
+        assert_eq!(
+            ledger.execute(Transaction::new_dispute(tx, client)),
+            Err(TransactionExecutionError::DisputeLimitReached(tx))
+        );
+        let history = ledger.dispute_history(client, tx).unwrap();
+        assert_eq!(history.disputes, 2);
+        assert_eq!(
+            history
+                .transitions
+                .iter()
+                .map(|(seq, _)| *seq)
+                .collect::<Vec<_>>(),
+            [2, 3, 4, 5]
+        );
+        assert_eq!(ledger.sequence(), 6);
+    }
+
+    #[test]
+    fn resolved_can_be_final() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            resolved_is_final: true,
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap()
+            .execute(Transaction::new_dispute(tx, client))
+            .unwrap()
+            .execute(Transaction::new_resolve(tx, client))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_dispute(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Ok,
+                actual_state: DepositState::Resolved
+            })
+        );
+        assert_eq!(account(&ledger).available, icur!(1));
+        assert_eq!(
+            ledger.dispute_history(client, tx).unwrap().transitions,
+            [(2, DepositState::Disputed), (3, DepositState::Resolved)]
+        );
+        assert_eq!(ledger.dispute_history(client, tx + 1), None);
+    }
+
+    fn charge_back_deposits(reversal_unlock: ReversalUnlock, deposits: u32) -> Ledger {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            reversal_unlock,
+            ..Default::default()
+        });
+        for deposit in 1..=deposits {
+            ledger
+                .execute(Transaction::new_deposit(deposit, client, amount))
+                .unwrap()
+                .execute(Transaction::new_dispute(deposit, client))
+                .unwrap()
+                .execute(Transaction::new_charge_back(deposit, client))
+                .unwrap()
+                .execute(Transaction::new_representment(deposit, client))
+                .unwrap();
+        }
+        ledger
+    }
+
+    #[test]
+    fn reversal_credits_charged_back_funds() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            dispute_funding: DisputeFunding::HoldAvailable,
+            reversal_unlock: ReversalUnlock::Unlock,
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(2, client, ucur!(8)))
+            .unwrap()
+            .execute(Transaction::new_dispute(tx, client))
+            .unwrap()
+            .execute(Transaction::new_charge_back(tx, client))
+            .unwrap()
+            .execute(Transaction::new_representment(tx, client))
+            .unwrap();
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                locked: true,
+                shortfall: ucur!(8),
+                ..Default::default()
+            }
+        );
+
+        ledger
+            .execute(Transaction::new_reversal(tx, client))
+            .unwrap();
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                available: icur!(2),
+                ..Default::default()
+            }
+        );
+        assert_eq!(ledger.stats(client).unwrap().charged_back, ucur!(0));
+        assert_eq!(
+            ledger.dispute_history(client, tx).unwrap().transitions,
+            [
+                (3, DepositState::Disputed),
+                (4, DepositState::ChargedBack),
+                (5, DepositState::Represented),
+                (6, DepositState::Reversed)
+            ]
+        );
+    }
+
+    #[test]
+    fn reversal_requires_representment() {
+        let mut ledger = Ledger::default();
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_representment(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::ChargedBack,
+                actual_state: DepositState::Ok
+            })
+        );
+        ledger
+            .execute(Transaction::new_dispute(tx, client))
+            .unwrap()
+            .execute(Transaction::new_charge_back(tx, client))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_reversal(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Represented,
+                actual_state: DepositState::ChargedBack
+            })
+        );
+        ledger
+            .execute(Transaction::new_representment(tx, client))
+            .unwrap()
+            .execute(Transaction::new_reversal(tx, client))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_reversal(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Represented,
+                actual_state: DepositState::Reversed
+            })
+        );
+        assert_eq!(
+            ledger.execute(Transaction::new_dispute(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Ok,
+                actual_state: DepositState::Reversed
+            })
+        );
+        // the default keeps the account locked
+        assert!(account(&ledger).locked);
+        assert_eq!(account(&ledger).available, icur!(1));
+    }
+
+    #[test]
+    fn reversal_unlocks_once_settled() {
+        let mut ledger = charge_back_deposits(ReversalUnlock::UnlockIfSettled, 2);
+        ledger
+            .execute(Transaction::new_reversal(1, client))
+            .unwrap();
+        assert!(account(&ledger).locked);
+        ledger
+            .execute(Transaction::new_reversal(2, client))
+            .unwrap();
+        assert!(!account(&ledger).locked);
+
+        let mut ledger = charge_back_deposits(ReversalUnlock::Unlock, 2);
+        ledger
+            .execute(Transaction::new_reversal(1, client))
+            .unwrap();
+        assert!(!account(&ledger).locked);
+    }
+
+    fn authorized(config: LedgerConfig) -> Ledger {
+        let mut ledger = Ledger::with_config(config);
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+            .unwrap()
+            .execute(Transaction::new_authorize(2, client, ucur!(6)))
+            .unwrap();
+        ledger
+    }
+
+    #[test]
+    fn authorizations_reserve_funds_until_captured_or_released() {
+        let mut ledger = authorized(LedgerConfig::default());
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                available: icur!(4),
+                reserved: ucur!(6),
+                ..Default::default()
+            }
+        );
+        assert_eq!(account(&ledger).total(), icur!(10));
+        assert_eq!(
+            ledger.execute(Transaction::new_authorize(3, client, ucur!(5))),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        assert_eq!(
+            ledger.execute(Transaction::new_authorize(2, client, ucur!(1))),
+            Err(TransactionExecutionError::DuplicateAuthorization(2))
+        );
+
+        ledger
+            .execute(Transaction::new_capture(2, client, ucur!(2)))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_capture(2, client, ucur!(5))),
+            Err(TransactionExecutionError::ExceedsAuthorization(2))
+        );
+        let authorization = ledger.authorization(client, 2).unwrap();
+        assert_eq!(
+            (authorization.remaining, authorization.captured),
+            (ucur!(4), ucur!(2))
+        );
+        assert_eq!(
+            (account(&ledger).available, account(&ledger).reserved),
+            (icur!(4), ucur!(4))
+        );
+
+        ledger.execute(Transaction::new_release(2, client)).unwrap();
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                available: icur!(8),
+                ..Default::default()
+            }
+        );
+        assert_eq!(ledger.authorization(client, 2), None);
+        assert_eq!(
+            ledger.execute(Transaction::new_release(2, client)),
+            Err(TransactionExecutionError::AuthorizationNotFound(2))
+        );
+        assert_eq!(ledger.stats(client).unwrap().withdrawals, 1);
+    }
+
+    #[test]
+    fn full_capture_closes_the_authorization() {
+        let mut ledger = authorized(LedgerConfig::default());
+        ledger
+            .execute(Transaction::new_capture(2, client, ucur!(6)))
+            .unwrap();
+        assert_eq!(ledger.authorization(client, 2), None);
+        assert_eq!(account(&ledger).total(), icur!(4));
+    }
+
+    #[test]
+    fn authorizations_expire_after_transactions() {
+        let mut ledger = authorized(LedgerConfig {
+            authorization_expiry: Some(2),
+            ..Default::default()
+        });
+        // released before the transaction making it expire, for any client
+        ledger
+            .execute(Transaction::new_deposit(3, 2, ucur!(1)))
+            .unwrap();
+        assert_eq!(account(&ledger).reserved, ucur!(6));
+        let mut events = Vec::new();
+        ledger
+            .execute_with_events(Transaction::new_withdrawal(4, client, ucur!(10)), |e| {
+                events.push(e)
+            })
+            .unwrap();
+        assert_eq!(
+            events.iter().map(|e| (e.kind, e.tx)).collect::<Vec<_>>(),
+            [(EventKind::Expired, 2), (EventKind::Withdrawn, 4)]
+        );
+        assert_eq!(events[0].after.available, icur!(10));
+        assert_eq!(ledger.authorization(client, 2), None);
+        assert_eq!(
+            ledger.execute(Transaction::new_capture(2, client, ucur!(1))),
+            Err(TransactionExecutionError::AuthorizationNotFound(2))
+        );
+    }
+
+    /// `transaction` with the `timestamp` metadata column set to `time`
+    fn at(mut transaction: Transaction, time: u64) -> Transaction {
+        transaction
+            .metadata_mut()
+            .insert("timestamp", time.to_string());
+        transaction
+    }
+
+    fn timed(config: LedgerConfig) -> LedgerConfig {
+        LedgerConfig {
+            timestamp_column: Some("timestamp".to_string()),
+            ..config
+        }
+    }
+
+    #[test]
+    fn authorizations_expire_after_a_timeout() {
+        let mut ledger = Ledger::with_config(timed(LedgerConfig {
+            authorization_timeout: Some(Duration::from_secs(60)),
+            ..Default::default()
+        }));
+        ledger
+            .execute(at(Transaction::new_deposit(tx, client, ucur!(10)), 1000))
+            .unwrap()
+            .execute(at(Transaction::new_authorize(2, client, ucur!(6)), 1000))
+            .unwrap();
+        // time only moves with the timestamps, however long processing takes
+        ledger
+            .execute(Transaction::new_capture(2, client, ucur!(1)))
+            .unwrap();
+        ledger
+            .execute(at(Transaction::new_release(9, client), 1059))
+            .ok();
+        assert_eq!(account(&ledger).reserved, ucur!(5));
+        ledger
+            .execute(at(Transaction::new_release(9, client), 1060))
+            .ok();
+        assert_eq!(ledger.now(), 1060);
+        assert_eq!(account(&ledger).available, icur!(9));
+        // an earlier timestamp doesn't turn the clock back
+        ledger
+            .execute(at(Transaction::new_release(9, client), 5))
+            .ok();
+        assert_eq!(ledger.now(), 1060);
+
+        // without a timestamp column time stands still
+        let mut ledger = authorized(LedgerConfig {
+            authorization_timeout: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        ledger
+            .execute(at(Transaction::new_release(9, client), 10))
+            .ok();
+        assert_eq!(account(&ledger).available, icur!(4));
+        assert_eq!(ledger.now(), 0);
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        let mut ledger = Ledger::with_config(timed(LedgerConfig::default()));
+        let mut deposit = Transaction::new_deposit(tx, client, ucur!(1));
+        deposit.metadata_mut().insert("timestamp", "yesterday");
+        assert_eq!(
+            ledger.execute(deposit.clone()),
+            Err(TransactionExecutionError::InvalidTimestamp(
+                "yesterday".to_string()
+            ))
+        );
+        assert_eq!(
+            ledger.execute_with_events(deposit, |_| {}).err(),
+            Some(TransactionExecutionError::InvalidTimestamp(
+                "yesterday".to_string()
+            ))
+        );
+        assert_eq!((ledger.sequence(), ledger.iter().count()), (2, 0));
+        // an empty timestamp is no timestamp
+        let mut deposit = Transaction::new_deposit(tx, client, ucur!(1));
+        deposit.metadata_mut().insert("timestamp", "");
+        assert!(ledger.execute(deposit).is_ok());
+    }
+
+    #[test]
+    fn identical_ledgers_compare_equal() {
+        let run = || {
+            let mut ledger = Ledger::with_config(LedgerConfig {
+                authorization_timeout: Some(Duration::from_secs(60)),
+                ..Default::default()
+            });
+            ledger
+                .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+                .unwrap()
+                .execute(Transaction::new_authorize(2, client, ucur!(6)))
+                .unwrap();
+            ledger
+        };
+        assert_eq!(run(), run());
+    }
+
+    fn pending(config: LedgerConfig) -> Ledger {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            pending_deposits: true,
+            ..config
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+            .unwrap();
+        ledger
+    }
+
+    #[test]
+    fn pending_deposits_are_not_available() {
+        let mut ledger = pending(LedgerConfig::default());
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                pending: ucur!(10),
+                ..Default::default()
+            }
+        );
+        assert_eq!(account(&ledger).total(), icur!(10));
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(2, client, ucur!(1))),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        assert_eq!(
+            ledger.execute(Transaction::new_dispute(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Ok,
+                actual_state: DepositState::Pending
+            })
+        );
+
+        let mut returned = ledger.clone();
+        returned
+            .execute(Transaction::new_return(tx, client))
+            .unwrap();
+        assert_eq!(account(&returned), ClientAccount::new(client));
+        assert_eq!(
+            returned.execute(Transaction::new_clear(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Pending,
+                actual_state: DepositState::Returned
+            })
+        );
+
+        ledger
+            .execute(Transaction::new_clear(tx, client))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(2, client, ucur!(1)))
+            .unwrap();
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                available: icur!(9),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            ledger.execute(Transaction::new_return(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Pending,
+                actual_state: DepositState::Ok
+            })
+        );
+    }
+
+    #[test]
+    fn deposits_clear_immediately_by_default() {
+        let mut ledger = Ledger::default();
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_clear(tx, client)),
+            Err(TransactionExecutionError::InvalidDepositState {
+                tx,
+                expected_state: DepositState::Pending,
+                actual_state: DepositState::Ok
+            })
+        );
+    }
+
+    #[test]
+    fn pending_deposits_clear_after_transactions() {
+        let mut ledger = pending(LedgerConfig {
+            clearing_period: Some(3),
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(2, client, ucur!(1)))
+            .unwrap()
+            .execute(Transaction::new_return(2, client))
+            .unwrap();
+        assert_eq!(account(&ledger).pending, ucur!(10));
+
+        let mut events = Vec::new();
+        ledger
+            .execute_with_events(Transaction::new_withdrawal(3, client, ucur!(10)), |e| {
+                events.push(e)
+            })
+            .unwrap();
+        assert_eq!(
+            events.iter().map(|e| (e.kind, e.tx)).collect::<Vec<_>>(),
+            [(EventKind::Cleared, tx), (EventKind::Withdrawn, 3)]
+        );
+        assert_eq!(events[0].after.available, icur!(10));
+        assert_eq!(account(&ledger).total(), icur!(0));
+        // the returned deposit was skipped
+        ledger
+            .execute(Transaction::new_deposit(4, client, ucur!(1)))
+            .unwrap();
+        assert_eq!(account(&ledger).pending, ucur!(1));
+    }
+
+    #[test]
+    fn pending_deposits_clear_after_a_timeout() {
+        let mut ledger = Ledger::with_config(timed(LedgerConfig {
+            pending_deposits: true,
+            clearing_timeout: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        }));
+        ledger
+            .execute(at(Transaction::new_deposit(tx, client, ucur!(10)), 7200))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(at(Transaction::new_withdrawal(2, client, ucur!(4)), 10799)),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        ledger
+            .execute(at(Transaction::new_withdrawal(2, client, ucur!(4)), 10800))
+            .unwrap();
+        assert_eq!(account(&ledger).available, icur!(6));
+    }
+
+    #[test]
+    fn pending_deposits_that_would_overflow_are_retried() {
+        let mut ledger = pending(LedgerConfig {
+            clearing_period: Some(2),
+            ..Default::default()
+        });
+        let max = UCurrency::from_num(crate::ICurrency::MAX) - ucur!(10);
+        ledger
+            .execute(Transaction::new_clear(tx, client))
+            .unwrap()
+            .execute(Transaction::new_deposit(2, client, max))
+            .unwrap()
+            .execute(Transaction::new_clear(2, client))
+            .unwrap()
+            .execute(Transaction::new_deposit(3, client, ucur!(10)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(4, client, ucur!(1)))
+            .unwrap();
+        // clearing deposit 3 would overflow available so it's still pending
+        ledger
+            .execute(Transaction::new_withdrawal(5, client, ucur!(20)))
+            .unwrap();
+        assert_eq!(account(&ledger).pending, ucur!(10));
+        ledger
+            .execute(Transaction::new_withdrawal(6, client, ucur!(1)))
+            .unwrap();
+        assert_eq!(account(&ledger).pending, ucur!(0));
+        assert_eq!(
+            account(&ledger).available,
+            crate::ICurrency::MAX - icur!(12)
+        );
+    }
+
+    fn with_fees() -> Ledger {
+        Ledger::with_config(LedgerConfig {
+            fees: FeeSchedule {
+                withdrawal: "0.5+1%".parse().unwrap(),
+                dispute: "0.25".parse().unwrap(),
+                charge_back: Fee {
+                    fixed: ucur!(2),
+                    percent: ucur!(0),
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn withdrawal_fees_need_available_funds() {
+        let mut ledger = with_fees();
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+            .unwrap();
+        // 9.5 + 0.5 + 0.095 exceeds the 10 available
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(2, client, ucur!(9.5))),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        assert_eq!(account(&ledger).available, icur!(10));
+        assert_eq!(ledger.fees(client), []);
+
+        ledger
+            .execute(Transaction::new_withdrawal(3, client, ucur!(5)))
+            .unwrap();
+        assert_eq!(account(&ledger).available, icur!(4.45));
+        assert_eq!(
+            ledger.fees(client),
+            [FeeEntry {
+                sequence: ledger.sequence(),
+                tx: 3,
+                kind: FeeKind::Withdrawal,
+                amount: ucur!(0.55),
+            }]
+        );
+        assert_eq!(ledger.fee_income(), ucur!(0.55));
+        assert_eq!(ledger.stats(client).unwrap().fees, ucur!(0.55));
+    }
+
+    #[test]
+    fn captures_pay_the_withdrawal_fee() {
+        let mut ledger = with_fees();
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(10)))
+            .unwrap()
+            .execute(Transaction::new_authorize(2, client, ucur!(9.6)))
+            .unwrap();
+        // the 0.55 fee comes out of the 0.4 left available
+        assert_eq!(
+            ledger.execute(Transaction::new_capture(2, client, ucur!(5))),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        assert_eq!(account(&ledger).reserved, ucur!(9.6));
+
+        let mut events = Vec::new();
+        ledger
+            .execute(Transaction::new_deposit(3, client, ucur!(1)))
+            .unwrap()
+            .execute_with_events(Transaction::new_capture(2, client, ucur!(5)), |e| {
+                events.push(e)
+            })
+            .unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.kind).collect::<Vec<_>>(),
+            [EventKind::Captured, EventKind::Fee]
+        );
+        assert_eq!(
+            (events[0].after.available, events[0].after.reserved),
+            (icur!(1.4), ucur!(4.6))
+        );
+        assert_eq!(events[1].before, events[0].after);
+        assert_eq!(events[1].after, account(&ledger));
+        assert_eq!(account(&ledger).available, icur!(1.4) - icur!(0.55));
+        assert_eq!(
+            ledger.fees(client),
+            [FeeEntry {
+                sequence: ledger.sequence(),
+                tx: 2,
+                kind: FeeKind::Withdrawal,
+                amount: ucur!(0.55),
+            }]
+        );
+    }
+
+    #[test]
+    fn dispute_and_charge_back_fees_can_drive_available_negative() {
+        let mut ledger = with_fees();
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap()
+            .execute(Transaction::new_dispute(tx, client))
+            .unwrap();
+        assert_eq!(
+            account(&ledger),
+            ClientAccount {
+                id: client,
+                available: icur!(-0.25),
+                held: amount,
+                ..Default::default()
+            }
+        );
+
+        let mut events = Vec::new();
+        ledger
+            .execute_with_events(Transaction::new_charge_back(tx, client), |e| events.push(e))
+            .unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.kind).collect::<Vec<_>>(),
+            [EventKind::ChargedBack, EventKind::Fee, EventKind::Locked]
+        );
+        assert_eq!(events[0].after.available, icur!(-0.25));
+        assert_eq!(events[1].before, events[0].after);
+        assert_eq!(events[1].after.available, icur!(-2.25));
+        assert_eq!(
+            ledger
+                .fees(client)
+                .iter()
+                .map(|fee| (fee.kind, fee.amount))
+                .collect::<Vec<_>>(),
+            [
+                (FeeKind::Dispute, ucur!(0.25)),
+                (FeeKind::ChargeBack, ucur!(2))
+            ]
+        );
+        assert_eq!(ledger.fee_income(), ucur!(2.25));
+    }
+
+    #[test]
+    fn books_reconcile_after_every_kind_of_transaction() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            dispute_funding: DisputeFunding::HoldAvailable,
+            fees: FeeSchedule {
+                withdrawal: "0.5+1%".parse().unwrap(),
+                dispute: "0.25".parse().unwrap(),
+                ..Default::default()
+            },
+            interest: InterestConfig {
+                rates: HashMap::from([(DEFAULT_TIER.to_string(), "1%/10%".parse().unwrap())]),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        for transaction in [
+            Transaction::new_deposit(1, 1, ucur!(10)),
+            Transaction::new_deposit(2, 1, ucur!(5)),
+            Transaction::new_withdrawal(3, 1, ucur!(12)),
+            // only 2.38 of the 10 can be held
+            Transaction::new_dispute(1, 1),
+            Transaction::new_charge_back(1, 1),
+            Transaction::new_deposit(4, 2, ucur!(30)),
+            Transaction::new_authorize(5, 2, ucur!(8)),
+            Transaction::new_capture(5, 2, ucur!(3)),
+            Transaction::new_dispute(4, 2),
+            Transaction::new_resolve(4, 2),
+            Transaction::new_accrue(6, 0),
+            Transaction::new_representment(1, 1),
+            Transaction::new_reversal(1, 1),
+            Transaction::new_accrue(7, 0),
+        ] {
+            ledger.execute(transaction).unwrap();
+            assert_eq!(ledger.reconcile(), Ok(()));
+        }
+        assert_ne!(ledger.fee_income(), ucur!(0));
+        assert_ne!(ledger.interest_paid(), icur!(0));
+
+        let mut broken = ledger.clone();
+        broken.clients.get_mut(&2).unwrap().account.available += icur!(0.0001);
+        assert_eq!(
+            broken.reconcile(),
+            Err(ReconciliationError::ClientTotal {
+                client: 2,
+                total: broken.clients[&2].account.total(),
+            })
+        );
+        let mut broken = ledger.clone();
+        broken.fee_income += ucur!(1);
+        assert_eq!(
+            broken.reconcile(),
+            Err(ReconciliationError::FeeIncome(broken.fee_income()))
+        );
+        let mut broken = ledger;
+        broken.interest_paid -= icur!(1);
+        assert_eq!(
+            broken.reconcile(),
+            Err(ReconciliationError::InterestPaid(broken.interest_paid()))
+        );
+    }
+
+    fn with_interest() -> Ledger {
+        let rate = |credit, debit| InterestRate { credit, debit };
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            interest: InterestConfig {
+                rates: HashMap::from([
+                    (DEFAULT_TIER.to_string(), rate(ucur!(1), ucur!(10))),
+                    ("gold".to_string(), rate(ucur!(2), ucur!(5))),
+                ]),
+                tiers: HashMap::from([(2, "gold".to_string()), (3, "none".to_string())]),
+                rounding: Rounding::HalfUp,
+            },
+            ..Default::default()
+        });
+        for client_id in 1..=3 {
+            ledger
+                .execute(Transaction::new_deposit(
+                    client_id.into(),
+                    client_id,
+                    ucur!(50),
+                ))
+                .unwrap();
+        }
+        ledger
+    }
+
+    #[test]
+    fn accrues_interest_by_tier() {
+        let mut ledger = with_interest();
+        let mut events = Vec::new();
+        ledger
+            .execute_with_events(Transaction::new_accrue(10, 0), |e| events.push(e))
+            .unwrap();
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| (e.kind, e.after.id, e.after.available))
+                .collect::<Vec<_>>(),
+            [
+                (EventKind::Accrued, 1, icur!(50.5)),
+                (EventKind::Accrued, 2, icur!(51))
+            ]
+        );
+        assert_eq!(
+            ledger.accruals(1),
+            [InterestEntry {
+                sequence: ledger.sequence(),
+                tx: 10,
+                amount: icur!(0.5),
+            }]
+        );
+        assert_eq!(ledger.accruals(3), []);
+        assert_eq!(ledger.interest_paid(), icur!(1.5));
+        assert_eq!(ledger.stats(2).unwrap().interest, icur!(1));
+        // accrual runs don't create clients
+        assert_eq!(ledger.iter().count(), 3);
+    }
+
+    #[test]
+    fn charges_interest_on_negative_balances() {
+        let mut ledger = with_interest();
+        ledger
+            .execute(Transaction::new_withdrawal(4, client, ucur!(45)))
+            .unwrap()
+            .execute(Transaction::new_dispute(client.into(), client))
+            .unwrap()
+            .execute(Transaction::new_charge_back(client.into(), client))
+            .unwrap()
+            .accrue(11)
+            .unwrap();
+        // 10% of -45 charged on the locked account, 1% of 50 paid to client 2
+        assert_eq!(ledger.accruals(client)[0].amount, icur!(-4.5));
+        assert_eq!(account(&ledger).available, icur!(-49.5));
+        assert!(account(&ledger).locked);
+        assert_eq!(ledger.interest_paid(), icur!(-3.5));
+
+        // reconciles with the movements of every client
+        let total = ledger
+            .iter()
+            .map(|account| account.total())
+            .sum::<crate::ICurrency>();
+        assert_eq!(
+            total,
+            icur!(150) - icur!(45) - icur!(50) + ledger.interest_paid()
+        );
+    }
+
+    #[test]
+    fn overdraft_limits_come_from_config_and_admin_transactions() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            overdraft_limits: HashMap::from([(client, ucur!(5))]),
+            allow_admin_rows: true,
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, amount))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(2, client, ucur!(6)))
+            .unwrap();
+        assert_eq!(account(&ledger).available, icur!(-5));
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(3, client, ucur!(0.0001))),
+            Err(TransactionExecutionError::OverLimit)
+        );
+
+        let mut events = Vec::new();
+        ledger
+            .execute_with_events(Transaction::new_limit(4, client, ucur!(8)), |e| {
+                events.push(e)
+            })
+            .unwrap();
+        assert_eq!(events[0].kind, EventKind::LimitChanged);
+        assert_eq!(events[0].after.overdraft_limit, ucur!(8));
+        ledger
+            .execute(Transaction::new_withdrawal(5, client, ucur!(3)))
+            .unwrap();
+        assert_eq!(account(&ledger).available, icur!(-8));
+
+        // lowering the limit below the balance only stops further withdrawals
+        ledger
+            .execute(Transaction::new_limit(6, client, ucur!(0)))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(7, client, amount)),
+            Err(TransactionExecutionError::InsufficientFunds)
+        );
+        assert_eq!(account(&ledger).available, icur!(-8));
+    }
+
+    #[test]
+    fn limit_rows_need_to_be_allowed_and_an_unlocked_account() {
+        let mut ledger = Ledger::default();
+        assert_eq!(
+            ledger.execute(Transaction::new_limit(tx, client, ucur!(1000))),
+            Err(TransactionExecutionError::AdminRowsNotAllowed)
+        );
+        assert_eq!(account(&ledger).overdraft_limit, ucur!(0));
+
+        let locked = |lock_policy| {
+            let mut ledger = Ledger::with_config(LedgerConfig {
+                allow_admin_rows: true,
+                lock_policy,
+                ..Default::default()
+            });
+            ledger
+                .execute(Transaction::new_deposit(tx, client, amount))
+                .unwrap()
+                .execute(Transaction::new_dispute(tx, client))
+                .unwrap()
+                .execute(Transaction::new_charge_back(tx, client))
+                .unwrap();
+            ledger
+        };
+        assert_eq!(
+            locked(LockPolicy::default()).execute(Transaction::new_limit(2, client, ucur!(1000))),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+        let policy = LockPolicy {
+            limit: crate::policy::LockRule::AllowUpTo(ucur!(10)),
+            ..Default::default()
+        };
+        let mut ledger = locked(policy);
+        ledger
+            .execute(Transaction::new_limit(2, client, ucur!(10)))
+            .unwrap();
+        assert_eq!(account(&ledger).overdraft_limit, ucur!(10));
+    }
+
+    #[test]
+    fn checks_withdrawal_rules_before_withdrawing() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            withdrawal_rules: RuleSet {
+                default: WithdrawalRules {
+                    max_amount: Some(ucur!(5)),
+                    max_count: Some((2, Window::Transactions(100))),
+                    ..Default::default()
+                },
+                clients: HashMap::from([(2, WithdrawalRules::default())]),
+            },
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(20)))
+            .unwrap();
+        // rules are checked before the funds
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(2, client, ucur!(50))),
+            Err(TransactionExecutionError::AmountLimitExceeded)
+        );
+        ledger
+            .execute(Transaction::new_withdrawal(4, client, ucur!(5)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(5, client, ucur!(5)))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(6, client, ucur!(5))),
+            Err(TransactionExecutionError::WithdrawalCountExceeded)
+        );
+        assert_eq!(account(&ledger).available, icur!(10));
+
+        // the empty entry of client 2 replaces the default rules
+        ledger
+            .execute(Transaction::new_deposit(7, 2, ucur!(20)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(8, 2, ucur!(20)))
+            .unwrap();
+        // client 3 has no entry and inherits the default rules
+        ledger
+            .execute(Transaction::new_deposit(9, 3, ucur!(20)))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_withdrawal(10, 3, ucur!(6))),
+            Err(TransactionExecutionError::AmountLimitExceeded)
+        );
+    }
+
+    #[test]
+    fn checks_withdrawal_rules_before_capturing() {
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            withdrawal_rules: RuleSet {
+                default: WithdrawalRules {
+                    max_amount: Some(ucur!(5)),
+                    max_count: Some((2, Window::Transactions(100))),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        ledger
+            .execute(Transaction::new_deposit(tx, client, ucur!(20)))
+            .unwrap()
+            .execute(Transaction::new_authorize(2, client, ucur!(10)))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_capture(2, client, ucur!(6))),
+            Err(TransactionExecutionError::AmountLimitExceeded)
+        );
+        // captures and withdrawals count against the same limits
+        ledger
+            .execute(Transaction::new_capture(2, client, ucur!(5)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(3, client, ucur!(5)))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(Transaction::new_capture(2, client, ucur!(1))),
+            Err(TransactionExecutionError::WithdrawalCountExceeded)
+        );
+        assert_eq!(account(&ledger).available, icur!(5));
+        assert_eq!(account(&ledger).reserved, ucur!(5));
+    }
+
+    #[test]
+    fn withdrawal_time_windows_follow_input_timestamps() {
+        let mut ledger = Ledger::with_config(timed(LedgerConfig {
+            withdrawal_rules: RuleSet {
+                default: WithdrawalRules {
+                    max_count: Some((1, Window::Time(Duration::from_secs(60 * 60)))),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
+        ledger
+            .execute(at(Transaction::new_deposit(tx, client, ucur!(20)), 1000))
+            .unwrap()
+            .execute(at(Transaction::new_withdrawal(2, client, amount), 1000))
+            .unwrap();
+        assert_eq!(
+            ledger.execute(at(Transaction::new_withdrawal(3, client, amount), 4599)),
+            Err(TransactionExecutionError::WithdrawalCountExceeded)
+        );
+        ledger
+            .execute(at(Transaction::new_withdrawal(4, client, amount), 4600))
+            .unwrap();
+        assert_eq!(account(&ledger).available, icur!(18));
+    }
 }
@@ -1,8 +1,25 @@
+pub mod binary;
 mod client;
+mod client_config;
+pub mod compression;
 mod error;
+pub mod events;
+#[cfg(feature = "fast-parser")]
+pub mod fast;
+pub mod fees;
+mod format;
+pub mod interest;
 mod ledger;
+mod ndjson;
+pub mod observer;
+pub mod parallel;
 mod parser;
+pub mod policy;
+mod report;
+pub mod rules;
+mod scanner;
 mod transaction;
+pub mod validation;
 mod writer;
 
 type Frac = fixed::types::extra::U16;
@@ -24,23 +41,80 @@ pub type UCurrency = fixed::FixedU64<Frac>;
 pub use fixed_macro::types::I48F16 as icur;
 pub use fixed_macro::types::U48F16 as ucur;
 
+pub use binary::parse_binary;
 pub use client::*;
+pub use client_config::load_client_config;
+pub use compression::{compress, decompress, Compression};
+pub use error::Error;
+pub use events::{AccountEvent, EventFormat, EventKind, EventWriter};
+#[cfg(feature = "fast-parser")]
+pub use fast::parse_fast;
+pub use fees::{Fee, FeeEntry, FeeKind, FeeSchedule, Rounding};
+pub use format::{InputFormat, OutputFormat, TransactionReader, UnknownFormat};
+pub use interest::{InterestConfig, InterestEntry, InterestRate, DEFAULT_TIER};
 pub use ledger::*;
-pub use parser::{parse_csv, parse_from_reader};
+pub use ndjson::{parse_ndjson, parse_ndjson_with_config};
+pub use observer::LedgerObserver;
+pub use parallel::{map_file, parse_parallel, ParallelOptions};
+pub use parser::{
+    parse_csv, parse_from_reader, parse_with_config, Header, ParserConfig, ParserError,
+    RecordError, RecordPosition,
+};
+pub use policy::{DisputeFunding, LockPolicy, ReversalUnlock};
+pub use report::{ExecutionReport, TypeCounts};
+pub use rules::{load_withdrawal_rules, RuleSet, WithdrawalRules};
 pub use transaction::*;
-pub use writer::write_csv;
+pub use writer::{
+    write_csv, write_csv_with_config, write_json, write_json_with_config, write_ndjson,
+    write_ndjson_with_config, AccountFilter, Column, NumberFormat, SortOrder, WriterConfig,
+};
+
+/// Executes all the transactions, counting the ones that fail to parse
+fn execute_all(
+    ledger: &mut Ledger,
+    transactions: impl Iterator<Item = Result<Transaction, ParserError>>,
+) -> ExecutionReport {
+    let start = std::time::Instant::now();
+    let mut report = ExecutionReport::default();
+    for transaction in transactions {
+        match transaction {
+            Ok(transaction) => {
+                let ty = TransactionDiscriminants::from(&transaction);
+                let error = ledger.execute(transaction).err();
+                report.record(ledger, ty, error.as_ref());
+            }
+            Err(_) => report.record_parse_error(),
+        }
+    }
+    report.finish(ledger, start.elapsed());
+    report
+}
 
 pub fn execute<R: std::io::Read, W: std::io::Write>(
     reader: R,
     writer: W,
-) -> Result<(), error::Error> {
+) -> Result<ExecutionReport, error::Error> {
     let transactions = parse_csv(reader)?;
     let mut ledger = Ledger::default();
-    for transaction in transactions.flatten() {
-        ledger.execute(transaction).ok();
-    }
+    let report = execute_all(&mut ledger, transactions);
+
+    write_csv(&ledger, writer)?;
+    Ok(report)
+}
+
+/// Same as [`execute`] but with explicit input and output formats.
+pub fn execute_with_formats<R: std::io::BufRead, W: std::io::Write>(
+    reader: R,
+    writer: W,
+    input: InputFormat,
+    output: OutputFormat,
+) -> Result<ExecutionReport, error::Error> {
+    let transactions = TransactionReader::new(reader, input)?;
+    let mut ledger = Ledger::default();
+    let report = execute_all(&mut ledger, transactions);
 
-    Ok(write_csv(&ledger, writer)?)
+    output.write(&ledger, writer)?;
+    Ok(report)
 }
 
 #[cfg(test)]
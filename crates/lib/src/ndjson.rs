@@ -0,0 +1,398 @@
+use crate::{
+    parser::{
+        failing_field, lenient_trim, parse_fields, parse_fields_lenient, Header, ParserConfig,
+        ParserError, RecordError, RecordPosition,
+    },
+    transaction::Transaction,
+    validation,
+};
+use serde::Deserialize;
+use serde_json::value::RawValue;
+use std::{borrow::Cow, collections::HashMap, io::BufRead, sync::Arc};
+
+/// A single NDJSON line with every field kept as raw JSON text.
+///
+/// Numbers are deliberately not deserialized into floats as that would lose precision,
+/// instead the raw text is handed to the same validation as the CSV parser.
+#[derive(Deserialize)]
+struct RawTransaction<'a> {
+    #[serde(rename = "type", borrow)]
+    ty: Option<&'a RawValue>,
+    #[serde(borrow)]
+    tx: Option<&'a RawValue>,
+    #[serde(borrow)]
+    client: Option<&'a RawValue>,
+    #[serde(borrow)]
+    amount: Option<&'a RawValue>,
+}
+
+/// Returns the textual content of a raw JSON value, unquoting strings.
+/// `null` is treated the same as the field being absent.
+fn raw_text(value: Option<&RawValue>) -> Result<Option<Cow<'_, str>>, ParserError> {
+    match value.map(RawValue::get) {
+        None | Some("null") => Ok(None),
+        Some(s) if s.starts_with('"') => Ok(Some(serde_json::from_str::<Cow<str>>(s)?)),
+        Some(s) => Ok(Some(Cow::Borrowed(s))),
+    }
+}
+
+fn parse_line(line: &str, lenient: bool, strict: bool) -> Result<Transaction, ParserError> {
+    let raw: RawTransaction = serde_json::from_str(line)?;
+
+    let ty = raw_text(raw.ty)?.ok_or(ParserError::MissingHeader(Header::Type))?;
+    let tx = raw_text(raw.tx)?.ok_or(ParserError::MissingHeader(Header::Tx))?;
+    let client = raw_text(raw.client)?.ok_or(ParserError::MissingHeader(Header::Client))?;
+    let amount = raw_text(raw.amount)?;
+    let amount = amount.as_deref().map(str::as_bytes);
+
+    let result = if lenient {
+        parse_fields_lenient(ty.as_bytes(), tx.as_bytes(), client.as_bytes(), amount)
+    } else {
+        parse_fields(ty.as_bytes(), tx.as_bytes(), client.as_bytes(), amount)
+    };
+
+    if strict {
+        validation::validate(result, amount, lenient)
+    } else {
+        result
+    }
+}
+
+/// Copies the configured extra keys of `line` into the transaction's metadata,
+/// absent and `null` keys are left out
+fn add_metadata(
+    line: &str,
+    extras: &[Arc<str>],
+    mut transaction: Transaction,
+) -> Result<Transaction, ParserError> {
+    let object: HashMap<String, &RawValue> = serde_json::from_str(line)?;
+    let metadata = transaction.metadata_mut();
+    for column in extras {
+        if let Some(value) = raw_text(object.get(column.as_ref()).copied())? {
+            metadata.insert(column.clone(), value);
+        }
+    }
+    Ok(transaction)
+}
+
+#[cold]
+fn line_record_error(
+    line: &str,
+    position: RecordPosition,
+    lenient: bool,
+    source: ParserError,
+) -> ParserError {
+    let field = match &source {
+        ParserError::JsonError(_) => None,
+        _ => serde_json::from_str::<RawTransaction>(line)
+            .ok()
+            .and_then(|raw| {
+                let text = |value| raw_text(value).ok().flatten();
+                let (tx, client, ty) = (text(raw.tx), text(raw.client), text(raw.ty));
+                failing_field(
+                    &source,
+                    tx.as_deref().map(str::as_bytes),
+                    client.as_deref().map(str::as_bytes),
+                    ty.as_deref().map(str::as_bytes),
+                    lenient,
+                )
+            }),
+    };
+
+    let row = line.trim_end().to_string();
+    // point at the key of the failing field, or the end of the line if it is absent
+    let span = field.map(|field| {
+        let key = format!("\"{field}\"");
+        match row.find(&key) {
+            Some(start) => start..start + key.len(),
+            None => row.len()..row.len(),
+        }
+    });
+
+    ParserError::InvalidRecord(Box::new(RecordError {
+        position,
+        field,
+        row,
+        span,
+        source,
+    }))
+}
+
+pub(crate) struct TransactionNdJsonIterator<R: BufRead> {
+    buf: String,
+    reader: R,
+    /// Position of the line currently in `buf`
+    position: RecordPosition,
+    lenient: bool,
+    strict: bool,
+    /// Keys to carry along in the transaction metadata
+    extras: Vec<Arc<str>>,
+}
+
+impl<R: BufRead> TransactionNdJsonIterator<R> {
+    pub(crate) fn new(reader: R, lenient: bool, strict: bool) -> Self {
+        Self {
+            buf: String::new(),
+            reader,
+            position: RecordPosition::default(),
+            lenient,
+            strict,
+            extras: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_extra_columns(mut self, extra_columns: &[String]) -> Self {
+        self.extras = extra_columns
+            .iter()
+            .map(|c| Arc::from(c.as_str()))
+            .collect();
+        self
+    }
+
+    /// Uses the parts of `config` that apply to NDJSON, see [`parse_ndjson_with_config`]
+    pub(crate) fn from_config(reader: R, config: &ParserConfig) -> Self {
+        Self::new(reader, config.lenient, config.strict).with_extra_columns(&config.extra_columns)
+    }
+}
+
+impl<R: BufRead> Iterator for TransactionNdJsonIterator<R> {
+    type Item = Result<Transaction, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.position.byte += self.buf.len() as u64;
+            self.position.line += 1;
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf) {
+                Ok(0) => return None, //EOF
+                Ok(_) if self.buf.trim().is_empty() => continue,
+                Ok(_) => {
+                    let line = if self.lenient {
+                        lenient_trim(&self.buf)
+                    } else {
+                        &self.buf
+                    };
+                    let mut result = parse_line(line, self.lenient, self.strict);
+                    if !self.extras.is_empty() {
+                        result = result.and_then(|t| add_metadata(line, &self.extras, t));
+                    }
+                    return Some(result.map_err(|e| {
+                        line_record_error(&self.buf, self.position, self.lenient, e)
+                    }));
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+/// Parses newline delimited JSON where each line is an object with the fields
+/// `type`, `client`, `tx` and optionally `amount`.
+///
+/// Numeric fields may be given either as JSON numbers or as strings.
+/// The field values follow the same rules as the CSV parser with the default config.
+pub fn parse_ndjson(
+    reader: impl BufRead,
+) -> Result<impl Iterator<Item = Result<Transaction, ParserError>>, ParserError> {
+    Ok(TransactionNdJsonIterator::new(reader, false, false))
+}
+
+/// Like [`parse_ndjson`] but with [`ParserConfig::lenient`] and [`ParserConfig::strict`] applying
+/// to the field values, the keys themselves are always case sensitive.
+/// [`ParserConfig::extra_columns`] are looked up as keys and may be absent on any line.
+/// The CSV dialect settings are ignored.
+pub fn parse_ndjson_with_config(
+    reader: impl BufRead,
+    config: &ParserConfig,
+) -> Result<impl Iterator<Item = Result<Transaction, ParserError>>, ParserError> {
+    Ok(TransactionNdJsonIterator::from_config(reader, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    #[test]
+    fn can_parse_numbers_and_strings() {
+        assert_eq!(
+            parse_line(
+                r#"{"type":"deposit","client":1,"tx":2,"amount":10.0001}"#,
+                false,
+                false
+            )
+            .unwrap(),
+            Transaction::new_deposit(2, 1, ucur!(10.0001))
+        );
+        assert_eq!(
+            parse_line(
+                r#"{"type":"withdrawal","client":"1","tx":"2","amount":"1.5"}"#,
+                false,
+                false
+            )
+            .unwrap(),
+            Transaction::new_withdrawal(2, 1, ucur!(1.5))
+        );
+    }
+
+    #[test]
+    fn amount_is_optional_for_disputes() {
+        assert_eq!(
+            parse_line(r#"{"type":"dispute","client":1,"tx":2}"#, false, false).unwrap(),
+            Transaction::new_dispute(2, 1)
+        );
+        assert_eq!(
+            parse_line(
+                r#"{"type":"resolve","client":1,"tx":2,"amount":null}"#,
+                false,
+                false
+            )
+            .unwrap(),
+            Transaction::new_resolve(2, 1)
+        );
+    }
+
+    #[test]
+    fn validation_matches_csv() {
+        assert!(matches!(
+            parse_line(r#"{"type":"deposit","client":1,"tx":2}"#, false, false),
+            Err(ParserError::MissingHeader(Header::Amount))
+        ));
+        assert!(matches!(
+            parse_line(
+                r#"{"type":"deposit","client":1,"tx":2,"amount":-1}"#,
+                false,
+                false
+            ),
+            Err(ParserError::CurrencyParseError(_))
+        ));
+        assert!(matches!(
+            parse_line(
+                r#"{"type":"Deposit","client":1,"tx":2,"amount":1}"#,
+                false,
+                false
+            ),
+            Err(ParserError::InvalidTypeField(_))
+        ));
+        assert!(matches!(
+            parse_line(
+                r#"{"type":"deposit","client":70000,"tx":2,"amount":1}"#,
+                false,
+                false
+            ),
+            Err(ParserError::IntParseError(_))
+        ));
+        assert!(matches!(
+            parse_line(r#"{"client":1,"tx":2}"#, false, false),
+            Err(ParserError::MissingHeader(Header::Type))
+        ));
+        assert!(matches!(
+            parse_line(r#"{"type":"deposit""#, false, false),
+            Err(ParserError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn errors_contain_position_and_field() {
+        let input = "{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":1}\n\n{\"type\":\"deposit\",\"client\":1,\"tx\":\"x\",\"amount\":1}\n";
+        let error = parse_ndjson(input.as_bytes())
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap();
+        let ParserError::InvalidRecord(error) = error else {
+            panic!("expected InvalidRecord but got {error:?}");
+        };
+        assert_eq!(error.position, RecordPosition { line: 3, byte: 49 });
+        assert_eq!(error.field, Some(Header::Tx));
+        assert!(matches!(error.source, ParserError::IntParseError(_)));
+        assert_eq!(&error.row[error.span.clone().unwrap()], "\"tx\"");
+    }
+
+    #[test]
+    fn skips_blank_lines() {
+        let input = "{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":1}\n\n  \n{\"type\":\"dispute\",\"client\":1,\"tx\":1}";
+        let transactions = parse_ndjson(input.as_bytes())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            transactions,
+            vec![
+                Transaction::new_deposit(1, 1, ucur!(1)),
+                Transaction::new_dispute(1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn lenient_mode_accepts_sloppy_input() {
+        let input = "\u{FEFF}{\"type\":\" Deposit\u{A0}\",\"client\":1,\"tx\":1,\"amount\":1}\n";
+        assert!(parse_ndjson(input.as_bytes())
+            .unwrap()
+            .next()
+            .unwrap()
+            .is_err());
+        let config = ParserConfig {
+            lenient: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_ndjson_with_config(input.as_bytes(), &config)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![Transaction::new_deposit(1, 1, ucur!(1))]
+        );
+    }
+
+    #[test]
+    fn strict_mode_validates_amounts() {
+        assert!(matches!(
+            parse_line(
+                r#"{"type":"deposit","client":1,"tx":2,"amount":1.00001}"#,
+                false,
+                true
+            ),
+            Err(ParserError::ExcessPrecision(_))
+        ));
+        assert!(matches!(
+            parse_line(
+                r#"{"type":"dispute","client":1,"tx":2,"amount":"1"}"#,
+                false,
+                true
+            ),
+            Err(ParserError::UnexpectedAmount(_))
+        ));
+        assert_eq!(
+            parse_line(
+                r#"{"type":"dispute","client":1,"tx":2,"amount":null}"#,
+                false,
+                true
+            )
+            .unwrap(),
+            Transaction::new_dispute(2, 1)
+        );
+    }
+
+    #[test]
+    fn can_carry_extra_keys() {
+        let input = concat!(
+            r#"{"type":"deposit","client":1,"tx":2,"amount":1,"memo":"a \"b\"","ts":17}"#,
+            "\n",
+            r#"{"type":"dispute","client":1,"tx":2,"memo":null}"#,
+        );
+        let config = ParserConfig {
+            extra_columns: vec!["memo".to_string(), "ts".to_string()],
+            ..Default::default()
+        };
+        let transactions = parse_ndjson_with_config(input.as_bytes(), &config)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let metadata = transactions[0].metadata();
+        assert_eq!(metadata.get("memo"), Some("a \"b\""));
+        assert_eq!(metadata.get("ts"), Some("17"));
+        assert!(transactions[1].metadata().is_empty());
+    }
+}
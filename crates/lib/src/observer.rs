@@ -0,0 +1,216 @@
+//! Hooks for reacting to what happens in a [`Ledger`](crate::Ledger) without changing it.
+use crate::{ClientAccount, DepositState, ICurrency, Transaction, TransactionExecutionError};
+
+/// Callbacks made by [`Ledger::execute`](crate::Ledger::execute) for every registered observer,
+/// see [`Ledger::add_observer`](crate::Ledger::add_observer). All methods default to doing nothing.
+///
+/// For a successful transaction the callbacks are made in the order
+/// [`on_applied`](Self::on_applied), [`on_deposit_state_changed`](Self::on_deposit_state_changed)
+/// and [`on_lock_changed`](Self::on_lock_changed), the latter two only if something changed.
+/// Each callback is made for all observers, in the order they were added, before the next.
+/// Transactions are passed with their [`Transaction::metadata`].
+pub trait LedgerObserver {
+    /// `transaction` was applied, `account` is the state after it
+    fn on_applied(&mut self, _transaction: &Transaction, _account: &ClientAccount) {}
+
+    /// `transaction` was rejected and the ledger is unchanged
+    fn on_rejected(&mut self, _transaction: &Transaction, _error: &TransactionExecutionError) {}
+
+    /// The `locked` flag of `account` changed because of the transaction `tx`
+    fn on_lock_changed(&mut self, _account: &ClientAccount, _tx: u32) {}
+
+    /// The open authorization `tx` of `account` expired and its funds were released,
+    /// this happens before the transaction that made it expire is applied
+    fn on_authorization_expired(&mut self, _account: &ClientAccount, _tx: u32) {}
+
+    /// The accrual run `tx` posted `interest` to `account`, negative if it was charged. This
+    /// happens for every client of the run before [`on_applied`](Self::on_applied) is called
+    /// with the `accrue` transaction and the account of its client, which may not exist.
+    fn on_interest_accrued(&mut self, _account: &ClientAccount, _tx: u32, _interest: ICurrency) {}
+
+    /// The deposit `tx` of `account` went from `from` to `to`, `from` is `None` for new deposits
+    fn on_deposit_state_changed(
+        &mut self,
+        _account: &ClientAccount,
+        _tx: u32,
+        _from: Option<DepositState>,
+        _to: DepositState,
+    ) {
+    }
+}
+
+/// The observers of a ledger, which don't take part in comparing ledgers.
+/// Observers are attached to a single ledger so a clone starts out without any.
+/// They have to be `Send` so the ledger stays `Send`.
+#[derive(Default)]
+pub(crate) struct Observers(pub(crate) Vec<Box<dyn LedgerObserver + Send>>);
+
+impl Observers {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Observers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Observers({})", self.0.len())
+    }
+}
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for Observers {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ucur, InterestConfig, Ledger, LedgerConfig, DEFAULT_TIER};
+    use std::sync::{Arc, Mutex};
+
+    /// Records every callback as a string
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl LedgerObserver for Recorder {
+        fn on_applied(&mut self, transaction: &Transaction, account: &ClientAccount) {
+            let tx = transaction.get_tx();
+            let available = account.available;
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("applied {tx} {available}"));
+        }
+
+        fn on_rejected(&mut self, transaction: &Transaction, error: &TransactionExecutionError) {
+            let tx = transaction.get_tx();
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("rejected {tx} {error}"));
+        }
+
+        fn on_lock_changed(&mut self, account: &ClientAccount, tx: u32) {
+            let locked = account.locked;
+            self.0.lock().unwrap().push(format!("locked {tx} {locked}"));
+        }
+
+        fn on_deposit_state_changed(
+            &mut self,
+            _account: &ClientAccount,
+            tx: u32,
+            from: Option<DepositState>,
+            to: DepositState,
+        ) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("deposit {tx} {from:?} -> {to}"));
+        }
+
+        fn on_interest_accrued(&mut self, account: &ClientAccount, tx: u32, interest: ICurrency) {
+            let client = account.id;
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("accrued {tx} {client} {interest}"));
+        }
+    }
+
+    #[test]
+    fn observers_see_every_change() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut ledger = Ledger::default();
+        ledger.add_observer(Recorder(calls.clone()));
+        ledger.add_observer(Recorder(calls.clone()));
+
+        for transaction in [
+            Transaction::new_deposit(1, 1, ucur!(2)),
+            Transaction::new_withdrawal(2, 1, ucur!(3)),
+            Transaction::new_dispute(1, 1),
+            Transaction::new_charge_back(1, 1),
+        ] {
+            ledger.execute(transaction).ok();
+        }
+
+        let calls = calls.lock().unwrap();
+        // every callback is made for both observers in turn
+        assert!(calls.chunks(2).all(|pair| pair[0] == pair[1]));
+        let calls = calls.iter().step_by(2).collect::<Vec<_>>();
+        assert_eq!(
+            calls,
+            [
+                "applied 1 2",
+                "deposit 1 None -> Ok",
+                "rejected 2 Inssuficient funds in account",
+                "applied 1 0",
+                "deposit 1 Some(Ok) -> Disputed",
+                "applied 1 0",
+                "deposit 1 Some(Disputed) -> ChargedBack",
+                "locked 1 true",
+            ]
+        );
+    }
+
+    #[test]
+    fn observers_see_accruals() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            interest: InterestConfig {
+                rates: [(DEFAULT_TIER.to_string(), "10%/10%".parse().unwrap())].into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        ledger.add_observer(Recorder(calls.clone()));
+        ledger
+            .execute(Transaction::new_deposit(1, 1, ucur!(10)))
+            .unwrap()
+            .execute(Transaction::new_deposit(2, 2, ucur!(20)))
+            .unwrap()
+            .accrue(7)
+            .unwrap();
+        assert_eq!(
+            calls.lock().unwrap()[4..],
+            ["accrued 7 1 1", "accrued 7 2 2", "applied 7 0"]
+        );
+    }
+
+    #[test]
+    fn observers_see_invalid_timestamps() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut ledger = Ledger::with_config(LedgerConfig {
+            timestamp_column: Some("timestamp".to_string()),
+            ..Default::default()
+        });
+        ledger.add_observer(Recorder(calls.clone()));
+        let mut transaction = Transaction::new_deposit(1, 1, ucur!(2));
+        transaction.metadata_mut().insert("timestamp", "noon");
+        assert!(ledger.execute(transaction.clone()).is_err());
+        assert!(ledger.execute_with_events(transaction, |_| {}).is_err());
+        let rejected = "rejected 1 The timestamp `noon` isn't a whole number of seconds";
+        assert_eq!(*calls.lock().unwrap(), [rejected, rejected]);
+    }
+
+    #[test]
+    fn observers_dont_affect_equality() {
+        let mut observed = Ledger::default();
+        observed.add_observer(Recorder(Default::default()));
+        observed
+            .execute(Transaction::new_deposit(1, 1, ucur!(2)))
+            .unwrap();
+        let mut plain = Ledger::default();
+        plain
+            .execute(Transaction::new_deposit(1, 1, ucur!(2)))
+            .unwrap();
+        assert_eq!(observed, plain);
+        assert_eq!(observed.clone(), plain);
+    }
+}
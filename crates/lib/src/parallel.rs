@@ -0,0 +1,313 @@
+//! Multi threaded CSV parsing of memory mapped files.
+//!
+//! The input is split into chunks that end on record boundaries, each chunk is parsed on its own
+//! thread by the regular parser, or the `fast-parser` scanner when enabled, seeked to the start
+//! of the chunk and the results are yielded in input order. Positions, line numbers and errors are exactly the same
+//! as when parsing sequentially with [`parse_with_config`](crate::parse_with_config).
+//!
+//! Splitting requires finding record boundaries without parsing, so input containing the quote
+//! character, bare `\r` line endings or compression falls back to sequential parsing.
+use crate::{
+    compression::{decompress, Compression, Decompressed},
+    parser::{ParserConfig, ParserError, TransactionCsvIterator},
+    scanner::RecordScanner,
+    transaction::Transaction,
+};
+use memchr::memchr;
+use std::{fs::File, io::Cursor, path::Path};
+
+/// Settings for [`parse_parallel`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelOptions {
+    /// Number of chunks parsed at the same time
+    pub threads: usize,
+    /// Minimum number of bytes in a chunk, a chunk always ends on a record boundary
+    pub chunk_size: usize,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            chunk_size: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Memory maps the file at `path` so it can be parsed by [`parse_parallel`]
+pub fn map_file(path: impl AsRef<Path>) -> std::io::Result<memmap2::Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: the map is only ever read. Another process truncating or modifying the file while
+    // it's mapped is undefined behaviour, which like any other mmap based tool we have to accept.
+    unsafe { memmap2::Mmap::map(&file) }
+}
+
+/// A run of records starting at `position` and ending at byte `end`
+#[derive(Debug)]
+struct Chunk {
+    position: csv::Position,
+    end: usize,
+}
+
+/// Splits the body of `data` into chunks of at least `chunk_size` bytes.
+/// Returns `None` if the record boundaries can't be found without a full parse.
+fn split_chunks(
+    data: &[u8],
+    config: &ParserConfig,
+    start: csv::Position,
+    chunk_size: usize,
+) -> Option<Vec<Chunk>> {
+    if memchr(config.quote, &data[start.byte() as usize..]).is_some() {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut chunk_start = start.clone();
+    for record in RecordScanner::new(data, config.comment, start) {
+        let record = record.ok()?;
+        if (record.end.byte() - chunk_start.byte()) as usize >= chunk_size {
+            chunks.push(Chunk {
+                position: std::mem::replace(&mut chunk_start, record.end.clone()),
+                end: record.end.byte() as usize,
+            });
+        }
+    }
+
+    if (chunk_start.byte() as usize) < data.len() {
+        chunks.push(Chunk {
+            position: chunk_start,
+            end: data.len(),
+        });
+    }
+    Some(chunks)
+}
+
+#[cfg(not(feature = "fast-parser"))]
+fn parse_chunk(
+    data: &[u8],
+    config: &ParserConfig,
+    chunk: &Chunk,
+) -> Vec<Result<Transaction, ParserError>> {
+    match TransactionCsvIterator::seeked(&data[..chunk.end], config, chunk.position.clone()) {
+        Ok(iter) => iter.collect(),
+        Err(e) => vec![Err(e)],
+    }
+}
+
+#[cfg(feature = "fast-parser")]
+fn parse_chunk(
+    data: &[u8],
+    config: &ParserConfig,
+    chunk: &Chunk,
+) -> Vec<Result<Transaction, ParserError>> {
+    match crate::fast::FastCsvIterator::seeked(&data[..chunk.end], config, chunk.position.clone()) {
+        Ok(iter) => iter.collect(),
+        Err(e) => vec![Err(e)],
+    }
+}
+
+enum Inner<'a> {
+    Sequential(TransactionCsvIterator<Decompressed<&'a [u8]>>),
+    Chunked {
+        data: &'a [u8],
+        config: &'a ParserConfig,
+        threads: usize,
+        chunks: std::vec::IntoIter<Chunk>,
+        batch: std::vec::IntoIter<Result<Transaction, ParserError>>,
+    },
+}
+
+/// Iterator returned by [`parse_parallel`]
+pub struct ParallelCsvIterator<'a>(Inner<'a>);
+
+impl<'a> Iterator for ParallelCsvIterator<'a> {
+    type Item = Result<Transaction, ParserError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (data, config, threads, chunks, batch) = match &mut self.0 {
+            Inner::Sequential(iter) => return iter.next(),
+            Inner::Chunked {
+                data,
+                config,
+                threads,
+                chunks,
+                batch,
+            } => (*data, *config, *threads, chunks, batch),
+        };
+
+        loop {
+            if let Some(result) = batch.next() {
+                return Some(result);
+            }
+
+            let next_chunks = chunks.by_ref().take(threads).collect::<Vec<_>>();
+            if next_chunks.is_empty() {
+                return None;
+            }
+            *batch = std::thread::scope(|scope| {
+                let handles = next_chunks
+                    .iter()
+                    .map(|chunk| scope.spawn(move || parse_chunk(data, config, chunk)))
+                    .collect::<Vec<_>>();
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().expect("parser thread panicked"))
+                    .collect::<Vec<_>>()
+            })
+            .into_iter();
+        }
+    }
+}
+
+/// Parses CSV `data`, typically from [`map_file`], on multiple threads.
+/// The transactions are yielded in the same order and with the same errors as the sequential
+/// [`parse_with_config`](crate::parse_with_config), see the [module documentation](self).
+pub fn parse_parallel<'a>(
+    data: &'a [u8],
+    config: &'a ParserConfig,
+    options: ParallelOptions,
+) -> Result<ParallelCsvIterator<'a>, ParserError> {
+    if Compression::from_magic(data) != Compression::None {
+        let iter = TransactionCsvIterator::from_csv(decompress(data)?, config)?;
+        return Ok(ParallelCsvIterator(Inner::Sequential(iter)));
+    }
+
+    // reads the header row, if any, which also reports invalid headers up front
+    let probe = TransactionCsvIterator::<_, true>::from_csv(Cursor::new(data), config)?;
+    let start = probe.position().clone();
+
+    Ok(ParallelCsvIterator(
+        match split_chunks(data, config, start, options.chunk_size.max(1)) {
+            Some(chunks) => Inner::Chunked {
+                data,
+                config,
+                threads: options.threads.max(1),
+                chunks: chunks.into_iter(),
+                batch: Vec::new().into_iter(),
+            },
+            None => Inner::Sequential(TransactionCsvIterator::from_csv(decompress(data)?, config)?),
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Header;
+
+    const INPUT: &str = "type, client, tx, amount\r
+deposit,1,1,1.0\r
+\r
+withdrawal,1,2,0.5\r
+dispute,1,1,\r
+bogus,1,3,1.0\r
+deposit,2,4\r
+resolve,1,1,\r
+deposit,2,5,abc\r
+\r
+\r
+deposit,2,6,2.0";
+
+    fn assert_same(data: &[u8], config: &ParserConfig) {
+        let expected = format!(
+            "{:?}",
+            crate::parse_with_config(data, config)
+                .unwrap()
+                .collect::<Vec<_>>()
+        );
+        for chunk_size in [1, 7, 30, 1024] {
+            for threads in [1, 3] {
+                let options = ParallelOptions {
+                    threads,
+                    chunk_size,
+                };
+                let actual = parse_parallel(data, config, options)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+                assert_eq!(format!("{actual:?}"), expected, "{options:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn matches_sequential_parser() {
+        let config = ParserConfig::default();
+        assert_same(INPUT.as_bytes(), &config);
+        assert_same(INPUT.replace('\r', "").as_bytes(), &config);
+        assert_same(format!("\u{feff}{INPUT}\n\n").as_bytes(), &config);
+        assert_same(b"type,client,tx,amount\n", &config);
+    }
+
+    #[test]
+    fn matches_sequential_parser_with_dialect() {
+        let config = ParserConfig {
+            comment: Some(b'#'),
+            columns: Some(vec![
+                Header::Type,
+                Header::Client,
+                Header::Tx,
+                Header::Amount,
+            ]),
+            ..Default::default()
+        };
+        let input = "\u{feff}deposit,1,1,1.0\n#comment\n\nwithdrawal,1,2\n  \ndeposit,1,3,1.0\n";
+        assert_same(input.as_bytes(), &config);
+
+        let config = ParserConfig {
+            lenient: true,
+            strict: true,
+            ..Default::default()
+        };
+        assert_same(INPUT.to_uppercase().as_bytes(), &config);
+    }
+
+    #[test]
+    fn splits_on_record_boundaries() {
+        let input = INPUT.replace('\r', "");
+        let config = ParserConfig::default();
+        let probe = TransactionCsvIterator::<_, true>::from_csv(input.as_bytes(), &config).unwrap();
+        let start = probe.position().clone();
+        let chunks = split_chunks(input.as_bytes(), &config, start, 1).unwrap();
+        // one chunk per record
+        assert_eq!(chunks.len(), 8);
+        assert_eq!(chunks.last().unwrap().end, input.len());
+    }
+
+    #[test]
+    fn falls_back_to_sequential() {
+        let config = ParserConfig::default();
+        let options = ParallelOptions {
+            threads: 2,
+            chunk_size: 1,
+        };
+        let quoted = "type,client,tx,amount\n\"deposit\",1,1,\"1.0\"\ndeposit,1,2,1.0\n";
+        let bare_cr = "type,client,tx,amount\rdeposit,1,1,1.0\rdeposit,1,2,1.0\r";
+        for input in [quoted, bare_cr] {
+            assert!(split_chunks(input.as_bytes(), &config, csv::Position::new(), 1).is_none());
+            assert!(matches!(
+                parse_parallel(input.as_bytes(), &config, options)
+                    .unwrap()
+                    .0,
+                Inner::Sequential(_)
+            ));
+            assert_same(input.as_bytes(), &config);
+        }
+
+        let mut compressed = crate::compress(Vec::new(), Compression::Gzip).unwrap();
+        std::io::Write::write_all(&mut compressed, INPUT.as_bytes()).unwrap();
+        let compressed = compressed.finish().unwrap();
+        assert_same(&compressed, &config);
+    }
+
+    #[test]
+    fn reports_invalid_headers() {
+        assert!(matches!(
+            parse_parallel(
+                b"type,tx,amount\n",
+                &ParserConfig::default(),
+                ParallelOptions::default()
+            ),
+            Err(ParserError::MissingHeader(Header::Client))
+        ));
+    }
+}
@@ -1,10 +1,10 @@
-use crate::transaction::*;
+use crate::{transaction::*, validation, UCurrency};
 use ascii::AsAsciiStr;
 use csv::{ByteRecord, StringRecord};
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, ops::Range, sync::Arc};
 use thiserror::Error;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Header {
     Type,
     Tx,
@@ -12,6 +12,153 @@ pub enum Header {
     Amount,
 }
 
+impl Header {
+    pub const ALL: [Header; 4] = [Header::Type, Header::Client, Header::Tx, Header::Amount];
+
+    /// The canonical column name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Header::Type => "type",
+            Header::Tx => "tx",
+            Header::Client => "client",
+            Header::Amount => "amount",
+        }
+    }
+}
+
+impl std::fmt::Display for Header {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Header {
+    type Err = ParserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Header::ALL
+            .into_iter()
+            .find(|header| header.name() == s)
+            .ok_or_else(|| ParserError::UnknownHeader(s.to_string()))
+    }
+}
+
+/// CSV dialect settings for [`parse_with_config`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserConfig {
+    pub delimiter: u8,
+    pub quote: u8,
+    /// Lines starting with this byte are skipped
+    pub comment: Option<u8>,
+    /// Alternative header names, e.g. `("client_id", Header::Client)`.
+    /// The canonical names are always accepted.
+    pub aliases: Vec<(String, Header)>,
+    /// When set the input has no header row and the i'th column holds `columns[i]`
+    pub columns: Option<Vec<Header>>,
+    /// Opt-in slower parsing mode for sloppy exports. It matches headers and transaction types
+    /// case insensitively, accepts any UTF-8 and trims all unicode whitespace and byte order marks.
+    pub lenient: bool,
+    /// Rejects amounts that would otherwise be silently rounded, truncated or ignored,
+    /// see [`crate::validation`] for the exact rules.
+    pub strict: bool,
+    /// Columns beyond the four known ones to carry along in [`Transaction::metadata`],
+    /// e.g. `timestamp` or `memo`. They must be present in the header row.
+    pub extra_columns: Vec<String>,
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            comment: None,
+            aliases: Vec::new(),
+            columns: None,
+            lenient: false,
+            strict: false,
+            extra_columns: Vec::new(),
+        }
+    }
+}
+
+impl ParserConfig {
+    /// Whether records need nothing but the plain parsing loop, i.e. neither lenient nor strict
+    /// parsing nor extra columns
+    pub(crate) fn is_plain(&self) -> bool {
+        !self.lenient && !self.strict && self.extra_columns.is_empty()
+    }
+
+    fn reader_builder(&self) -> csv::ReaderBuilder {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .trim(csv::Trim::Headers)
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .comment(self.comment)
+            .has_headers(self.columns.is_none());
+        builder
+    }
+}
+
+/// Location of a record in the input
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordPosition {
+    /// 1 based line number the record starts on
+    pub line: u64,
+    /// Byte offset from the start of the input to the start of the record
+    pub byte: u64,
+}
+
+/// A [`ParserError`] annotated with where it happened
+#[derive(Error, Debug)]
+pub struct RecordError {
+    pub position: RecordPosition,
+    /// The field that failed to parse if it could be determined
+    pub field: Option<Header>,
+    /// The offending record as text
+    pub row: String,
+    /// Byte range of `field` within `row`
+    pub span: Option<Range<usize>>,
+    pub source: ParserError,
+}
+
+impl std::fmt::Display for RecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, byte offset {}",
+            self.source, self.position.line, self.position.byte
+        )?;
+        if let Some(field) = self.field {
+            write!(f, ", field `{field}`")?;
+        }
+        Ok(())
+    }
+}
+
+impl RecordError {
+    /// Renders the offending row with a caret line pointing at the failing field, e.g.
+    /// ```text
+    ///   3 | deposit,1,1,abc
+    ///     |             ^^^
+    /// ```
+    pub fn excerpt(&self) -> String {
+        let line = self.position.line.to_string();
+        let mut excerpt = format!("{line:>3} | {}\n", self.row);
+        if let Some(span) = &self.span {
+            let width = self.row[span.clone()].chars().count().max(1);
+            let offset = self.row[..span.start].chars().count();
+            excerpt.push_str(&format!(
+                "{:>3} | {}{}\n",
+                "",
+                " ".repeat(offset),
+                "^".repeat(width)
+            ));
+        }
+        excerpt
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParserError {
     #[error("Column `{0:?}` missing")]
@@ -26,6 +173,36 @@ pub enum ParserError {
     CurrencyParseError(#[from] fixed::ParseFixedError),
     #[error(transparent)]
     InvalidAscii(#[from] ascii::AsAsciiStrError),
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    IOError(#[from] std::io::Error),
+    #[error("Invalid binary file header")]
+    InvalidBinaryHeader,
+    #[error("Unsupported binary format version {0}")]
+    UnsupportedBinaryVersion(u8),
+    #[error("Invalid value in binary type field: `{0}`")]
+    InvalidTypeByte(u8),
+    #[error(transparent)]
+    InvalidRecord(Box<RecordError>),
+    #[error("Unknown column `{0}`")]
+    UnknownHeader(String),
+    #[error(transparent)]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("Amount `{0}` has more than 4 decimals")]
+    ExcessPrecision(String),
+    #[error("Amount must be greater than zero")]
+    ZeroAmount,
+    #[error("Amount is empty")]
+    EmptyAmount,
+    #[error("Unexpected amount `{0}`, only deposits and withdrawals have an amount")]
+    UnexpectedAmount(String),
+    #[error("Amount `{0}` is out of range")]
+    AmountOutOfRange(String),
+    #[error("Extra column `{0}` missing")]
+    MissingExtraColumn(String),
+    #[error(transparent)]
+    UnknownFormat(#[from] crate::format::UnknownFormat),
 }
 
 const MISSING_TYPE_HEADER: ParserError = ParserError::MissingHeader(Header::Type);
@@ -33,134 +210,513 @@ const MISSING_TX_HEADER: ParserError = ParserError::MissingHeader(Header::Tx);
 const MISSING_CLIENT_HEADER: ParserError = ParserError::MissingHeader(Header::Client);
 const MISSING_AMOUNT_HEADER: ParserError = ParserError::MissingHeader(Header::Amount);
 
-fn extract_field_map(headers: &StringRecord) -> Result<FieldToIndexMap, ParserError> {
+fn extract_field_map(
+    headers: &StringRecord,
+    aliases: &[(String, Header)],
+) -> Result<FieldToIndexMap, ParserError> {
     let mut header_to_index = headers
         .into_iter()
         .zip(0u8..u8::MAX)
         .collect::<HashMap<_, _>>();
 
+    let mut remove = |header: Header| {
+        header_to_index
+            .remove(header.name())
+            .or_else(|| {
+                aliases
+                    .iter()
+                    .filter(|(_, h)| *h == header)
+                    .find_map(|(alias, _)| header_to_index.remove(alias.as_str()))
+            })
+            .ok_or(ParserError::MissingHeader(header))
+    };
+
     // build a struct with u8 indecies for each field
     // based on the headers
     Ok(FieldToIndexMap {
-        ty: header_to_index.remove("type").ok_or(MISSING_TYPE_HEADER)?,
-        tx: header_to_index.remove("tx").ok_or(MISSING_TX_HEADER)?,
-        client: header_to_index
-            .remove("client")
-            .ok_or(MISSING_CLIENT_HEADER)?,
-        amount: header_to_index
-            .remove("amount")
-            .ok_or(MISSING_AMOUNT_HEADER)?,
+        ty: remove(Header::Type)?,
+        tx: remove(Header::Tx)?,
+        client: remove(Header::Client)?,
+        amount: remove(Header::Amount)?,
     })
 }
 
-struct TransactionCsvIterator<R: std::io::Read> {
+/// Looks up the indices of the configured extra columns, the names are compared the same way
+/// as the known columns which means ignoring case in lenient mode
+fn extract_extra_columns(
+    headers: &StringRecord,
+    extra_columns: &[String],
+    lenient: bool,
+) -> Result<Vec<(Arc<str>, usize)>, ParserError> {
+    extra_columns
+        .iter()
+        .map(|column| {
+            let name = if lenient {
+                Cow::Owned(lenient_trim(column).to_lowercase())
+            } else {
+                Cow::Borrowed(column.as_str())
+            };
+            headers
+                .iter()
+                .position(|h| h == name)
+                .map(|i| (Arc::from(column.as_str()), i))
+                .ok_or_else(|| ParserError::MissingExtraColumn(column.clone()))
+        })
+        .collect()
+}
+
+/// Same as [`extract_field_map`] but for headerless files where the columns are given up front
+fn columns_to_field_map(columns: &[Header]) -> Result<FieldToIndexMap, ParserError> {
+    let index = |header: Header| {
+        columns
+            .iter()
+            .position(|h| *h == header)
+            .and_then(|i| u8::try_from(i).ok())
+            .ok_or(ParserError::MissingHeader(header))
+    };
+
+    Ok(FieldToIndexMap {
+        ty: index(Header::Type)?,
+        tx: index(Header::Tx)?,
+        client: index(Header::Client)?,
+        amount: index(Header::Amount)?,
+    })
+}
+
+/// Parses CSV records into transactions. With `CONFIGURED` false the records only go through
+/// [`parse_transaction`], the lenient, strict and extra column handling is compiled out of the
+/// loop, which requires a [plain](ParserConfig::is_plain) config.
+pub(crate) struct TransactionCsvIterator<R: std::io::Read, const CONFIGURED: bool = true> {
     buf: ByteRecord,
     field_map: FieldToIndexMap,
     reader: csv::Reader<R>,
+    /// Delimiter and quote of the dialect, to rebuild failing rows
+    dialect: (u8, u8),
+    lenient: bool,
+    strict: bool,
+    /// Configured extra columns and their indices
+    extras: Vec<(Arc<str>, usize)>,
 }
 
-impl<R: std::io::Read> Iterator for TransactionCsvIterator<R> {
+impl<R: std::io::Read, const CONFIGURED: bool> Iterator for TransactionCsvIterator<R, CONFIGURED> {
     type Item = Result<Transaction, ParserError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.reader.read_byte_record(&mut self.buf) {
-            Ok(true) => Some(parse_transaction(&self.buf, self.field_map)),
+            Ok(true) if CONFIGURED => {
+                let mut result = if self.lenient {
+                    parse_transaction_lenient(&self.buf, self.field_map)
+                } else {
+                    parse_transaction(&self.buf, self.field_map)
+                };
+                if self.strict {
+                    result = validation::validate(
+                        result,
+                        self.buf.get(self.field_map.amount.into()),
+                        self.lenient,
+                    );
+                }
+                if !self.extras.is_empty() {
+                    result = result.map(|t| self.add_metadata(t));
+                }
+                Some(result.map_err(|e| {
+                    csv_record_error(&self.buf, self.field_map, self.dialect, self.lenient, e)
+                }))
+            }
+            Ok(true) => {
+                Some(parse_transaction(&self.buf, self.field_map).map_err(|e| {
+                    csv_record_error(&self.buf, self.field_map, self.dialect, false, e)
+                }))
+            }
             Ok(false) => None, //EOF
             Err(e) => Some(Err(e.into())),
         }
     }
 }
 
+impl<R: std::io::Read, const CONFIGURED: bool> TransactionCsvIterator<R, CONFIGURED> {
+    fn new(mut reader: csv::Reader<R>, config: &ParserConfig) -> Result<Self, ParserError> {
+        debug_assert!(CONFIGURED || config.is_plain());
+        let (field_map, extras) = match &config.columns {
+            Some(_) if !config.extra_columns.is_empty() => {
+                // without a header row there is nothing to find the extra columns by
+                return Err(ParserError::MissingExtraColumn(
+                    config.extra_columns[0].clone(),
+                ));
+            }
+            Some(columns) => (columns_to_field_map(columns)?, Vec::new()),
+            None if config.lenient => {
+                let headers = reader
+                    .headers()?
+                    .iter()
+                    .map(|h| lenient_trim(h).to_lowercase())
+                    .collect();
+                let aliases = config
+                    .aliases
+                    .iter()
+                    .map(|(alias, header)| (alias.to_lowercase(), *header))
+                    .collect::<Vec<_>>();
+                (
+                    extract_field_map(&headers, &aliases)?,
+                    extract_extra_columns(&headers, &config.extra_columns, true)?,
+                )
+            }
+            None => {
+                let headers = reader.headers()?;
+                (
+                    extract_field_map(headers, &config.aliases)?,
+                    extract_extra_columns(headers, &config.extra_columns, false)?,
+                )
+            }
+        };
+
+        Ok(TransactionCsvIterator {
+            buf: ByteRecord::new(),
+            field_map,
+            reader,
+            dialect: (config.delimiter, config.quote),
+            lenient: config.lenient,
+            strict: config.strict,
+            extras,
+        })
+    }
+
+    fn add_metadata(&self, mut transaction: Transaction) -> Transaction {
+        let metadata = transaction.metadata_mut();
+        for (column, index) in &self.extras {
+            let value = self.buf.get(*index).unwrap_or_default();
+            metadata.insert(column.clone(), String::from_utf8_lossy(value));
+        }
+        transaction
+    }
+
+    pub(crate) fn from_csv(reader: R, config: &ParserConfig) -> Result<Self, ParserError> {
+        Self::new(config.reader_builder().from_reader(reader), config)
+    }
+
+    /// Position after the last record read, or the header row right after construction
+    pub(crate) fn position(&self) -> &csv::Position {
+        self.reader.position()
+    }
+
+    #[cfg(feature = "fast-parser")]
+    pub(crate) fn field_map(&self) -> FieldToIndexMap {
+        self.field_map
+    }
+
+    /// Number of fields in the header row, or the first record of headerless files,
+    /// which every other record must match
+    #[cfg(feature = "fast-parser")]
+    pub(crate) fn first_record_len(&mut self) -> Option<usize> {
+        self.reader.byte_headers().ok().map(ByteRecord::len)
+    }
+}
+
+impl<'a> TransactionCsvIterator<std::io::Cursor<&'a [u8]>> {
+    /// Parses `data` starting from `position`, which must be at a record boundary as found by
+    /// [`RecordScanner`](crate::scanner::RecordScanner). Positions and errors are reported
+    /// exactly as if `data` was read from the start.
+    pub(crate) fn seeked(
+        data: &'a [u8],
+        config: &ParserConfig,
+        position: csv::Position,
+    ) -> Result<Self, ParserError> {
+        let mut iter = Self::from_csv(std::io::Cursor::new(data), config)?;
+        iter.reader
+            .seek_raw(std::io::SeekFrom::Start(position.byte()), position)?;
+        Ok(iter)
+    }
+}
+
 pub fn parse_from_reader<R: std::io::Read>(
-    mut reader: csv::Reader<R>,
+    reader: csv::Reader<R>,
 ) -> Result<impl Iterator<Item = Result<Transaction, ParserError>>, ParserError> {
-    let field_map = extract_field_map(reader.headers()?)?;
-
-    Ok(TransactionCsvIterator {
-        buf: ByteRecord::new(),
-        field_map,
-        reader,
-    })
+    TransactionCsvIterator::<_, false>::new(reader, &ParserConfig::default())
 }
 
+/// Parses CSV with the default dialect, gzip and zstd compressed input is decompressed
+/// on the fly
 pub fn parse_csv(
     reader: impl std::io::Read,
 ) -> Result<impl Iterator<Item = Result<Transaction, ParserError>>, ParserError> {
-    parse_from_reader(
-        csv::ReaderBuilder::new()
-            .trim(csv::Trim::Headers)
-            .from_reader(reader),
+    TransactionCsvIterator::<_, false>::from_csv(
+        crate::compression::decompress(std::io::BufReader::new(reader))?,
+        &ParserConfig::default(),
     )
 }
 
+/// Parses CSV using the dialect described by `config`.
+/// Gzip and zstd compressed input is detected and decompressed on the fly.
+pub fn parse_with_config(
+    reader: impl std::io::Read,
+    config: &ParserConfig,
+) -> Result<impl Iterator<Item = Result<Transaction, ParserError>>, ParserError> {
+    let reader = crate::compression::decompress(std::io::BufReader::new(reader))?;
+    TransactionCsvIterator::<_, true>::from_csv(reader, config)
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct FieldToIndexMap {
-    ty: u8,
-    tx: u8,
-    client: u8,
-    amount: u8,
+pub(crate) struct FieldToIndexMap {
+    pub(crate) ty: u8,
+    pub(crate) tx: u8,
+    pub(crate) client: u8,
+    pub(crate) amount: u8,
 }
 
 fn parse_transaction(
     record: &ByteRecord,
     field_map: FieldToIndexMap,
 ) -> Result<Transaction, ParserError> {
-    let tx = record
-        .get(field_map.tx.into())
-        .ok_or(MISSING_TX_HEADER)?
-        .as_ascii_str()?
-        .trim()
-        .as_str()
-        .parse()?;
-    let client = record
-        .get(field_map.client.into())
-        .ok_or(MISSING_CLIENT_HEADER)?
+    parse_fields(
+        record.get(field_map.ty.into()).ok_or(MISSING_TYPE_HEADER)?,
+        record.get(field_map.tx.into()).ok_or(MISSING_TX_HEADER)?,
+        record
+            .get(field_map.client.into())
+            .ok_or(MISSING_CLIENT_HEADER)?,
+        record.get(field_map.amount.into()),
+    )
+}
+
+#[inline]
+fn parse_int<T: std::str::FromStr<Err = std::num::ParseIntError>>(
+    field: &[u8],
+) -> Result<T, ParserError> {
+    Ok(field.as_ascii_str()?.trim().as_str().parse()?)
+}
+
+#[inline]
+fn parse_type(field: &[u8]) -> Result<&str, ParserError> {
+    Ok(field.as_ascii_str()?.trim().as_str())
+}
+
+#[inline]
+fn parse_amount(field: Option<&[u8]>) -> Result<UCurrency, ParserError> {
+    Ok(field
+        .ok_or(MISSING_AMOUNT_HEADER)?
         .as_ascii_str()?
         .trim()
         .as_str()
-        .parse()?;
-    let ty = record
-        .get(field_map.ty.into())
-        .ok_or(MISSING_TYPE_HEADER)?
-        .as_ascii_str()?
-        .trim()
-        .as_str();
+        .parse()?)
+}
+
+/// Validates and converts the raw bytes of the four transaction fields into a [`Transaction`].
+///
+/// Shared by every input format so they all accept and reject exactly the same values.
+/// `amount` is only required to be present for deposits and withdrawals.
+#[inline]
+pub(crate) fn parse_fields(
+    ty: &[u8],
+    tx: &[u8],
+    client: &[u8],
+    amount: Option<&[u8]>,
+) -> Result<Transaction, ParserError> {
+    let tx = parse_int(tx)?;
+    let client = parse_int(client)?;
+    let ty = parse_type(ty)?;
 
     Ok(match ty {
         //case sensitive for performance and simplicity reasons
-        "withdrawal" => Transaction::new_withdrawal(
-            tx,
-            client,
-            record
-                .get(field_map.amount.into())
-                .ok_or(MISSING_AMOUNT_HEADER)?
-                .as_ascii_str()?
-                .as_str()
-                .trim()
-                .parse()?,
-        ),
-        "deposit" => Transaction::new_deposit(
-            tx,
-            client,
-            record
-                .get(field_map.amount.into())
-                .ok_or(MISSING_AMOUNT_HEADER)?
-                .as_ascii_str()?
-                .trim()
-                .as_str()
-                .parse()?,
-        ),
+        "withdrawal" => Transaction::new_withdrawal(tx, client, parse_amount(amount)?),
+        "deposit" => Transaction::new_deposit(tx, client, parse_amount(amount)?),
         "dispute" => Transaction::new_dispute(tx, client),
         "chargeback" => Transaction::new_charge_back(tx, client),
         "resolve" => Transaction::new_resolve(tx, client),
+        "representment" => Transaction::new_representment(tx, client),
+        "reversal" => Transaction::new_reversal(tx, client),
+        "authorize" => Transaction::new_authorize(tx, client, parse_amount(amount)?),
+        "capture" => Transaction::new_capture(tx, client, parse_amount(amount)?),
+        "release" => Transaction::new_release(tx, client),
+        "clear" => Transaction::new_clear(tx, client),
+        "return" => Transaction::new_return(tx, client),
+        "accrue" => Transaction::new_accrue(tx, client),
+        "limit" => Transaction::new_limit(tx, client, parse_amount(amount)?),
         _ => return Err(ParserError::InvalidTypeField(ty.to_string())),
     })
 }
 
+fn parse_transaction_lenient(
+    record: &ByteRecord,
+    field_map: FieldToIndexMap,
+) -> Result<Transaction, ParserError> {
+    parse_fields_lenient(
+        record.get(field_map.ty.into()).ok_or(MISSING_TYPE_HEADER)?,
+        record.get(field_map.tx.into()).ok_or(MISSING_TX_HEADER)?,
+        record
+            .get(field_map.client.into())
+            .ok_or(MISSING_CLIENT_HEADER)?,
+        record.get(field_map.amount.into()),
+    )
+}
+
+/// Trims unicode whitespace, such as non breaking spaces, and byte order marks
+pub(crate) fn lenient_trim(s: &str) -> &str {
+    s.trim_matches(|c: char| c.is_whitespace() || c == '\u{FEFF}')
+}
+
+#[inline]
+fn lenient_str(field: &[u8]) -> Result<&str, ParserError> {
+    Ok(lenient_trim(std::str::from_utf8(field)?))
+}
+
+#[inline]
+fn parse_amount_lenient(field: Option<&[u8]>) -> Result<UCurrency, ParserError> {
+    Ok(lenient_str(field.ok_or(MISSING_AMOUNT_HEADER)?)?.parse()?)
+}
+
+/// The [`ParserConfig::lenient`] counterpart to [`parse_fields`].
+/// Kept separate so the default path doesn't pay for the extra work.
+pub(crate) fn parse_fields_lenient(
+    ty: &[u8],
+    tx: &[u8],
+    client: &[u8],
+    amount: Option<&[u8]>,
+) -> Result<Transaction, ParserError> {
+    let tx = lenient_str(tx)?.parse()?;
+    let client = lenient_str(client)?.parse()?;
+    let ty = lenient_str(ty)?;
+
+    Ok(if ty.eq_ignore_ascii_case("withdrawal") {
+        Transaction::new_withdrawal(tx, client, parse_amount_lenient(amount)?)
+    } else if ty.eq_ignore_ascii_case("deposit") {
+        Transaction::new_deposit(tx, client, parse_amount_lenient(amount)?)
+    } else if ty.eq_ignore_ascii_case("dispute") {
+        Transaction::new_dispute(tx, client)
+    } else if ty.eq_ignore_ascii_case("chargeback") {
+        Transaction::new_charge_back(tx, client)
+    } else if ty.eq_ignore_ascii_case("resolve") {
+        Transaction::new_resolve(tx, client)
+    } else if ty.eq_ignore_ascii_case("representment") {
+        Transaction::new_representment(tx, client)
+    } else if ty.eq_ignore_ascii_case("reversal") {
+        Transaction::new_reversal(tx, client)
+    } else if ty.eq_ignore_ascii_case("authorize") {
+        Transaction::new_authorize(tx, client, parse_amount_lenient(amount)?)
+    } else if ty.eq_ignore_ascii_case("capture") {
+        Transaction::new_capture(tx, client, parse_amount_lenient(amount)?)
+    } else if ty.eq_ignore_ascii_case("release") {
+        Transaction::new_release(tx, client)
+    } else if ty.eq_ignore_ascii_case("clear") {
+        Transaction::new_clear(tx, client)
+    } else if ty.eq_ignore_ascii_case("return") {
+        Transaction::new_return(tx, client)
+    } else if ty.eq_ignore_ascii_case("accrue") {
+        Transaction::new_accrue(tx, client)
+    } else if ty.eq_ignore_ascii_case("limit") {
+        Transaction::new_limit(tx, client, parse_amount_lenient(amount)?)
+    } else {
+        return Err(ParserError::InvalidTypeField(ty.to_string()));
+    })
+}
+
+/// Figures out which field caused `error` when [`parse_fields`] failed.
+///
+/// This is only done on the error path by re-parsing the fields one at a time
+/// in the same order as [`parse_fields`] so the happy path doesn't pay for the bookkeeping.
+#[cold]
+pub(crate) fn failing_field(
+    error: &ParserError,
+    tx: Option<&[u8]>,
+    client: Option<&[u8]>,
+    ty: Option<&[u8]>,
+    lenient: bool,
+) -> Option<Header> {
+    let is_int = |field: &[u8], parse: fn(&str) -> bool| {
+        if lenient {
+            lenient_str(field).is_ok_and(parse)
+        } else {
+            field.as_ascii_str().is_ok_and(|s| parse(s.trim().as_str()))
+        }
+    };
+    let is_type = |field: &[u8]| {
+        if lenient {
+            lenient_str(field).is_ok()
+        } else {
+            parse_type(field).is_ok()
+        }
+    };
+
+    match error {
+        ParserError::MissingHeader(header) => Some(*header),
+        ParserError::InvalidTypeField(_) => Some(Header::Type),
+        _ if !tx.is_some_and(|tx| is_int(tx, |s| s.parse::<u32>().is_ok())) => Some(Header::Tx),
+        _ if !client.is_some_and(|c| is_int(c, |s| s.parse::<u16>().is_ok())) => {
+            Some(Header::Client)
+        }
+        _ if !ty.is_some_and(is_type) => Some(Header::Type),
+        _ => Some(Header::Amount),
+    }
+}
+
+#[cold]
+fn csv_record_error(
+    record: &ByteRecord,
+    field_map: FieldToIndexMap,
+    (delimiter, quote): (u8, u8),
+    lenient: bool,
+    source: ParserError,
+) -> ParserError {
+    let index = |header| match header {
+        Header::Type => field_map.ty,
+        Header::Tx => field_map.tx,
+        Header::Client => field_map.client,
+        Header::Amount => field_map.amount,
+    } as usize;
+
+    let field = failing_field(
+        &source,
+        record.get(index(Header::Tx)),
+        record.get(index(Header::Client)),
+        record.get(index(Header::Type)),
+        lenient,
+    );
+
+    // rebuild the row from the fields in the input's dialect, keeping track of where the failing
+    // field is
+    let (delimiter, quote) = (char::from(delimiter), char::from(quote));
+    let mut row = String::new();
+    let mut span = None;
+    for (i, value) in record.iter().enumerate() {
+        if i != 0 {
+            row.push(delimiter);
+        }
+        let start = row.len();
+        let value = String::from_utf8_lossy(value);
+        if value.contains([delimiter, quote, '\r', '\n']) {
+            let escaped = value.replace(quote, &format!("{quote}{quote}"));
+            row.push_str(&format!("{quote}{escaped}{quote}"));
+        } else {
+            row.push_str(&value);
+        }
+        if field.map(index) == Some(i) {
+            span = Some(start..row.len());
+        }
+    }
+    if span.is_none() && field.is_some() {
+        // the field is missing entirely, point just past the end of the row
+        span = Some(row.len()..row.len());
+    }
+
+    let position = record
+        .position()
+        .map(|p| RecordPosition {
+            line: p.line(),
+            byte: p.byte(),
+        })
+        .unwrap_or_default();
+
+    ParserError::InvalidRecord(Box::new(RecordError {
+        position,
+        field,
+        row,
+        span,
+        source,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ucur;
+    use crate::{ucur, Metadata};
 
     const FIELD_MAP: FieldToIndexMap = FieldToIndexMap {
         ty: 0,
@@ -239,6 +795,67 @@ mod tests {
             Transaction::new_charge_back(1, 1)
         );
     }
+    #[test]
+    fn can_parse_authorization_transactions() {
+        assert_eq!(
+            parse_transaction(
+                &ByteRecord::from(vec!["authorize", "1", "1", "2.5"]),
+                FIELD_MAP
+            )
+            .unwrap(),
+            Transaction::new_authorize(1, 1, ucur!(2.5))
+        );
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["capture", "1", "1", "1"]), FIELD_MAP)
+                .unwrap(),
+            Transaction::new_capture(1, 1, ucur!(1))
+        );
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["release", "1", "1"]), FIELD_MAP).unwrap(),
+            Transaction::new_release(1, 1)
+        );
+    }
+    #[test]
+    fn can_parse_clear_and_return_transactions() {
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["clear", "1", "1"]), FIELD_MAP).unwrap(),
+            Transaction::new_clear(1, 1)
+        );
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["return", "1", "1"]), FIELD_MAP).unwrap(),
+            Transaction::new_return(1, 1)
+        );
+    }
+    #[test]
+    fn can_parse_accrue_transactions() {
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["accrue", "7", "0"]), FIELD_MAP).unwrap(),
+            Transaction::new_accrue(7, 0)
+        );
+    }
+    #[test]
+    fn can_parse_limit_transactions() {
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["limit", "1", "2", "100"]), FIELD_MAP)
+                .unwrap(),
+            Transaction::new_limit(1, 2, ucur!(100))
+        );
+    }
+    #[test]
+    fn can_parse_representment_and_reversal_transactions() {
+        assert_eq!(
+            parse_transaction(
+                &ByteRecord::from(vec!["representment", "1", "1"]),
+                FIELD_MAP
+            )
+            .unwrap(),
+            Transaction::new_representment(1, 1)
+        );
+        assert_eq!(
+            parse_transaction(&ByteRecord::from(vec!["reversal", "1", "1"]), FIELD_MAP).unwrap(),
+            Transaction::new_reversal(1, 1)
+        );
+    }
 
     #[test]
     fn can_handle_whitespace() {
@@ -257,10 +874,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn errors_contain_position_and_field() {
+        let input = "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,abc\n";
+        let errors = parse_csv(input.as_bytes())
+            .unwrap()
+            .filter_map(Result::err)
+            .map(|e| match e {
+                ParserError::InvalidRecord(e) => e,
+                e => panic!("expected InvalidRecord but got {e:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 1);
+
+        let error = &errors[0];
+        assert_eq!(error.position, RecordPosition { line: 3, byte: 38 });
+        assert_eq!(error.field, Some(Header::Amount));
+        assert!(matches!(error.source, ParserError::CurrencyParseError(_)));
+        assert_eq!(
+            error.excerpt(),
+            "  3 | deposit,1,2,abc\n    |             ^^^\n"
+        );
+        assert_eq!(
+            error.to_string(),
+            format!("{} at line 3, byte offset 38, field `amount`", error.source)
+        );
+    }
+
+    #[test]
+    fn short_rows_point_past_the_end() {
+        let reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader("type,client,tx,amount\ndeposit,1,1\n".as_bytes());
+        let error = parse_from_reader(reader).unwrap().next().unwrap();
+        let Err(ParserError::InvalidRecord(error)) = error else {
+            panic!("expected InvalidRecord but got {error:?}");
+        };
+        assert_eq!(error.field, Some(Header::Amount));
+        assert!(matches!(
+            error.source,
+            ParserError::MissingHeader(Header::Amount)
+        ));
+        assert_eq!(error.excerpt(), "  2 | deposit,1,1\n    |            ^\n");
+    }
+
+    #[test]
+    fn excerpts_keep_the_dialect() {
+        let config = ParserConfig {
+            delimiter: b';',
+            quote: b'\'',
+            ..Default::default()
+        };
+        let input = "type;client;tx;amount\ndeposit;1;1;abc\ndeposit;1;2;'1;5'\n";
+        let excerpts = parse_with_config(input.as_bytes(), &config)
+            .unwrap()
+            .map(|result| match result {
+                Err(ParserError::InvalidRecord(e)) => e.excerpt(),
+                result => panic!("expected InvalidRecord but got {result:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            excerpts,
+            [
+                "  2 | deposit;1;1;abc\n    |             ^^^\n",
+                "  3 | deposit;1;2;'1;5'\n    |             ^^^^^\n",
+            ]
+        );
+    }
+
     #[test]
     fn can_extract_field_map() {
         assert_eq!(
-            extract_field_map(&StringRecord::from(vec!["type", "tx", "client", "amount"])).unwrap(),
+            extract_field_map(
+                &StringRecord::from(vec!["type", "tx", "client", "amount"]),
+                &[]
+            )
+            .unwrap(),
             FieldToIndexMap {
                 ty: 0,
                 tx: 1,
@@ -269,7 +958,11 @@ mod tests {
             }
         );
         assert_eq!(
-            extract_field_map(&StringRecord::from(vec!["tx", "client", "amount", "type"])).unwrap(),
+            extract_field_map(
+                &StringRecord::from(vec!["tx", "client", "amount", "type"]),
+                &[]
+            )
+            .unwrap(),
             FieldToIndexMap {
                 tx: 0,
                 client: 1,
@@ -282,17 +975,287 @@ mod tests {
     #[test]
     fn extracting_missing_header_fields_fails() {
         assert!(matches!(
-            extract_field_map(&StringRecord::from(vec![""])),
+            extract_field_map(&StringRecord::from(vec![""]), &[]),
             Err(ParserError::MissingHeader(_))
         ));
 
         assert!(matches!(
-            extract_field_map(&StringRecord::from(vec!["type"])),
+            extract_field_map(&StringRecord::from(vec!["type"]), &[]),
             Err(ParserError::MissingHeader(_))
         ));
         assert!(matches!(
-            extract_field_map(&StringRecord::from(vec!["type", "tx", "client"])),
+            extract_field_map(&StringRecord::from(vec!["type", "tx", "client"]), &[]),
             Err(ParserError::MissingHeader(_))
         ));
     }
+
+    #[test]
+    fn can_extract_field_map_with_aliases() {
+        let aliases = [
+            ("client_id".to_string(), Header::Client),
+            ("transaction_id".to_string(), Header::Tx),
+        ];
+        assert_eq!(
+            extract_field_map(
+                &StringRecord::from(vec!["type", "client_id", "transaction_id", "amount"]),
+                &aliases
+            )
+            .unwrap(),
+            FieldToIndexMap {
+                ty: 0,
+                client: 1,
+                tx: 2,
+                amount: 3
+            }
+        );
+        // the canonical name wins over an alias
+        assert_eq!(
+            extract_field_map(
+                &StringRecord::from(vec!["type", "client_id", "tx", "amount", "client"]),
+                &aliases
+            )
+            .unwrap()
+            .client,
+            4
+        );
+    }
+
+    #[test]
+    fn can_parse_with_custom_dialect() {
+        let config = ParserConfig {
+            delimiter: b';',
+            quote: b'\'',
+            comment: Some(b'#'),
+            aliases: vec![("client_id".to_string(), Header::Client)],
+            ..Default::default()
+        };
+        let input =
+            "# exported 2022-01-01\ntype;client_id;tx;amount\n'deposit';1;1;'1,5'\n# trailing\n";
+        assert!(matches!(
+            parse_with_config(input.as_bytes(), &config)
+                .unwrap()
+                .collect::<Vec<_>>()
+                .as_slice(),
+            // the quoted amount uses a decimal comma which isn't supported
+            [Err(ParserError::InvalidRecord(_))]
+        ));
+
+        let input = "# exported 2022-01-01\ntype;client_id;tx;amount\n'deposit';1;1;'1.5'\n";
+        assert_eq!(
+            parse_with_config(input.as_bytes(), &config)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![Transaction::new_deposit(1, 1, ucur!(1.5))]
+        );
+    }
+
+    #[test]
+    fn can_parse_headerless_with_columns() {
+        let config = ParserConfig {
+            delimiter: b'\t',
+            columns: Some(vec![
+                Header::Client,
+                Header::Tx,
+                Header::Type,
+                Header::Amount,
+            ]),
+            ..Default::default()
+        };
+        let input = "1\t1\tdeposit\t2.0\n1\t1\tdispute\t\n";
+        assert_eq!(
+            parse_with_config(input.as_bytes(), &config)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![
+                Transaction::new_deposit(1, 1, ucur!(2)),
+                Transaction::new_dispute(1, 1)
+            ]
+        );
+
+        let config = ParserConfig {
+            columns: Some(vec![Header::Client, Header::Tx, Header::Type]),
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_with_config("".as_bytes(), &config),
+            Err(ParserError::MissingHeader(Header::Amount))
+        ));
+    }
+
+    #[test]
+    fn can_carry_extra_columns() {
+        let config = ParserConfig {
+            extra_columns: vec!["memo".to_string(), "timestamp".to_string()],
+            ..Default::default()
+        };
+        let input = "timestamp,type,client,tx,amount,memo,currency\n\
+                     1700000000,deposit,1,1,2.0,first,EUR\n\
+                     1700000001,dispute,1,1,,,EUR\n";
+        let transactions = parse_with_config(input.as_bytes(), &config)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        let mut metadata = Metadata::default();
+        metadata.insert("memo", "first");
+        metadata.insert("timestamp", "1700000000");
+        assert_eq!(
+            transactions[0],
+            Transaction::new_deposit(1, 1, ucur!(2)).with_metadata(metadata)
+        );
+        assert_eq!(transactions[1].metadata().get("memo"), Some(""));
+        assert_eq!(transactions[1].metadata().get("currency"), None);
+
+        // the default path doesn't carry anything
+        let transactions = parse_csv(input.as_bytes()).unwrap();
+        assert!(transactions.flatten().all(|t| t.metadata().is_empty()));
+
+        let lenient = ParserConfig {
+            lenient: true,
+            extra_columns: vec!["Memo".to_string()],
+            ..Default::default()
+        };
+        let first = parse_with_config(input.as_bytes(), &lenient)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.metadata().get("Memo"), Some("first"));
+    }
+
+    #[test]
+    fn missing_extra_columns_fail() {
+        let config = ParserConfig {
+            extra_columns: vec!["memo".to_string()],
+            ..Default::default()
+        };
+        assert!(matches!(
+            parse_with_config("type,client,tx,amount\n".as_bytes(), &config),
+            Err(ParserError::MissingExtraColumn(column)) if column == "memo"
+        ));
+
+        let config = ParserConfig {
+            columns: Some(Header::ALL.to_vec()),
+            ..config
+        };
+        assert!(matches!(
+            parse_with_config("deposit,1,1,1.0\n".as_bytes(), &config),
+            Err(ParserError::MissingExtraColumn(_))
+        ));
+    }
+
+    #[test]
+    fn can_parse_header_names() {
+        assert_eq!("client".parse::<Header>().unwrap(), Header::Client);
+        assert!(matches!(
+            "client_id".parse::<Header>(),
+            Err(ParserError::UnknownHeader(_))
+        ));
+    }
+
+    #[test]
+    fn lenient_mode_accepts_sloppy_input() {
+        let config = ParserConfig {
+            lenient: true,
+            aliases: vec![("Client_ID".to_string(), Header::Client)],
+            ..Default::default()
+        };
+        let input = "\u{FEFF}Type, CLIENT_ID ,Tx,Amount\n\u{A0}Deposit\u{A0},1, 1,\u{2003}1.5\nCHARGEBACK,1,1,\n";
+        assert_eq!(
+            parse_with_config(input.as_bytes(), &config)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            vec![
+                Transaction::new_deposit(1, 1, ucur!(1.5)),
+                Transaction::new_charge_back(1, 1)
+            ]
+        );
+
+        // the default mode still rejects the same input
+        assert!(matches!(
+            parse_with_config(input.as_bytes(), &ParserConfig::default()),
+            Err(ParserError::MissingHeader(_))
+        ));
+    }
+
+    #[test]
+    fn lenient_mode_still_validates() {
+        let config = ParserConfig {
+            lenient: true,
+            ..Default::default()
+        };
+        let input = "type,client,tx,amount\nrefund,1,1,1.0\ndeposit,1,\u{A0}x,1.0\n";
+        let errors = parse_with_config(input.as_bytes(), &config)
+            .unwrap()
+            .map(|r| match r {
+                Err(ParserError::InvalidRecord(e)) => (e.field, e.source.to_string()),
+                r => panic!("expected InvalidRecord but got {r:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(errors[0].0, Some(Header::Type));
+        assert_eq!(errors[1].0, Some(Header::Tx));
+    }
+
+    #[test]
+    fn strict_mode_reports_amount_errors_on_the_amount_field() {
+        let config = ParserConfig {
+            strict: true,
+            ..Default::default()
+        };
+        let input = "type,client,tx,amount\ndeposit,1,1,1.00005\ndeposit,1,2,\ndispute,1,1,2\ndeposit,1,3,1\n";
+        let results = parse_with_config(input.as_bytes(), &config)
+            .unwrap()
+            .collect::<Vec<_>>();
+        let errors = results
+            .iter()
+            .filter_map(|r| match r {
+                Err(ParserError::InvalidRecord(e)) => Some(e),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(errors.len(), 3);
+        assert!(errors.iter().all(|e| e.field == Some(Header::Amount)));
+        assert!(matches!(errors[0].source, ParserError::ExcessPrecision(_)));
+        assert!(matches!(errors[1].source, ParserError::EmptyAmount));
+        assert!(matches!(errors[2].source, ParserError::UnexpectedAmount(_)));
+        assert_eq!(
+            results.last().unwrap().as_ref().unwrap(),
+            &Transaction::new_deposit(3, 1, ucur!(1))
+        );
+
+        // the default mode keeps the old behaviour
+        assert_eq!(
+            parse_csv(input.as_bytes())
+                .unwrap()
+                .filter(Result::is_ok)
+                .count(),
+            3
+        );
+    }
+
+    #[test]
+    fn can_parse_compressed() {
+        use crate::compression::{compress, Compression};
+        use std::io::Write;
+
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let mut writer = compress(Vec::new(), compression).unwrap();
+            writer
+                .write_all(b"type,client,tx,amount\ndeposit,1,1,1.0\n")
+                .unwrap();
+            let compressed = writer.finish().unwrap();
+
+            assert_eq!(
+                parse_csv(compressed.as_slice())
+                    .unwrap()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap(),
+                vec![Transaction::new_deposit(1, 1, ucur!(1))]
+            );
+        }
+    }
 }
@@ -0,0 +1,317 @@
+//! Configurable account semantics: what a locked account may still do, see [`LockPolicy`],
+//! how disputes exceeding the available funds are handled, see [`DisputeFunding`], and whether
+//! reversing a chargeback unlocks the account, see [`ReversalUnlock`].
+use crate::{format::UnknownFormat, TransactionExecutionError, UCurrency};
+use std::str::FromStr;
+
+/// The operations of a [`ClientAccount`](crate::ClientAccount)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Deposit,
+    Withdrawal,
+    Dispute,
+    Resolve,
+    ChargeBack,
+    Representment,
+    Reversal,
+    Authorize,
+    Capture,
+    Release,
+    Clear,
+    Return,
+    /// Setting the overdraft limit with an admin transaction
+    Limit,
+}
+
+/// Whether an operation is allowed on a locked account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockRule {
+    Allow,
+    Reject,
+    /// Allow operations moving at most the given amount
+    AllowUpTo(UCurrency),
+}
+
+impl LockRule {
+    fn allows(&self, amount: UCurrency) -> bool {
+        match self {
+            LockRule::Allow => true,
+            LockRule::Reject => false,
+            LockRule::AllowUpTo(limit) => amount <= *limit,
+        }
+    }
+}
+
+/// Rules for every operation on a locked account, consulted by all the
+/// [`ClientAccount`](crate::ClientAccount) operations. Unlocked accounts allow everything.
+///
+/// The default only rejects withdrawals and authorizations, which reserve funds for a later
+/// withdrawal, and changing the overdraft limit with admin transactions. Captures withdraw the
+/// reserved funds and are held to the withdrawal rule too.
+/// A client can bring a charged back account back into good standing by depositing enough to
+/// cover it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockPolicy {
+    pub deposit: LockRule,
+    pub withdrawal: LockRule,
+    pub dispute: LockRule,
+    pub resolve: LockRule,
+    pub charge_back: LockRule,
+    pub representment: LockRule,
+    pub reversal: LockRule,
+    pub authorize: LockRule,
+    pub capture: LockRule,
+    pub release: LockRule,
+    pub clear: LockRule,
+    pub deposit_return: LockRule,
+    /// Compared against the new limit
+    pub limit: LockRule,
+}
+
+impl Default for LockPolicy {
+    fn default() -> Self {
+        Self {
+            withdrawal: LockRule::Reject,
+            authorize: LockRule::Reject,
+            limit: LockRule::Reject,
+            ..Self::ALLOW_ALL
+        }
+    }
+}
+
+impl LockPolicy {
+    const ALLOW_ALL: Self = Self {
+        deposit: LockRule::Allow,
+        withdrawal: LockRule::Allow,
+        dispute: LockRule::Allow,
+        resolve: LockRule::Allow,
+        charge_back: LockRule::Allow,
+        representment: LockRule::Allow,
+        reversal: LockRule::Allow,
+        authorize: LockRule::Allow,
+        capture: LockRule::Allow,
+        release: LockRule::Allow,
+        clear: LockRule::Allow,
+        deposit_return: LockRule::Allow,
+        limit: LockRule::Allow,
+    };
+
+    /// Locked accounts reject everything
+    pub const FROZEN: Self = Self {
+        deposit: LockRule::Reject,
+        withdrawal: LockRule::Reject,
+        dispute: LockRule::Reject,
+        resolve: LockRule::Reject,
+        charge_back: LockRule::Reject,
+        representment: LockRule::Reject,
+        reversal: LockRule::Reject,
+        authorize: LockRule::Reject,
+        capture: LockRule::Reject,
+        release: LockRule::Reject,
+        clear: LockRule::Reject,
+        deposit_return: LockRule::Reject,
+        limit: LockRule::Reject,
+    };
+
+    /// Locked accounts reject deposits, withdrawals, authorizations and limit changes but open
+    /// disputes can still be settled and authorizations released
+    pub const DISPUTES_ONLY: Self = Self {
+        deposit: LockRule::Reject,
+        withdrawal: LockRule::Reject,
+        authorize: LockRule::Reject,
+        limit: LockRule::Reject,
+        ..Self::ALLOW_ALL
+    };
+
+    /// Like the default but withdrawals of at most `limit` are allowed
+    pub fn withdrawal_limit(limit: UCurrency) -> Self {
+        Self {
+            withdrawal: LockRule::AllowUpTo(limit),
+            ..Self::default()
+        }
+    }
+
+    pub fn rule(&self, operation: Operation) -> LockRule {
+        match operation {
+            Operation::Deposit => self.deposit,
+            Operation::Withdrawal => self.withdrawal,
+            Operation::Dispute => self.dispute,
+            Operation::Resolve => self.resolve,
+            Operation::ChargeBack => self.charge_back,
+            Operation::Representment => self.representment,
+            Operation::Reversal => self.reversal,
+            Operation::Authorize => self.authorize,
+            Operation::Capture => self.capture,
+            Operation::Release => self.release,
+            Operation::Clear => self.clear,
+            Operation::Return => self.deposit_return,
+            Operation::Limit => self.limit,
+        }
+    }
+
+    /// Fails with [`TransactionExecutionError::AccountLocked`] if `operation` moving `amount`
+    /// isn't allowed on an account with the given `locked` state.
+    #[inline]
+    pub fn check(
+        &self,
+        locked: bool,
+        operation: Operation,
+        amount: UCurrency,
+    ) -> Result<(), TransactionExecutionError> {
+        if locked && !self.rule(operation).allows(amount) {
+            return Err(TransactionExecutionError::AccountLocked);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for LockPolicy {
+    type Err = UnknownFormat;
+
+    /// Parses `withdrawals` (the default), `all`, `disputes-only` or `withdrawal-limit:<amount>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "withdrawals" => Ok(Self::default()),
+            None if s == "all" => Ok(Self::FROZEN),
+            None if s == "disputes-only" => Ok(Self::DISPUTES_ONLY),
+            Some(("withdrawal-limit", limit)) => limit
+                .parse()
+                .map(Self::withdrawal_limit)
+                .map_err(|_| UnknownFormat(s.to_string())),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// What a dispute does when the client has less available than the disputed deposit,
+/// for example because the funds were already withdrawn
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DisputeFunding {
+    /// Hold the full amount even if `available` goes negative
+    #[default]
+    AllowNegative,
+    /// Reject the dispute with [`TransactionExecutionError::InsufficientFunds`]
+    Reject,
+    /// Hold only what's available and record the rest as the account's
+    /// [`shortfall`](crate::ClientAccount::shortfall) receivable
+    HoldAvailable,
+    /// Hold the full amount like [`DisputeFunding::AllowNegative`] and lock the account
+    Lock,
+}
+
+impl FromStr for DisputeFunding {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow-negative" => Ok(Self::AllowNegative),
+            "reject" => Ok(Self::Reject),
+            "hold-available" => Ok(Self::HoldAvailable),
+            "lock" => Ok(Self::Lock),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Whether reversing a chargeback unlocks the account it locked
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ReversalUnlock {
+    /// The account stays locked until someone intervenes
+    #[default]
+    Keep,
+    /// Always unlock the account
+    Unlock,
+    /// Unlock the account unless another deposit of the client is still charged back
+    /// or represented
+    UnlockIfSettled,
+}
+
+impl FromStr for ReversalUnlock {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "unlock" => Ok(Self::Unlock),
+            "unlock-if-settled" => Ok(Self::UnlockIfSettled),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    #[test]
+    fn only_locked_accounts_are_restricted() {
+        let policy = LockPolicy::FROZEN;
+        assert_eq!(policy.check(false, Operation::Deposit, ucur!(1)), Ok(()));
+        assert_eq!(
+            policy.check(true, Operation::Deposit, ucur!(1)),
+            Err(TransactionExecutionError::AccountLocked)
+        );
+    }
+
+    #[test]
+    fn default_rejects_withdrawals_and_authorizations() {
+        let policy = LockPolicy::default();
+        for operation in [
+            Operation::Deposit,
+            Operation::Dispute,
+            Operation::Resolve,
+            Operation::ChargeBack,
+            Operation::Representment,
+            Operation::Reversal,
+            Operation::Capture,
+            Operation::Release,
+            Operation::Clear,
+            Operation::Return,
+        ] {
+            assert_eq!(policy.check(true, operation, ucur!(1)), Ok(()));
+        }
+        assert!(policy.check(true, Operation::Withdrawal, ucur!(0)).is_err());
+        assert!(policy.check(true, Operation::Authorize, ucur!(0)).is_err());
+        assert!(policy.check(true, Operation::Limit, ucur!(0)).is_err());
+    }
+
+    #[test]
+    fn limits_withdrawals() {
+        let policy = LockPolicy::withdrawal_limit(ucur!(10));
+        assert_eq!(policy.check(true, Operation::Withdrawal, ucur!(10)), Ok(()));
+        assert!(policy
+            .check(true, Operation::Withdrawal, ucur!(10.0001))
+            .is_err());
+    }
+
+    #[test]
+    fn parses_policy_names() {
+        assert_eq!("withdrawals".parse(), Ok(LockPolicy::default()));
+        assert_eq!("all".parse(), Ok(LockPolicy::FROZEN));
+        assert_eq!("disputes-only".parse(), Ok(LockPolicy::DISPUTES_ONLY));
+        assert_eq!(
+            "withdrawal-limit:2.5".parse(),
+            Ok(LockPolicy::withdrawal_limit(ucur!(2.5)))
+        );
+        assert!("withdrawal-limit:x".parse::<LockPolicy>().is_err());
+        assert!("none".parse::<LockPolicy>().is_err());
+    }
+
+    #[test]
+    fn parses_dispute_funding_names() {
+        assert_eq!("hold-available".parse(), Ok(DisputeFunding::HoldAvailable));
+        assert_eq!("lock".parse(), Ok(DisputeFunding::Lock));
+        assert!("hold".parse::<DisputeFunding>().is_err());
+    }
+
+    #[test]
+    fn parses_reversal_unlock_names() {
+        assert_eq!("keep".parse(), Ok(ReversalUnlock::Keep));
+        assert_eq!(
+            "unlock-if-settled".parse(),
+            Ok(ReversalUnlock::UnlockIfSettled)
+        );
+        assert!("settled".parse::<ReversalUnlock>().is_err());
+    }
+}
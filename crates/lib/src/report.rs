@@ -0,0 +1,312 @@
+//! Statistics about a run of transactions through a [`Ledger`].
+use crate::{
+    ICurrency, Ledger, Transaction, TransactionDiscriminants, TransactionExecutionError, UCurrency,
+};
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
+use strum::VariantNames;
+
+/// Outcomes of a single transaction type
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TypeCounts {
+    pub applied: u64,
+    pub rejected: u64,
+}
+
+/// Returned by [`execute`](crate::execute), see [`ExecutionReport::record`] for building one
+/// from a custom loop.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExecutionReport {
+    /// Indexed by [`TransactionDiscriminants`], use [`ExecutionReport::by_type`]
+    types: [TypeCounts; Transaction::VARIANTS.len()],
+    /// Rejections by [`TransactionExecutionError`] variant name
+    pub errors: BTreeMap<&'static str, u64>,
+    /// Rows that couldn't be parsed into a transaction
+    pub parse_errors: u64,
+    pub clients: usize,
+    pub locked_accounts: usize,
+    /// Largest number of deposits the ledger kept for disputes at once
+    pub peak_deposits: usize,
+    /// See [`Ledger::fee_income`]
+    pub fee_income: UCurrency,
+    /// See [`Ledger::interest_paid`]
+    pub interest_paid: ICurrency,
+    pub elapsed: Duration,
+}
+
+/// Lowercase names of the transaction types as they appear in the input
+fn type_name(ty: TransactionDiscriminants) -> &'static str {
+    match ty {
+        TransactionDiscriminants::Deposit => "deposit",
+        TransactionDiscriminants::Dispute => "dispute",
+        TransactionDiscriminants::ChargeBack => "chargeback",
+        TransactionDiscriminants::Resolve => "resolve",
+        TransactionDiscriminants::Withdrawal => "withdrawal",
+        TransactionDiscriminants::Representment => "representment",
+        TransactionDiscriminants::Reversal => "reversal",
+        TransactionDiscriminants::Authorize => "authorize",
+        TransactionDiscriminants::Capture => "capture",
+        TransactionDiscriminants::Release => "release",
+        TransactionDiscriminants::Clear => "clear",
+        TransactionDiscriminants::Return => "return",
+        TransactionDiscriminants::Accrue => "accrue",
+        TransactionDiscriminants::Limit => "limit",
+    }
+}
+
+impl ExecutionReport {
+    /// Counts the outcome of executing a transaction of type `ty` on `ledger`
+    #[inline]
+    pub fn record(
+        &mut self,
+        ledger: &Ledger,
+        ty: TransactionDiscriminants,
+        error: Option<&TransactionExecutionError>,
+    ) {
+        self.peak_deposits = self.peak_deposits.max(ledger.deposit_count());
+        let counts = &mut self.types[ty as usize];
+        match error {
+            None => counts.applied += 1,
+            Some(error) => {
+                counts.rejected += 1;
+                *self.errors.entry(error.into()).or_default() += 1;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn record_parse_error(&mut self) {
+        self.parse_errors += 1;
+    }
+
+    /// Takes the account statistics from `ledger` at the end of the run
+    pub fn finish(&mut self, ledger: &Ledger, elapsed: Duration) {
+        self.clients = ledger.iter().count();
+        self.locked_accounts = ledger.iter().filter(|account| account.locked).count();
+        self.peak_deposits = self.peak_deposits.max(ledger.deposit_count());
+        self.fee_income = ledger.fee_income();
+        self.interest_paid = ledger.interest_paid();
+        self.elapsed = elapsed;
+    }
+
+    pub fn by_type(&self) -> impl Iterator<Item = (TransactionDiscriminants, TypeCounts)> + '_ {
+        self.types.iter().enumerate().map(|(i, counts)| {
+            let ty = TransactionDiscriminants::from_repr(i).expect("index of a discriminant");
+            (ty, *counts)
+        })
+    }
+
+    /// Transactions parsed successfully
+    pub fn parsed(&self) -> u64 {
+        self.applied() + self.rejected()
+    }
+
+    pub fn applied(&self) -> u64 {
+        self.types.iter().map(|counts| counts.applied).sum()
+    }
+
+    pub fn rejected(&self) -> u64 {
+        self.types.iter().map(|counts| counts.rejected).sum()
+    }
+
+    /// Rows, including the ones that failed to parse, per second
+    pub fn throughput(&self) -> f64 {
+        let rows = (self.parsed() + self.parse_errors) as f64;
+        match self.elapsed.as_secs_f64() {
+            secs if secs > 0.0 => rows / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Amounts are JSON numbers with 4 decimals like in the account output, never rounded
+    /// through a float
+    pub fn to_json(&self) -> serde_json::Value {
+        let amount = |amount: &dyn Display| {
+            format!("{amount:.4}")
+                .parse::<serde_json::Number>()
+                .expect("amounts are valid JSON numbers")
+        };
+        let types = self
+            .by_type()
+            .map(|(ty, counts)| {
+                let counts = serde_json::json!({
+                    "applied": counts.applied,
+                    "rejected": counts.rejected,
+                });
+                (type_name(ty).to_string(), counts)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        serde_json::json!({
+            "parsed": self.parsed(),
+            "applied": self.applied(),
+            "rejected": self.rejected(),
+            "parse_errors": self.parse_errors,
+            "types": types,
+            "errors": self.errors,
+            "clients": self.clients,
+            "locked_accounts": self.locked_accounts,
+            "peak_deposits": self.peak_deposits,
+            "fee_income": amount(&self.fee_income),
+            "interest_paid": amount(&self.interest_paid),
+            "elapsed_seconds": self.elapsed.as_secs_f64(),
+            "rows_per_second": self.throughput(),
+        })
+    }
+}
+
+impl Display for ExecutionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "parsed {} rows, {} applied, {} rejected, {} parse errors",
+            self.parsed(),
+            self.applied(),
+            self.rejected(),
+            self.parse_errors
+        )?;
+        for (ty, counts) in self.by_type() {
+            let (applied, rejected) = (counts.applied, counts.rejected);
+            writeln!(
+                f,
+                "  {:<13} {applied} applied, {rejected} rejected",
+                type_name(ty)
+            )?;
+        }
+        for (error, count) in &self.errors {
+            writeln!(f, "  {error}: {count}")?;
+        }
+        writeln!(
+            f,
+            "{} clients, {} locked, peak of {} deposits",
+            self.clients, self.locked_accounts, self.peak_deposits
+        )?;
+        if self.fee_income > UCurrency::ZERO {
+            writeln!(f, "{:.4} fee income", self.fee_income)?;
+        }
+        if self.interest_paid != ICurrency::ZERO {
+            writeln!(f, "{:.4} interest paid", self.interest_paid)?;
+        }
+        writeln!(
+            f,
+            "took {:.3}s, {:.0} rows/s",
+            self.elapsed.as_secs_f64(),
+            self.throughput()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ExecutionReport {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,2\n\
+                     deposit,2,2,1\n\
+                     withdrawal,1,3,5\n\
+                     bogus,1,4,1\n\
+                     dispute,1,1,\n\
+                     chargeback,1,1,\n\
+                     dispute,1,9,\n";
+        let mut ledger = Ledger::default();
+        let mut report = ExecutionReport::default();
+        for transaction in crate::parse_csv(input.as_bytes()).unwrap() {
+            match transaction {
+                Ok(transaction) => {
+                    let ty = TransactionDiscriminants::from(&transaction);
+                    let error = ledger.execute(transaction).err();
+                    report.record(&ledger, ty, error.as_ref());
+                }
+                Err(_) => report.record_parse_error(),
+            }
+        }
+        report.finish(&ledger, Duration::from_secs(2));
+        report
+    }
+
+    #[test]
+    fn counts_outcomes() {
+        let report = report();
+        assert_eq!(
+            (report.parsed(), report.applied(), report.rejected()),
+            (6, 4, 2)
+        );
+        assert_eq!(report.parse_errors, 1);
+        assert_eq!(
+            report.by_type().collect::<Vec<_>>()[..2],
+            [
+                (
+                    TransactionDiscriminants::Deposit,
+                    TypeCounts {
+                        applied: 2,
+                        rejected: 0
+                    }
+                ),
+                (
+                    TransactionDiscriminants::Dispute,
+                    TypeCounts {
+                        applied: 1,
+                        rejected: 1
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            report.errors,
+            BTreeMap::from([("DepositNotFound", 1), ("InsufficientFunds", 1)])
+        );
+        assert_eq!(
+            (report.clients, report.locked_accounts, report.peak_deposits),
+            (2, 1, 2)
+        );
+        assert_eq!(report.throughput(), 3.5);
+    }
+
+    #[test]
+    fn formats_as_text_and_json() {
+        let report = report();
+        let text = report.to_string();
+        assert!(text.starts_with("parsed 6 rows, 4 applied, 2 rejected, 1 parse errors\n"));
+        assert!(text.contains("  withdrawal    0 applied, 1 rejected\n"));
+        assert!(text.contains("  InsufficientFunds: 1\n"));
+        assert!(text.ends_with("took 2.000s, 4 rows/s\n"));
+
+        let json = report.to_json();
+        assert_eq!(json["types"]["chargeback"]["applied"], 1);
+        assert_eq!(json["errors"]["DepositNotFound"], 1);
+        assert_eq!(json["rows_per_second"], 3.5);
+        assert_eq!(json["peak_deposits"], 2);
+        assert_eq!(json["fee_income"].to_string(), "0.0000");
+
+        // larger amounts than a float holds exactly
+        let report = ExecutionReport {
+            interest_paid: crate::icur!(-12345678901234.5678),
+            ..report
+        };
+        assert_eq!(
+            report.to_json()["interest_paid"].to_string(),
+            "-12345678901234.5678"
+        );
+    }
+
+    #[test]
+    fn empty_report_has_no_throughput() {
+        let mut report = ExecutionReport::default();
+        report.finish(&Ledger::default(), Duration::ZERO);
+        assert_eq!(report.parsed(), 0);
+        assert_eq!(report.throughput(), 0.0);
+    }
+
+    #[test]
+    fn samples_peak_deposits_per_transaction() {
+        let mut ledger = Ledger::default();
+        let mut report = ExecutionReport::default();
+        ledger
+            .execute(Transaction::new_deposit(1, 1, crate::ucur!(1)))
+            .unwrap();
+        report.record(&ledger, TransactionDiscriminants::Deposit, None);
+        assert_eq!(report.peak_deposits, 1);
+        // finishing with a different ledger keeps the peak seen while recording
+        report.finish(&Ledger::default(), Duration::ZERO);
+        assert_eq!(report.peak_deposits, 1);
+    }
+}
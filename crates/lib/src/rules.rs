@@ -0,0 +1,345 @@
+//! Velocity and amount limits on withdrawals, checked by the [`Ledger`](crate::Ledger) before
+//! [`ClientAccount::withdraw`](crate::ClientAccount::withdraw) and
+//! [`ClientAccount::capture`](crate::ClientAccount::capture), see [`WithdrawalRules`].
+use crate::{
+    format::UnknownFormat,
+    parser::{Header, ParserError},
+    TransactionExecutionError, UCurrency,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+    str::FromStr,
+    time::Duration,
+};
+
+/// The span of earlier withdrawals a rule looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+    /// The last N transactions of the ledger, including the withdrawal checked
+    Transactions(u64),
+    /// Withdrawals younger than this, by the time of the input, see
+    /// [`Ledger::now`](crate::Ledger::now)
+    Time(Duration),
+}
+
+impl Window {
+    /// Whether the withdrawal made by the transaction `since` at `at` falls in the window of the
+    /// transaction `sequence` executing at `now`, times in seconds
+    fn covers(self, (since, at): (u64, u64), sequence: u64, now: u64) -> bool {
+        match self {
+            Window::Transactions(transactions) => sequence - since < transactions,
+            Window::Time(duration) => Duration::from_secs(now.saturating_sub(at)) < duration,
+        }
+    }
+}
+
+impl FromStr for Window {
+    type Err = UnknownFormat;
+
+    /// Parses a number of transactions `100` or `100tx`, or a duration `90s`, `30m`, `24h`, `1d`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || UnknownFormat(s.to_string());
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (count, unit) = s.split_at(split);
+        let count = count.parse::<u64>().map_err(|_| error())?;
+        let seconds = match unit {
+            "" | "tx" => return Ok(Window::Transactions(count)),
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return Err(error()),
+        };
+        let seconds = count.checked_mul(seconds).ok_or_else(error)?;
+        Ok(Window::Time(Duration::from_secs(seconds)))
+    }
+}
+
+/// Earlier withdrawals of a client as `(sequence, time, amount)`, oldest first. Only the ones
+/// still covered by a window of the rules are kept.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WithdrawalHistory(VecDeque<(u64, u64, UCurrency)>);
+
+impl WithdrawalHistory {
+    /// Records a successful withdrawal of `amount` by the transaction `sequence` at the time `at`
+    /// in seconds
+    pub fn record(&mut self, sequence: u64, at: u64, amount: UCurrency) {
+        self.0.push_back((sequence, at, amount));
+    }
+
+    fn within(
+        &self,
+        window: Window,
+        sequence: u64,
+        now: u64,
+    ) -> impl Iterator<Item = UCurrency> + '_ {
+        self.0
+            .iter()
+            .filter(move |(since, at, _)| window.covers((*since, *at), sequence, now))
+            .map(|(_, _, amount)| *amount)
+    }
+}
+
+/// Limits on the withdrawals of a client, nothing is limited by default. Each violated rule
+/// fails the withdrawal with its own [`TransactionExecutionError`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawalRules {
+    /// Largest single withdrawal, see [`TransactionExecutionError::AmountLimitExceeded`]
+    pub max_amount: Option<UCurrency>,
+    /// Withdrawals allowed in the window, see
+    /// [`TransactionExecutionError::WithdrawalCountExceeded`]
+    pub max_count: Option<(u32, Window)>,
+    /// Sum of the withdrawals allowed in the window, see
+    /// [`TransactionExecutionError::WithdrawalTotalExceeded`]
+    pub max_total: Option<(UCurrency, Window)>,
+}
+
+impl WithdrawalRules {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether a window of the rules is a [`Window::Time`]
+    pub fn uses_time(&self) -> bool {
+        [
+            self.max_count.map(|(_, window)| window),
+            self.max_total.map(|(_, window)| window),
+        ]
+        .iter()
+        .flatten()
+        .any(|window| matches!(window, Window::Time(_)))
+    }
+
+    /// Checks a withdrawal of `amount` by the transaction `sequence` at the time `now` in seconds
+    /// against the earlier withdrawals in `history`, forgetting the ones no window covers anymore
+    pub fn check(
+        &self,
+        history: &mut WithdrawalHistory,
+        amount: UCurrency,
+        sequence: u64,
+        now: u64,
+    ) -> Result<(), TransactionExecutionError> {
+        if self.max_amount.is_some_and(|max| amount > max) {
+            return Err(TransactionExecutionError::AmountLimitExceeded);
+        }
+
+        let windows = [
+            self.max_count.map(|(_, window)| window),
+            self.max_total.map(|(_, window)| window),
+        ];
+        while let Some(&(since, at, _)) = history.0.front() {
+            if windows
+                .iter()
+                .flatten()
+                .any(|window| window.covers((since, at), sequence, now))
+            {
+                break;
+            }
+            history.0.pop_front();
+        }
+
+        if let Some((max, window)) = self.max_count {
+            if history.within(window, sequence, now).count() >= max as usize {
+                return Err(TransactionExecutionError::WithdrawalCountExceeded);
+            }
+        }
+        if let Some((max, window)) = self.max_total {
+            let total = history
+                .within(window, sequence, now)
+                .try_fold(amount, |total, amount| total.checked_add(amount));
+            // an overflowing total is over any maximum
+            let exceeded = match total {
+                Some(total) => total > max,
+                None => true,
+            };
+            if exceeded {
+                return Err(TransactionExecutionError::WithdrawalTotalExceeded);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The [`WithdrawalRules`] of every client, see [`load_withdrawal_rules`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RuleSet {
+    pub default: WithdrawalRules,
+    /// Replace the default rules of a client entirely
+    pub clients: HashMap<u16, WithdrawalRules>,
+}
+
+impl RuleSet {
+    pub fn for_client(&self, client: u16) -> &WithdrawalRules {
+        self.clients.get(&client).unwrap_or(&self.default)
+    }
+
+    /// Whether any of the rules has a time window, see [`WithdrawalRules::uses_time`]
+    pub fn uses_time(&self) -> bool {
+        self.default.uses_time() || self.clients.values().any(WithdrawalRules::uses_time)
+    }
+}
+
+/// Parses `max/window` like `5/1d` into the maximum and its window
+fn parse_windowed<T: FromStr>(value: &str) -> Result<(T, Window), ParserError>
+where
+    ParserError: From<T::Err>,
+{
+    let (max, window) = value
+        .split_once('/')
+        .ok_or_else(|| UnknownFormat(value.to_string()))?;
+    Ok((max.parse()?, window.parse::<Window>()?))
+}
+
+/// Reads a [`RuleSet`] from CSV with a header row and the columns:
+///
+/// * `client`, empty for the default rules
+/// * `max_amount`, the largest single withdrawal
+/// * `max_count`, withdrawals per window like `5/1d` or `3/100tx`, see [`Window`]
+/// * `max_total`, cumulative amount per window like `1000/24h`
+///
+/// Empty values aren't limited.
+pub fn load_withdrawal_rules(reader: impl Read) -> Result<RuleSet, ParserError> {
+    const COLUMNS: [&str; 4] = ["client", "max_amount", "max_count", "max_total"];
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    if let Some(unknown) = headers.iter().find(|header| !COLUMNS.contains(header)) {
+        return Err(ParserError::UnknownHeader(unknown.to_string()));
+    }
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let [client, max_amount, max_count, max_total] = COLUMNS.map(column);
+    if client.is_none() {
+        return Err(ParserError::MissingHeader(Header::Client));
+    }
+
+    let mut rules = RuleSet::default();
+    for record in reader.records() {
+        let record = record?;
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| record.get(column))
+                .filter(|value| !value.is_empty())
+        };
+        let client_rules = WithdrawalRules {
+            max_amount: field(max_amount).map(str::parse).transpose()?,
+            max_count: field(max_count).map(parse_windowed).transpose()?,
+            max_total: field(max_total).map(parse_windowed).transpose()?,
+        };
+        match field(client) {
+            Some(client) => {
+                rules.clients.insert(client.parse()?, client_rules);
+            }
+            None => rules.default = client_rules,
+        }
+    }
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    fn check(
+        rules: &WithdrawalRules,
+        history: &mut WithdrawalHistory,
+        amount: UCurrency,
+        sequence: u64,
+        now: u64,
+    ) -> Result<(), TransactionExecutionError> {
+        rules.check(history, amount, sequence, now)?;
+        history.record(sequence, now, amount);
+        Ok(())
+    }
+
+    #[test]
+    fn limits_single_withdrawals() {
+        let rules = WithdrawalRules {
+            max_amount: Some(ucur!(100)),
+            ..Default::default()
+        };
+        let (history, now) = (&mut WithdrawalHistory::default(), 0);
+        assert_eq!(check(&rules, history, ucur!(100), 1, now), Ok(()));
+        assert_eq!(
+            check(&rules, history, ucur!(100.0001), 2, now),
+            Err(TransactionExecutionError::AmountLimitExceeded)
+        );
+        // nothing to remember without windows
+        assert_eq!(rules.check(history, ucur!(1), 3, now), Ok(()));
+        assert!(history.0.is_empty());
+    }
+
+    #[test]
+    fn limits_withdrawals_per_transactions() {
+        let rules = WithdrawalRules {
+            max_count: Some((2, Window::Transactions(10))),
+            ..Default::default()
+        };
+        let (history, now) = (&mut WithdrawalHistory::default(), 0);
+        assert_eq!(check(&rules, history, ucur!(1), 1, now), Ok(()));
+        assert_eq!(check(&rules, history, ucur!(1), 5, now), Ok(()));
+        assert_eq!(
+            check(&rules, history, ucur!(1), 10, now),
+            Err(TransactionExecutionError::WithdrawalCountExceeded)
+        );
+        // the first withdrawal left the window
+        assert_eq!(check(&rules, history, ucur!(1), 11, now), Ok(()));
+        assert_eq!(history.0.len(), 2);
+    }
+
+    #[test]
+    fn limits_totals_per_day() {
+        let rules = WithdrawalRules {
+            max_total: Some((ucur!(100), "1d".parse().unwrap())),
+            ..Default::default()
+        };
+        let history = &mut WithdrawalHistory::default();
+        let start = 1_700_000_000;
+        let later = |hours: u64| start + hours * 60 * 60;
+        assert_eq!(check(&rules, history, ucur!(60), 1, start), Ok(()));
+        assert_eq!(
+            check(&rules, history, ucur!(40.0001), 2, later(23)),
+            Err(TransactionExecutionError::WithdrawalTotalExceeded)
+        );
+        assert_eq!(check(&rules, history, ucur!(40), 3, later(23)), Ok(()));
+        assert_eq!(check(&rules, history, ucur!(60), 4, later(24)), Ok(()));
+    }
+
+    #[test]
+    fn loads_rules() {
+        let rules = load_withdrawal_rules(
+            "client,max_amount,max_count,max_total\n,500,,1000/24h\n7,50,3/100tx,\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            rules.default,
+            WithdrawalRules {
+                max_amount: Some(ucur!(500)),
+                max_count: None,
+                max_total: Some((ucur!(1000), Window::Time(Duration::from_secs(86_400)))),
+            }
+        );
+        assert_eq!(
+            rules.for_client(7).max_count,
+            Some((3, Window::Transactions(100)))
+        );
+        assert_eq!(rules.for_client(8), &rules.default);
+        assert!(rules.uses_time());
+        assert!(!rules.clients[&7].uses_time());
+
+        assert!(matches!(
+            load_withdrawal_rules("client,max_count\n,3\n".as_bytes()),
+            Err(ParserError::UnknownFormat(_))
+        ));
+        assert!(matches!(
+            load_withdrawal_rules("client,per_day\n".as_bytes()),
+            Err(ParserError::UnknownHeader(_))
+        ));
+        assert!(matches!(
+            "5w".parse::<Window>(),
+            Err(UnknownFormat(window)) if window == "5w"
+        ));
+    }
+}
@@ -0,0 +1,164 @@
+//! Finds CSV record boundaries without parsing the records, for input without quoted fields.
+//!
+//! Positions follow the conventions of the `csv` crate exactly so a `csv::Reader` can be
+//! seeked to any boundary and report the same positions as when reading from the start.
+use memchr::memchr;
+use std::ops::Range;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// A single record found by [`RecordScanner`]
+#[cfg_attr(not(feature = "fast-parser"), allow(dead_code))]
+#[derive(Debug, Clone)]
+pub(crate) struct ScannedRecord {
+    /// Position `csv` reports for the record, that is the end of the previous record
+    pub(crate) start: csv::Position,
+    /// Position right after the record's terminator
+    pub(crate) end: csv::Position,
+    /// Byte range of the record without its terminator
+    pub(crate) content: Range<usize>,
+}
+
+/// Returned when the record boundaries can't be found without a full parse
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Unsupported;
+
+/// Iterator over the records of `data` starting from `start`, skipping empty and comment lines
+/// like `csv` does. Quoted fields are not understood, so callers must check the records for
+/// the quote character themselves.
+pub(crate) struct RecordScanner<'a> {
+    data: &'a [u8],
+    comment: Option<u8>,
+    /// Position after the last record
+    position: csv::Position,
+    line: u64,
+    line_start: usize,
+}
+
+impl<'a> RecordScanner<'a> {
+    pub(crate) fn new(data: &'a [u8], comment: Option<u8>, start: csv::Position) -> Self {
+        Self {
+            data,
+            comment,
+            line: start.line(),
+            line_start: start.byte() as usize,
+            position: start,
+        }
+    }
+
+    /// Position after the last record returned
+    #[cfg_attr(not(feature = "fast-parser"), allow(dead_code))]
+    pub(crate) fn position(&self) -> &csv::Position {
+        &self.position
+    }
+}
+
+impl<'a> Iterator for RecordScanner<'a> {
+    type Item = Result<ScannedRecord, Unsupported>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.line_start < self.data.len() {
+            let line_start = self.line_start;
+            let newline = memchr(b'\n', &self.data[line_start..]).map(|i| line_start + i);
+            let line_end = newline.unwrap_or(self.data.len());
+
+            let mut content = line_start..line_end;
+            if line_start == 0 && self.data.starts_with(UTF8_BOM) {
+                // csv strips the BOM at the very start of the input
+                content.start = UTF8_BOM.len().min(line_end);
+            }
+            let crlf = content.end > content.start && self.data[content.end - 1] == b'\r';
+            if crlf {
+                content.end -= 1;
+            }
+            if memchr(b'\r', &self.data[content.clone()]).is_some() {
+                // a bare `\r` terminates a record as well, leave those files to `csv`
+                return Some(Err(Unsupported));
+            }
+
+            let line = self.line;
+            self.line += newline.is_some() as u64;
+            self.line_start = line_end + 1;
+
+            let first = self.data.get(content.start).filter(|_| !content.is_empty());
+            if first.is_none() || first.copied() == self.comment {
+                continue;
+            }
+
+            // a record ends right after its terminator, `\r\n` is terminated by the `\r`
+            let (byte, line) = match newline {
+                Some(newline) if !crlf => (newline + 1, line + 1),
+                Some(newline) => (newline, line),
+                None => (self.data.len(), line),
+            };
+            let mut end = csv::Position::new();
+            end.set_byte(byte as u64)
+                .set_line(line)
+                .set_record(self.position.record() + 1);
+
+            let start = std::mem::replace(&mut self.position, end.clone());
+            return Some(Ok(ScannedRecord {
+                start,
+                end,
+                content,
+            }));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Positions reported by `csv` for every record of `data`
+    fn csv_positions(data: &[u8]) -> Vec<(u64, u64, u64)> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_reader(data);
+        let mut record = csv::ByteRecord::new();
+        let mut positions = Vec::new();
+        while reader.read_byte_record(&mut record).unwrap() {
+            let p = record.position().unwrap();
+            positions.push((p.byte(), p.line(), p.record()));
+        }
+        positions
+    }
+
+    #[test]
+    fn matches_csv_positions() {
+        for data in [
+            "a,b\nc\n\n\nd,e,f\n#comment\ng",
+            "\u{feff}a\r\nb\r\n\r\n#c\r\nd\r\n",
+            "\n\n a\n\n",
+            "\u{feff}#a\nb",
+        ] {
+            let records = RecordScanner::new(data.as_bytes(), Some(b'#'), csv::Position::new())
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let positions = records
+                .iter()
+                .map(|r| (r.start.byte(), r.start.line(), r.start.record()))
+                .collect::<Vec<_>>();
+            assert_eq!(positions, csv_positions(data.as_bytes()), "{data:?}");
+        }
+    }
+
+    #[test]
+    fn returns_record_content() {
+        let data = "a,b\r\n\nc";
+        let contents = RecordScanner::new(data.as_bytes(), None, csv::Position::new())
+            .map(|r| &data[r.unwrap().content])
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["a,b", "c"]);
+    }
+
+    #[test]
+    fn rejects_bare_carriage_returns() {
+        let mut scanner = RecordScanner::new(b"a\rb\n", None, csv::Position::new());
+        assert_eq!(scanner.next().unwrap().unwrap_err(), Unsupported);
+    }
+}
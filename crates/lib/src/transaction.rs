@@ -1,4 +1,5 @@
 use crate::UCurrency;
+use std::sync::Arc;
 
 #[derive(
     Debug, Clone, PartialEq, Eq, strum_macros::EnumVariantNames, strum_macros::EnumDiscriminants,
@@ -10,23 +11,124 @@ pub enum Transaction {
     ChargeBack(ChargeBack),
     Resolve(Resolve),
     Withdrawal(Withdrawal),
+    Representment(Representment),
+    Reversal(Reversal),
+    Authorize(Authorize),
+    Capture(Capture),
+    Release(Release),
+    Clear(Clear),
+    Return(Return),
+    Accrue(Accrue),
+    Limit(Limit),
 }
 
 impl Transaction {
     pub fn new_deposit(tx: u32, client: u16, amount: UCurrency) -> Self {
-        Self::Deposit(Deposit { tx, client, amount })
+        Self::Deposit(Deposit {
+            tx,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        })
     }
     pub fn new_dispute(tx: u32, client: u16) -> Self {
-        Self::Dispute(Dispute { tx, client })
+        Self::Dispute(Dispute {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
     }
     pub fn new_charge_back(tx: u32, client: u16) -> Self {
-        Self::ChargeBack(ChargeBack { tx, client })
+        Self::ChargeBack(ChargeBack {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
     }
     pub fn new_resolve(tx: u32, client: u16) -> Self {
-        Self::Resolve(Resolve { tx, client })
+        Self::Resolve(Resolve {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
     }
     pub fn new_withdrawal(tx: u32, client: u16, amount: UCurrency) -> Self {
-        Self::Withdrawal(Withdrawal { tx, client, amount })
+        Self::Withdrawal(Withdrawal {
+            tx,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        })
+    }
+
+    pub fn new_representment(tx: u32, client: u16) -> Self {
+        Self::Representment(Representment {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
+    }
+    pub fn new_reversal(tx: u32, client: u16) -> Self {
+        Self::Reversal(Reversal {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
+    }
+
+    pub fn new_authorize(tx: u32, client: u16, amount: UCurrency) -> Self {
+        Self::Authorize(Authorize {
+            tx,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        })
+    }
+    pub fn new_capture(tx: u32, client: u16, amount: UCurrency) -> Self {
+        Self::Capture(Capture {
+            tx,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        })
+    }
+    pub fn new_release(tx: u32, client: u16) -> Self {
+        Self::Release(Release {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
+    }
+
+    pub fn new_clear(tx: u32, client: u16) -> Self {
+        Self::Clear(Clear {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
+    }
+    pub fn new_return(tx: u32, client: u16) -> Self {
+        Self::Return(Return {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
+    }
+
+    pub fn new_accrue(tx: u32, client: u16) -> Self {
+        Self::Accrue(Accrue {
+            tx,
+            client,
+            metadata: Metadata::default(),
+        })
+    }
+    pub fn new_limit(tx: u32, client: u16, amount: UCurrency) -> Self {
+        Self::Limit(Limit {
+            tx,
+            client,
+            amount,
+            metadata: Metadata::default(),
+        })
     }
 
     #[inline]
@@ -37,6 +139,15 @@ impl Transaction {
             Transaction::ChargeBack(d) => d.tx,
             Transaction::Resolve(d) => d.tx,
             Transaction::Withdrawal(d) => d.tx,
+            Transaction::Representment(d) => d.tx,
+            Transaction::Reversal(d) => d.tx,
+            Transaction::Authorize(d) => d.tx,
+            Transaction::Capture(d) => d.tx,
+            Transaction::Release(d) => d.tx,
+            Transaction::Clear(d) => d.tx,
+            Transaction::Return(d) => d.tx,
+            Transaction::Accrue(d) => d.tx,
+            Transaction::Limit(d) => d.tx,
         }
     }
 
@@ -48,8 +159,61 @@ impl Transaction {
             Transaction::ChargeBack(d) => d.client,
             Transaction::Resolve(d) => d.client,
             Transaction::Withdrawal(d) => d.client,
+            Transaction::Representment(d) => d.client,
+            Transaction::Reversal(d) => d.client,
+            Transaction::Authorize(d) => d.client,
+            Transaction::Capture(d) => d.client,
+            Transaction::Release(d) => d.client,
+            Transaction::Clear(d) => d.client,
+            Transaction::Return(d) => d.client,
+            Transaction::Accrue(d) => d.client,
+            Transaction::Limit(d) => d.client,
         }
     }
+    #[inline]
+    pub fn metadata(&self) -> &Metadata {
+        match self {
+            Transaction::Deposit(d) => &d.metadata,
+            Transaction::Dispute(d) => &d.metadata,
+            Transaction::ChargeBack(d) => &d.metadata,
+            Transaction::Resolve(d) => &d.metadata,
+            Transaction::Withdrawal(d) => &d.metadata,
+            Transaction::Representment(d) => &d.metadata,
+            Transaction::Reversal(d) => &d.metadata,
+            Transaction::Authorize(d) => &d.metadata,
+            Transaction::Capture(d) => &d.metadata,
+            Transaction::Release(d) => &d.metadata,
+            Transaction::Clear(d) => &d.metadata,
+            Transaction::Return(d) => &d.metadata,
+            Transaction::Accrue(d) => &d.metadata,
+            Transaction::Limit(d) => &d.metadata,
+        }
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut Metadata {
+        match self {
+            Transaction::Deposit(d) => &mut d.metadata,
+            Transaction::Dispute(d) => &mut d.metadata,
+            Transaction::ChargeBack(d) => &mut d.metadata,
+            Transaction::Resolve(d) => &mut d.metadata,
+            Transaction::Withdrawal(d) => &mut d.metadata,
+            Transaction::Representment(d) => &mut d.metadata,
+            Transaction::Reversal(d) => &mut d.metadata,
+            Transaction::Authorize(d) => &mut d.metadata,
+            Transaction::Capture(d) => &mut d.metadata,
+            Transaction::Release(d) => &mut d.metadata,
+            Transaction::Clear(d) => &mut d.metadata,
+            Transaction::Return(d) => &mut d.metadata,
+            Transaction::Accrue(d) => &mut d.metadata,
+            Transaction::Limit(d) => &mut d.metadata,
+        }
+    }
+
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        *self.metadata_mut() = metadata;
+        self
+    }
+
     pub fn as_deposit(&self) -> Option<&Deposit> {
         if let Self::Deposit(v) = self {
             Some(v)
@@ -96,6 +260,134 @@ impl Transaction {
             None
         }
     }
+
+    pub fn as_representment(&self) -> Option<&Representment> {
+        if let Self::Representment(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_reversal(&self) -> Option<&Reversal> {
+        if let Self::Reversal(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_authorize(&self) -> Option<&Authorize> {
+        if let Self::Authorize(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_capture(&self) -> Option<&Capture> {
+        if let Self::Capture(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_release(&self) -> Option<&Release> {
+        if let Self::Release(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_clear(&self) -> Option<&Clear> {
+        if let Self::Clear(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_return(&self) -> Option<&Return> {
+        if let Self::Return(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_accrue(&self) -> Option<&Accrue> {
+        if let Self::Accrue(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_limit(&self) -> Option<&Limit> {
+        if let Self::Limit(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+/// `(column, value)` pairs of [`Metadata`]
+type MetadataEntries = Vec<(Arc<str>, String)>;
+
+/// Extra input columns carried along with a transaction as `(column, value)` pairs,
+/// see [`ParserConfig::extra_columns`](crate::ParserConfig::extra_columns).
+///
+/// Empty metadata is a null pointer so transactions without extra columns don't pay for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+// the box keeps the empty case pointer sized
+#[allow(clippy::box_collection)]
+pub struct Metadata(Option<Box<MetadataEntries>>);
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Value of the column `name`, if present
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(column, _)| *column == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the value of the column `name`, replacing any previous value
+    pub fn insert(&mut self, name: impl Into<Arc<str>>, value: impl Into<String>) {
+        let name = name.into();
+        let entries = self.0.get_or_insert_with(Default::default);
+        match entries.iter_mut().find(|(column, _)| *column == name) {
+            Some((_, old)) => *old = value.into(),
+            None => entries.push((name, value.into())),
+        }
+    }
+
+    /// The columns and their values in the order they were inserted
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .flat_map(|entries| entries.iter())
+            .map(|(column, value)| (column.as_ref(), value.as_str()))
+    }
+}
+
+/// Writes the entries as `column=value` separated by `, `
+impl std::fmt::Display for Metadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (column, value)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{column}={value}")?;
+        }
+        Ok(())
+    }
 }
 
 pub trait TransactionExecutor<TransactionType>
@@ -111,6 +403,7 @@ pub struct Deposit {
     pub tx: u32,
     pub client: u16,
     pub amount: UCurrency,
+    pub metadata: Metadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -118,22 +411,132 @@ pub struct Withdrawal {
     pub tx: u32,
     pub client: u16,
     pub amount: UCurrency,
+    pub metadata: Metadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChargeBack {
     pub tx: u32,
     pub client: u16,
+    pub metadata: Metadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Resolve {
     pub tx: u32,
     pub client: u16,
+    pub metadata: Metadata,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Dispute {
     pub tx: u32,
     pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// The merchant contests the chargeback of the deposit `tx`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Representment {
+    pub tx: u32,
+    pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// The contested chargeback of the deposit `tx` is reversed and the funds are credited again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reversal {
+    pub tx: u32,
+    pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// Reserves `amount` of the available funds under the authorization `tx`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authorize {
+    pub tx: u32,
+    pub client: u16,
+    pub amount: UCurrency,
+    pub metadata: Metadata,
+}
+
+/// Withdraws `amount` of the funds reserved by the authorization `tx`, the rest stays reserved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    pub tx: u32,
+    pub client: u16,
+    pub amount: UCurrency,
+    pub metadata: Metadata,
+}
+
+/// Makes the funds still reserved by the authorization `tx` available again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    pub tx: u32,
+    pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// The pending deposit `tx` cleared and its funds become available
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clear {
+    pub tx: u32,
+    pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// The pending deposit `tx` failed to clear, its funds never become available
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Return {
+    pub tx: u32,
+    pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// Control row running an interest accrual over all clients, see
+/// [`Ledger::accrue`](crate::Ledger::accrue). `tx` identifies the run, `client` is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accrue {
+    pub tx: u32,
+    pub client: u16,
+    pub metadata: Metadata,
+}
+
+/// Admin transaction setting the overdraft limit of the client to `amount`, zero removes it.
+/// See [`ClientAccount::overdraft_limit`](crate::ClientAccount::overdraft_limit).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limit {
+    pub tx: u32,
+    pub client: u16,
+    pub amount: UCurrency,
+    pub metadata: Metadata,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metadata_keeps_insertion_order() {
+        let mut metadata = Metadata::default();
+        assert!(metadata.is_empty());
+        metadata.insert("b", "1");
+        metadata.insert("a", "2");
+        metadata.insert("b", "3");
+        assert_eq!(
+            metadata.iter().collect::<Vec<_>>(),
+            [("b", "3"), ("a", "2")]
+        );
+        assert_eq!(metadata.get("a"), Some("2"));
+        assert_eq!(metadata.get("c"), None);
+        assert_eq!(metadata.to_string(), "b=3, a=2");
+    }
+
+    #[test]
+    fn metadata_is_pointer_sized() {
+        assert_eq!(
+            std::mem::size_of::<Metadata>(),
+            std::mem::size_of::<usize>()
+        );
+    }
 }
@@ -0,0 +1,233 @@
+//! Strict amount validation, enabled by [`ParserConfig::strict`](crate::ParserConfig::strict).
+//!
+//! It runs on the result of the regular field parsing together with the raw amount text,
+//! which is needed since the currency parser silently rounds excess decimals away.
+use crate::{
+    parser::{lenient_trim, ParserError},
+    transaction::Transaction,
+    ICurrency, UCurrency,
+};
+use ascii::AsAsciiStr;
+
+/// Number of decimals representable without rounding
+pub const MAX_DECIMALS: usize = 4;
+
+/// The largest amount accepted, anything larger can't be represented in a client's balance
+pub const MAX_AMOUNT: UCurrency = UCurrency::from_bits(ICurrency::MAX.to_bits() as u64);
+
+fn amount_text(raw: Option<&[u8]>, lenient: bool) -> Option<&str> {
+    let raw = raw?;
+    if lenient {
+        std::str::from_utf8(raw).ok().map(lenient_trim)
+    } else {
+        raw.as_ascii_str().ok().map(|s| s.trim().as_str())
+    }
+}
+
+/// Returns true for plain decimal numbers like `123` or `1.5`
+fn is_decimal(text: &str) -> bool {
+    let (integral, fraction) = text.split_once('.').unwrap_or((text, ""));
+    !integral.is_empty()
+        && integral.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Number of decimals that would be lost to rounding, trailing zeros don't count
+fn significant_decimals(text: &str) -> usize {
+    text.split_once('.')
+        .map_or(0, |(_, fraction)| fraction.trim_end_matches('0').len())
+}
+
+fn validate_amount(text: &str, amount: UCurrency) -> Result<(), ParserError> {
+    if significant_decimals(text) > MAX_DECIMALS {
+        return Err(ParserError::ExcessPrecision(text.to_string()));
+    }
+    if amount == UCurrency::ZERO {
+        return Err(ParserError::ZeroAmount);
+    }
+    if amount > MAX_AMOUNT {
+        return Err(ParserError::AmountOutOfRange(text.to_string()));
+    }
+    Ok(())
+}
+
+/// Like [`validate_amount`] but zero is fine, it removes the overdraft limit
+fn validate_limit(text: &str, amount: UCurrency) -> Result<(), ParserError> {
+    match validate_amount(text, amount) {
+        Err(ParserError::ZeroAmount) => Ok(()),
+        result => result,
+    }
+}
+
+/// Applies the strict rules to the outcome of parsing a record.
+/// `raw_amount` is the untouched amount field, `None` if the column is absent.
+#[inline]
+pub(crate) fn validate(
+    result: Result<Transaction, ParserError>,
+    raw_amount: Option<&[u8]>,
+    lenient: bool,
+) -> Result<Transaction, ParserError> {
+    let text = amount_text(raw_amount, lenient);
+
+    match result {
+        Ok(Transaction::Deposit(ref d)) => validate_amount(text.unwrap_or_default(), d.amount)?,
+        Ok(Transaction::Withdrawal(ref w)) => validate_amount(text.unwrap_or_default(), w.amount)?,
+        Ok(Transaction::Authorize(ref a)) => validate_amount(text.unwrap_or_default(), a.amount)?,
+        Ok(Transaction::Capture(ref c)) => validate_amount(text.unwrap_or_default(), c.amount)?,
+        Ok(Transaction::Limit(ref l)) => validate_limit(text.unwrap_or_default(), l.amount)?,
+        Ok(
+            Transaction::Dispute(_)
+            | Transaction::Resolve(_)
+            | Transaction::ChargeBack(_)
+            | Transaction::Representment(_)
+            | Transaction::Reversal(_)
+            | Transaction::Release(_)
+            | Transaction::Clear(_)
+            | Transaction::Return(_)
+            | Transaction::Accrue(_),
+        ) => {
+            if let Some(text) = text.filter(|text| !text.is_empty()) {
+                return Err(ParserError::UnexpectedAmount(text.to_string()));
+            }
+        }
+        Err(ParserError::CurrencyParseError(_)) => match text {
+            Some("") => return Err(ParserError::EmptyAmount),
+            // a well formed number the currency type can't hold
+            Some(text) if is_decimal(text) => {
+                return Err(ParserError::AmountOutOfRange(text.to_string()))
+            }
+            _ => {}
+        },
+        Err(_) => {}
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ucur;
+
+    fn check(ty: &str, amount: Option<&str>) -> Result<Transaction, ParserError> {
+        let amount = amount.map(str::as_bytes);
+        validate(
+            crate::parser::parse_fields(ty.as_bytes(), b"1", b"1", amount),
+            amount,
+            false,
+        )
+    }
+
+    #[test]
+    fn accepts_valid_amounts() {
+        assert_eq!(
+            check("deposit", Some("1.2345")).unwrap(),
+            Transaction::new_deposit(1, 1, ucur!(1.2345))
+        );
+        // trailing zeros don't lose precision
+        assert_eq!(
+            check("withdrawal", Some(" 1.500000 ")).unwrap(),
+            Transaction::new_withdrawal(1, 1, ucur!(1.5))
+        );
+        assert_eq!(
+            check("dispute", Some("")).unwrap(),
+            Transaction::new_dispute(1, 1)
+        );
+        assert_eq!(
+            check("resolve", None).unwrap(),
+            Transaction::new_resolve(1, 1)
+        );
+    }
+
+    #[test]
+    fn rejects_excess_precision() {
+        assert!(matches!(
+            check("deposit", Some("1.23456")),
+            Err(ParserError::ExcessPrecision(text)) if text == "1.23456"
+        ));
+    }
+
+    #[test]
+    fn rejects_zero_amounts() {
+        assert!(matches!(
+            check("deposit", Some("0")),
+            Err(ParserError::ZeroAmount)
+        ));
+        assert!(matches!(
+            check("withdrawal", Some("0.0000")),
+            Err(ParserError::ZeroAmount)
+        ));
+        assert!(matches!(
+            check("capture", Some("0")),
+            Err(ParserError::ZeroAmount)
+        ));
+    }
+
+    #[test]
+    fn accepts_zero_limits() {
+        assert_eq!(
+            check("limit", Some("0")).unwrap(),
+            Transaction::new_limit(1, 1, ucur!(0))
+        );
+        assert!(matches!(
+            check("limit", Some("0.00001")),
+            Err(ParserError::ExcessPrecision(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_unexpected_amounts() {
+        for ty in [
+            "dispute",
+            "resolve",
+            "chargeback",
+            "representment",
+            "reversal",
+            "release",
+            "clear",
+            "return",
+            "accrue",
+        ] {
+            assert!(matches!(
+                check(ty, Some("1.0")),
+                Err(ParserError::UnexpectedAmount(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_empty_amounts() {
+        assert!(matches!(
+            check("deposit", Some(" ")),
+            Err(ParserError::EmptyAmount)
+        ));
+        // a missing column is still reported as such
+        assert!(matches!(
+            check("deposit", None),
+            Err(ParserError::MissingHeader(crate::Header::Amount))
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range() {
+        // fits in UCurrency but not in a balance
+        assert!(matches!(
+            check("deposit", Some("200000000000000")),
+            Err(ParserError::AmountOutOfRange(_))
+        ));
+        // doesn't fit in UCurrency at all
+        assert!(matches!(
+            check("deposit", Some("99999999999999999999")),
+            Err(ParserError::AmountOutOfRange(_))
+        ));
+        assert_eq!(
+            check("deposit", Some("140737488355327")).unwrap(),
+            Transaction::new_deposit(1, 1, ucur!(140737488355327))
+        );
+        // malformed numbers keep the generic error
+        assert!(matches!(
+            check("deposit", Some("1e5")),
+            Err(ParserError::CurrencyParseError(_))
+        ));
+    }
+}
@@ -1,42 +1,433 @@
-use crate::Ledger;
-use csv::WriterBuilder;
-use std::io::{Cursor, Write};
+use crate::{format::UnknownFormat, ClientAccount, ClientStats, ICurrency, Ledger};
+use csv::{ByteRecord, WriterBuilder};
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fmt::Display,
+    io::{BufWriter, Write},
+    str::FromStr,
+};
 
+/// Order of the accounts in the output, ties are always broken by client id
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    ClientId,
+    /// Highest total first
+    Total,
+    /// Locked accounts first
+    LockedFirst,
+}
+
+impl FromStr for SortOrder {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "client" => Ok(Self::ClientId),
+            "total" => Ok(Self::Total),
+            "locked" => Ok(Self::LockedFirst),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Selects which accounts are written, an account must match every condition that is set
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountFilter {
+    pub locked_only: bool,
+    pub negative_available_only: bool,
+    /// Only these clients, all clients if `None`
+    pub clients: Option<HashSet<u16>>,
+}
+
+impl AccountFilter {
+    pub fn matches(&self, account: &ClientAccount) -> bool {
+        (!self.locked_only || account.locked)
+            && (!self.negative_available_only || account.available < ICurrency::ZERO)
+            && match &self.clients {
+                Some(clients) => clients.contains(&account.id),
+                None => true,
+            }
+    }
+}
+
+/// A column of the account output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Client,
+    Available,
+    Held,
+    Total,
+    Locked,
+    /// Number of deposits, see [`ClientStats`]
+    Deposits,
+    Withdrawals,
+    OpenDisputes,
+    ChargedBack,
+    /// Empty, or `null` in JSON, for clients without a successful transaction
+    LastTx,
+    /// See [`ClientAccount::shortfall`]
+    Shortfall,
+    /// See [`ClientAccount::reserved`], part of the total but not of the available funds
+    Reserved,
+    /// See [`ClientAccount::pending`], part of the total but not of the available funds
+    Pending,
+    /// Sum of the fees charged, see [`ClientStats::fees`]
+    Fees,
+    /// Net interest paid, see [`ClientStats::interest`]
+    Interest,
+    /// See [`ClientAccount::overdraft_limit`]
+    OverdraftLimit,
+}
+
+impl Column {
+    /// The columns of the original output format
+    pub const DEFAULT: [Column; 5] = [
+        Column::Client,
+        Column::Available,
+        Column::Held,
+        Column::Total,
+        Column::Locked,
+    ];
+
+    /// Name used for the CSV header and JSON keys
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Client => "client",
+            Column::Available => "available",
+            Column::Held => "held",
+            Column::Total => "total",
+            Column::Locked => "locked",
+            Column::Deposits => "deposits",
+            Column::Withdrawals => "withdrawals",
+            Column::OpenDisputes => "open_disputes",
+            Column::ChargedBack => "charged_back",
+            Column::LastTx => "last_tx",
+            Column::Shortfall => "shortfall",
+            Column::Reserved => "reserved",
+            Column::Pending => "pending",
+            Column::Fees => "fees",
+            Column::Interest => "interest",
+            Column::OverdraftLimit => "overdraft_limit",
+        }
+    }
+
+    /// Writes the value of the column for `account`, `null` is written for absent values
+    fn write_value(
+        &self,
+        buf: &mut Vec<u8>,
+        (account, stats): (&ClientAccount, &ClientStats),
+        format: NumberFormat,
+        null: &[u8],
+    ) -> Result<(), std::io::Error> {
+        let mut int =
+            |value: u64| buf.extend_from_slice(itoa::Buffer::new().format(value).as_bytes());
+        match self {
+            Column::Client => int(account.id.into()),
+            Column::Deposits => int(stats.deposits.into()),
+            Column::Withdrawals => int(stats.withdrawals.into()),
+            Column::OpenDisputes => int(stats.open_disputes.into()),
+            Column::LastTx => match stats.last_tx {
+                Some(tx) => int(tx.into()),
+                None => buf.extend_from_slice(null),
+            },
+            Column::Available => format.write(buf, account.available)?,
+            Column::Held => format.write(buf, account.held)?,
+            Column::Total => format.write(buf, account.total())?,
+            Column::ChargedBack => format.write(buf, stats.charged_back)?,
+            Column::Shortfall => format.write(buf, account.shortfall)?,
+            Column::Reserved => format.write(buf, account.reserved)?,
+            Column::Pending => format.write(buf, account.pending)?,
+            Column::Fees => format.write(buf, stats.fees)?,
+            Column::Interest => format.write(buf, stats.interest)?,
+            Column::OverdraftLimit => format.write(buf, account.overdraft_limit)?,
+            Column::Locked => {
+                buf.extend_from_slice(if account.locked { b"true" } else { b"false" })
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Column {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Column::Client,
+            Column::Available,
+            Column::Held,
+            Column::Total,
+            Column::Locked,
+            Column::Deposits,
+            Column::Withdrawals,
+            Column::OpenDisputes,
+            Column::ChargedBack,
+            Column::LastTx,
+            Column::Shortfall,
+            Column::Reserved,
+            Column::Pending,
+            Column::Fees,
+            Column::Interest,
+            Column::OverdraftLimit,
+        ]
+        .into_iter()
+        .find(|column| column.name() == s)
+        .ok_or_else(|| UnknownFormat(s.to_string()))
+    }
+}
+
+/// How amounts are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// A fixed number of decimals, at most 4, `1.5000` with 4
+    Fixed(u8),
+    /// 4 decimals without trailing zeros, `1.5`
+    Trimmed,
+    /// An integer amount of the smallest unit 0.0001, `15000`
+    MinorUnits,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self::Fixed(4)
+    }
+}
+
+impl NumberFormat {
+    fn write(self, buf: &mut Vec<u8>, value: impl Display) -> Result<(), std::io::Error> {
+        let start = buf.len();
+        match self {
+            NumberFormat::Fixed(decimals) => return write!(buf, "{value:.*}", decimals.into()),
+            NumberFormat::Trimmed => {
+                write!(buf, "{value:.4}")?;
+                let trimmed = buf[start..]
+                    .iter()
+                    .rposition(|b| *b != b'0')
+                    .map_or(buf.len(), |i| start + i + 1);
+                buf.truncate(trimmed);
+                if buf.last() == Some(&b'.') {
+                    buf.pop();
+                }
+            }
+            NumberFormat::MinorUnits => {
+                write!(buf, "{value:.4}")?;
+                let sign = usize::from(buf.get(start) == Some(&b'-'));
+                let digits = buf
+                    .drain(start + sign..)
+                    .filter(|b| *b != b'.')
+                    .collect::<Vec<_>>();
+                // keep at least one digit so zero is written as `0`
+                let first = digits[..digits.len() - 1]
+                    .iter()
+                    .position(|b| *b != b'0')
+                    .unwrap_or(digits.len() - 1);
+                buf.extend_from_slice(&digits[first..]);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for NumberFormat {
+    type Err = UnknownFormat;
+
+    /// Parses `fixed`, `fixed:<decimals>`, `trimmed` or `minor`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "fixed" => Ok(Self::default()),
+            None if s == "trimmed" => Ok(Self::Trimmed),
+            None if s == "minor" => Ok(Self::MinorUnits),
+            Some(("fixed", decimals)) => decimals
+                .parse()
+                .ok()
+                .filter(|decimals| *decimals <= 4)
+                .map(Self::Fixed)
+                .ok_or_else(|| UnknownFormat(s.to_string())),
+            _ => Err(UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// Output settings shared by all the writers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriterConfig {
+    pub sort: SortOrder,
+    pub filter: AccountFilter,
+    /// Columns in the order they are written, defaults to [`Column::DEFAULT`]. Funds that count
+    /// towards the total but aren't available or held are never hidden: if any written account
+    /// has some and the total is written without their column, it's added before the total.
+    pub columns: Vec<Column>,
+    pub number_format: NumberFormat,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        Self {
+            sort: SortOrder::default(),
+            filter: AccountFilter::default(),
+            columns: Column::DEFAULT.to_vec(),
+            number_format: NumberFormat::default(),
+        }
+    }
+}
+
+impl WriterConfig {
+    /// The accounts to write in the order to write them
+    fn accounts<'a>(&self, ledger: &'a Ledger) -> Vec<(&'a ClientAccount, &'a ClientStats)> {
+        let mut accounts = ledger
+            .iter_with_stats()
+            .filter(|(account, _)| self.filter.matches(account))
+            .collect::<Vec<_>>();
+        // client ids are unique so an unstable sort is still deterministic
+        match self.sort {
+            SortOrder::ClientId => accounts.sort_unstable_by_key(|(a, _)| a.id),
+            SortOrder::Total => accounts.sort_unstable_by_key(|(a, _)| (Reverse(a.total()), a.id)),
+            SortOrder::LockedFirst => accounts.sort_unstable_by_key(|(a, _)| (!a.locked, a.id)),
+        }
+        accounts
+    }
+
+    /// [`WriterConfig::columns`] with the columns the `accounts` need to explain their total
+    fn columns(&self, accounts: &[(&ClientAccount, &ClientStats)]) -> Vec<Column> {
+        let mut columns = self.columns.clone();
+        let total = match columns.iter().position(|column| *column == Column::Total) {
+            Some(total) => total,
+            None => return columns,
+        };
+        let hidden = [
+            (
+                Column::Reserved,
+                accounts.iter().any(|(account, _)| account.reserved != 0),
+            ),
+            (
+                Column::Pending,
+                accounts.iter().any(|(account, _)| account.pending != 0),
+            ),
+        ];
+        for (column, nonzero) in hidden.into_iter().rev() {
+            if nonzero && !columns.contains(&column) {
+                columns.insert(total, column);
+            }
+        }
+        columns
+    }
+}
+
+/// Writes the accounts as CSV ordered by client id
 pub fn write_csv<W: Write>(ledger: &Ledger, writer: W) -> Result<(), std::io::Error> {
+    write_csv_with_config(ledger, writer, &WriterConfig::default())
+}
+
+pub fn write_csv_with_config<W: Write>(
+    ledger: &Ledger,
+    writer: W,
+    config: &WriterConfig,
+) -> Result<(), std::io::Error> {
+    let accounts = config.accounts(ledger);
+    let columns = config.columns(&accounts);
     let mut writer = WriterBuilder::new().from_writer(writer);
-    writer.write_record(&["client", "available", "held", "total", "locked"])?;
-
-    let mut id_buf = itoa::Buffer::new();
-    let mut available_buf = [0u8; 24];
-    let mut held_buf = [0u8; 24];
-    let mut total_buf = [0u8; 24];
-
-    for client in ledger.iter() {
-        let mut available_cursor = Cursor::new(&mut available_buf[..]);
-        let mut held_cursor = Cursor::new(&mut held_buf[..]);
-        let mut total_cursor = Cursor::new(&mut total_buf[..]);
-        write!(available_cursor, "{:.4}", client.available)?;
-        write!(held_cursor, "{:.4}", client.held)?;
-        write!(total_cursor, "{:.4}", client.total())?;
-
-        writer.write_record(&[
-            id_buf.format(client.id).as_bytes(),
-            &available_cursor.get_ref()[..(available_cursor.position() as usize)],
-            &held_cursor.get_ref()[..(held_cursor.position() as usize)],
-            &total_cursor.get_ref()[..(total_cursor.position() as usize)],
-            if client.locked {
-                "true".as_bytes()
-            } else {
-                "false".as_bytes()
-            },
-        ])?;
+    writer.write_record(columns.iter().map(Column::name))?;
+
+    let mut record = ByteRecord::new();
+    let mut field = Vec::new();
+    for client in accounts {
+        record.clear();
+        for column in &columns {
+            field.clear();
+            column.write_value(&mut field, client, config.number_format, b"")?;
+            record.push_field(&field);
+        }
+        writer.write_byte_record(&record)?;
     }
 
     writer.flush()
 }
 
+fn write_json_object<W: Write>(
+    writer: &mut W,
+    client: (&ClientAccount, &ClientStats),
+    columns: &[Column],
+    number_format: NumberFormat,
+    buf: &mut Vec<u8>,
+) -> Result<(), std::io::Error> {
+    buf.clear();
+    buf.push(b'{');
+    for (i, column) in columns.iter().enumerate() {
+        if i != 0 {
+            buf.push(b',');
+        }
+        write!(buf, "\"{}\":", column.name())?;
+        column.write_value(buf, client, number_format, b"null")?;
+    }
+    buf.push(b'}');
+    writer.write_all(buf)
+}
+
+/// Writes the accounts as a single JSON array of objects ordered by client id.
+/// Amounts are written as JSON numbers with 4 decimals, just like in the CSV output.
+pub fn write_json<W: Write>(ledger: &Ledger, writer: W) -> Result<(), std::io::Error> {
+    write_json_with_config(ledger, writer, &WriterConfig::default())
+}
+
+pub fn write_json_with_config<W: Write>(
+    ledger: &Ledger,
+    writer: W,
+    config: &WriterConfig,
+) -> Result<(), std::io::Error> {
+    let accounts = config.accounts(ledger);
+    let columns = config.columns(&accounts);
+    let mut writer = BufWriter::new(writer);
+    let mut buf = Vec::new();
+    writer.write_all(b"[")?;
+    for (i, client) in accounts.into_iter().enumerate() {
+        if i != 0 {
+            writer.write_all(b",")?;
+        }
+        writer.write_all(b"\n")?;
+        write_json_object(
+            &mut writer,
+            client,
+            &columns,
+            config.number_format,
+            &mut buf,
+        )?;
+    }
+    writer.write_all(b"\n]\n")?;
+    writer.flush()
+}
+
+/// Writes the accounts as newline delimited JSON with one object per line ordered by client id.
+pub fn write_ndjson<W: Write>(ledger: &Ledger, writer: W) -> Result<(), std::io::Error> {
+    write_ndjson_with_config(ledger, writer, &WriterConfig::default())
+}
+
+pub fn write_ndjson_with_config<W: Write>(
+    ledger: &Ledger,
+    writer: W,
+    config: &WriterConfig,
+) -> Result<(), std::io::Error> {
+    let accounts = config.accounts(ledger);
+    let columns = config.columns(&accounts);
+    let mut writer = BufWriter::new(writer);
+    let mut buf = Vec::new();
+    for client in accounts {
+        write_json_object(
+            &mut writer,
+            client,
+            &columns,
+            config.number_format,
+            &mut buf,
+        )?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{AccountFilter, Column, NumberFormat, SortOrder, WriterConfig};
     use crate::ucur;
     use crate::Ledger;
     use crate::Transaction;
@@ -81,4 +472,269 @@ mod tests {
             "client,available,held,total,locked\n3,0.0000,10.0000,10.0000,false\n"
         );
     }
+
+    #[test]
+    fn outputs_json_correctly() {
+        let mut l = Ledger::default();
+        l.execute(Transaction::new_deposit(1, 1, ucur!(1.5)))
+            .unwrap()
+            .execute(Transaction::new_dispute(1, 1))
+            .unwrap();
+        let expected =
+            r#"{"client":1,"available":0.0000,"held":1.5000,"total":1.5000,"locked":false}"#;
+
+        let mut buf = Vec::new();
+        super::write_ndjson(&l, &mut buf).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), format!("{expected}\n"));
+
+        buf.clear();
+        super::write_json(&l, &mut buf).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), format!("[\n{expected}\n]\n"));
+
+        buf.clear();
+        super::write_json(&Ledger::default(), &mut buf).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), "[\n]\n");
+    }
+
+    fn ledger() -> Ledger {
+        let mut l = Ledger::default();
+        for (client, amount) in [(3, ucur!(5)), (1, ucur!(7)), (4, ucur!(5)), (2, ucur!(1))] {
+            l.execute(Transaction::new_deposit(client.into(), client, amount))
+                .unwrap();
+        }
+        l.execute(Transaction::new_dispute(2, 2))
+            .unwrap()
+            .execute(Transaction::new_charge_back(2, 2))
+            .unwrap()
+            .execute(Transaction::new_deposit(5, 3, ucur!(1)))
+            .unwrap()
+            .execute(Transaction::new_withdrawal(6, 3, ucur!(6)))
+            .unwrap()
+            .execute(Transaction::new_dispute(3, 3))
+            .unwrap();
+        l
+    }
+
+    fn write(config: &WriterConfig) -> String {
+        let mut buf = Vec::new();
+        super::write_csv_with_config(&ledger(), &mut buf, config).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn outputs_in_client_order() {
+        let mut buf = Vec::new();
+        super::write_csv(&ledger(), &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "client,available,held,total,locked\n\
+             1,7.0000,0.0000,7.0000,false\n\
+             2,0.0000,0.0000,0.0000,true\n\
+             3,-5.0000,5.0000,0.0000,false\n\
+             4,5.0000,0.0000,5.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn can_sort_output() {
+        let clients = |config: &WriterConfig| {
+            write(config)
+                .lines()
+                .skip(1)
+                .map(|line| line.split(',').next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let config = |sort| WriterConfig {
+            sort,
+            ..Default::default()
+        };
+        assert_eq!(clients(&config(SortOrder::Total)), ["1", "4", "2", "3"]);
+        assert_eq!(
+            clients(&config(SortOrder::LockedFirst)),
+            ["2", "1", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn can_filter_output() {
+        let filter = |filter| WriterConfig {
+            filter,
+            ..Default::default()
+        };
+        assert_eq!(
+            write(&filter(AccountFilter {
+                locked_only: true,
+                ..Default::default()
+            })),
+            "client,available,held,total,locked\n2,0.0000,0.0000,0.0000,true\n"
+        );
+        assert_eq!(
+            write(&filter(AccountFilter {
+                negative_available_only: true,
+                ..Default::default()
+            })),
+            "client,available,held,total,locked\n3,-5.0000,5.0000,0.0000,false\n"
+        );
+        assert_eq!(
+            write(&filter(AccountFilter {
+                clients: Some([4, 1, 9].into()),
+                ..Default::default()
+            })),
+            "client,available,held,total,locked\n1,7.0000,0.0000,7.0000,false\n4,5.0000,0.0000,5.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn parses_sort_order_names() {
+        assert_eq!("total".parse(), Ok(SortOrder::Total));
+        assert!("amount".parse::<SortOrder>().is_err());
+    }
+
+    #[test]
+    fn can_write_statistics_columns() {
+        let config = WriterConfig {
+            columns: vec![
+                Column::Client,
+                Column::Deposits,
+                Column::Withdrawals,
+                Column::OpenDisputes,
+                Column::ChargedBack,
+                Column::LastTx,
+            ],
+            ..Default::default()
+        };
+        let mut l = ledger();
+        // a failed transaction still creates the client but doesn't count
+        l.execute(Transaction::new_withdrawal(7, 5, ucur!(1))).ok();
+
+        let mut buf = Vec::new();
+        super::write_csv_with_config(&l, &mut buf, &config).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "client,deposits,withdrawals,open_disputes,charged_back,last_tx\n\
+             1,1,0,0,0.0000,1\n\
+             2,1,0,0,1.0000,2\n\
+             3,2,1,1,0.0000,3\n\
+             4,1,0,0,0.0000,4\n\
+             5,0,0,0,0.0000,\n"
+        );
+
+        buf.clear();
+        let config = WriterConfig {
+            columns: vec![Column::Client, Column::LastTx],
+            filter: AccountFilter {
+                clients: Some([4, 5].into()),
+                ..Default::default()
+            },
+            ..config
+        };
+        super::write_ndjson_with_config(&l, &mut buf, &config).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "{\"client\":4,\"last_tx\":4}\n{\"client\":5,\"last_tx\":null}\n"
+        );
+    }
+
+    #[test]
+    fn can_write_reserved_column() {
+        let mut l = ledger();
+        l.execute(Transaction::new_authorize(8, 1, ucur!(2.5)))
+            .unwrap();
+        let config = WriterConfig {
+            columns: vec![
+                Column::Client,
+                Column::Available,
+                Column::Reserved,
+                Column::Total,
+            ],
+            filter: AccountFilter {
+                clients: Some([1].into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        super::write_csv_with_config(&l, &mut buf, &config).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "client,available,reserved,total
+1,4.5000,2.5000,7.0000
+"
+        );
+
+        // the default columns gain it as soon as some account has reserved funds
+        let config = WriterConfig {
+            filter: config.filter,
+            ..Default::default()
+        };
+        buf.clear();
+        super::write_ndjson_with_config(&l, &mut buf, &config).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "{\"client\":1,\"available\":4.5000,\"held\":0.0000,\"reserved\":2.5000,\
+             \"total\":7.0000,\"locked\":false}\n"
+        );
+        l.execute(Transaction::new_release(8, 1)).unwrap();
+        buf.clear();
+        super::write_csv_with_config(&l, &mut buf, &config).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "client,available,held,total,locked\n1,7.0000,0.0000,7.0000,false\n"
+        );
+    }
+
+    #[test]
+    fn writes_pending_column_before_total_while_deposits_are_pending() {
+        let mut l = Ledger::with_config(crate::LedgerConfig {
+            pending_deposits: true,
+            ..Default::default()
+        });
+        l.execute(Transaction::new_deposit(1, 1, ucur!(2)))
+            .unwrap()
+            .execute(Transaction::new_deposit(2, 2, ucur!(3)))
+            .unwrap()
+            .execute(Transaction::new_clear(2, 2))
+            .unwrap();
+        let mut buf = Vec::new();
+        super::write_csv(&l, &mut buf).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&buf),
+            "client,available,held,pending,total,locked
+1,0.0000,0.0000,2.0000,2.0000,false
+2,3.0000,0.0000,0.0000,3.0000,false
+"
+        );
+    }
+
+    #[test]
+    fn can_format_numbers() {
+        let format = |format: NumberFormat, value: &str| {
+            let mut buf = b"x".to_vec();
+            let value = value.parse::<crate::ICurrency>().unwrap();
+            format.write(&mut buf, value).unwrap();
+            String::from_utf8(buf[1..].to_vec()).unwrap()
+        };
+        assert_eq!(format(NumberFormat::Fixed(4), "1.5"), "1.5000");
+        assert_eq!(format(NumberFormat::Fixed(2), "-1.2345"), "-1.23");
+        assert_eq!(format(NumberFormat::Fixed(0), "7.25"), "7");
+        assert_eq!(format(NumberFormat::Trimmed, "1.5"), "1.5");
+        assert_eq!(format(NumberFormat::Trimmed, "-20"), "-20");
+        assert_eq!(format(NumberFormat::Trimmed, "0"), "0");
+        assert_eq!(format(NumberFormat::MinorUnits, "1.5"), "15000");
+        assert_eq!(format(NumberFormat::MinorUnits, "-0.0012"), "-12");
+        assert_eq!(format(NumberFormat::MinorUnits, "0"), "0");
+        assert_eq!(format(NumberFormat::MinorUnits, "100"), "1000000");
+    }
+
+    #[test]
+    fn parses_column_and_number_format_names() {
+        assert_eq!("open_disputes".parse(), Ok(Column::OpenDisputes));
+        assert_eq!("pending".parse(), Ok(Column::Pending));
+        assert!("balance".parse::<Column>().is_err());
+        assert_eq!("fixed:2".parse(), Ok(NumberFormat::Fixed(2)));
+        assert_eq!("fixed".parse(), Ok(NumberFormat::Fixed(4)));
+        assert_eq!("minor".parse(), Ok(NumberFormat::MinorUnits));
+        assert!("fixed:5".parse::<NumberFormat>().is_err());
+        assert!("fixed:".parse::<NumberFormat>().is_err());
+    }
 }
@@ -1,11 +1,8 @@
-use csv_diff::{csv::Csv, csv_diff::CsvByteDiff};
-use eyre::Context;
-use frost_snake_lib::execute;
-use glob::glob;
-use std::{
-    fs::File,
-    io::{BufReader, Cursor},
+use frost_snake_lib::{
+    execute, map_file, parse_csv, parse_parallel, Ledger, ParallelOptions, ParserConfig,
 };
+use glob::glob;
+use std::{fs::File, io::BufReader};
 
 #[test]
 fn run_test_files() {
@@ -21,35 +18,88 @@ fn run_test_files() {
     inputs.sort();
     outputs.sort();
 
-    let csv_byte_diff = CsvByteDiff::new().unwrap();
-
     for (input, output) in inputs.iter().zip(outputs.iter()) {
-        let file_name = input.file_name().unwrap();
-        let expected = std::fs::read(output).unwrap();
+        let expected = std::fs::read_to_string(output).unwrap();
         let mut actual = Vec::new();
         let mut reader = BufReader::new(File::open(input).unwrap());
 
         execute(&mut reader, &mut actual).unwrap();
+        let actual = String::from_utf8(actual).unwrap();
 
-        let mut diff = csv_byte_diff
-            .diff(
-                Csv::new(Cursor::new(&actual)),
-                Csv::new(Cursor::new(&expected)),
-            )
-            .with_context(|| {
-                format!(
-                    "Failed to diff csv ({file_name:?}), actual.len() = {} and expected.len() = {}",
-                    actual.len(),
-                    expected.len()
-                )
-            })
-            .unwrap();
-
-        diff.sort_by_line();
-
+        // the output is sorted by client id so the golden files are compared byte for byte
+        let mismatch = actual
+            .lines()
+            .zip(expected.lines())
+            .enumerate()
+            .find(|(_, (actual, expected))| actual != expected);
         assert!(
-            diff.as_slice().is_empty(),
-            "The input file {input:?} didn't match the output file {output:?}\n{diff:#?}"
+            actual == expected,
+            "The input file {input:?} didn't match the output file {output:?}, first difference {mismatch:?}, actual.len() = {} and expected.len() = {}",
+            actual.len(),
+            expected.len()
+        );
+    }
+}
+
+#[test]
+fn test_files_reconcile() {
+    for input in glob("tests/test-cases/*.input.csv").unwrap() {
+        let input = input.unwrap();
+        let mut ledger = Ledger::default();
+        for transaction in parse_csv(BufReader::new(File::open(&input).unwrap())).unwrap() {
+            ledger.execute(transaction.unwrap()).ok();
+        }
+        assert_eq!(ledger.reconcile(), Ok(()), "{input:?} doesn't reconcile");
+    }
+}
+
+#[test]
+fn parallel_parser_matches_sequential() {
+    let inputs = glob("tests/test-cases/*.input.csv")
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let config = ParserConfig::default();
+    let options = ParallelOptions {
+        threads: 4,
+        chunk_size: 4096,
+    };
+
+    for input in inputs {
+        let data = map_file(&input).unwrap();
+        let expected = parse_csv(&data[..]).unwrap().collect::<Vec<_>>();
+        let actual = parse_parallel(&data, &config, options)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            format!("{actual:?}"),
+            format!("{expected:?}"),
+            "The parallel parser didn't match the sequential parser for {input:?}"
+        );
+    }
+}
+
+#[cfg(feature = "fast-parser")]
+#[test]
+fn fast_parser_matches_regular_parser() {
+    let inputs = glob("tests/test-cases/*.input.csv")
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let config = ParserConfig::default();
+
+    for input in inputs {
+        let data = map_file(&input).unwrap();
+        let expected = parse_csv(&data[..]).unwrap().collect::<Vec<_>>();
+        let actual = frost_snake_lib::parse_fast(&data, &config)
+            .unwrap()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            format!("{actual:?}"),
+            format!("{expected:?}"),
+            "The fast parser didn't match the regular parser for {input:?}"
         );
     }
 }